@@ -0,0 +1,78 @@
+use color_eyre::Result;
+use roselite_core::Codec;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Name of the project configuration file kept in a site's root directory
+pub const PROJECT_CONFIG_FILENAME: &str = "roselite.toml";
+
+/// Project configuration from `roselite.toml`; command line flags take precedence over it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Manifest fields of the site
+    #[serde(default)]
+    pub site: SiteConfig,
+    /// Where the site is built and bundled
+    #[serde(default)]
+    pub build: BuildConfig,
+    /// Where and as whom the site is published
+    #[serde(default)]
+    pub publish: PublishConfig,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SiteConfig {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub developer: Option<String>,
+    pub entry: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildConfig {
+    /// Directory holding the built site, relative to the project root (default: the root itself)
+    pub dir: Option<PathBuf>,
+    /// Package file that `bundle` writes and `publish` reads, relative to the project root
+    pub output: Option<PathBuf>,
+    /// Codec to compress packages with, "zstd" or "gzip"
+    pub compression: Option<Codec>,
+    /// Compression level for the codec
+    pub level: Option<i32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PublishConfig {
+    /// Base domain of the universal gateway, as for --gateway-url
+    pub gateway_url: Option<String>,
+    /// Identity that signs the site's packages
+    pub identity: Option<String>,
+    /// DHT key of the published site, which `publish` then updates in place
+    pub site_key: Option<String>,
+}
+
+impl ProjectConfig {
+    /// Load the configuration of the project in `root`, or an empty one if it has none
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(PROJECT_CONFIG_FILENAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        toml::from_str(&content)
+            .map_err(|e| color_eyre::eyre::eyre!("Invalid {}: {}", path.display(), e))
+    }
+
+    /// Write the configuration to `roselite.toml` in `root`
+    pub fn save(&self, root: &Path) -> Result<PathBuf> {
+        let path = root.join(PROJECT_CONFIG_FILENAME);
+        std::fs::write(&path, toml::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+}
@@ -1,22 +1,28 @@
 use clap::{Parser, Subcommand};
 use color_eyre::Result;
 use roselite_core::{
-    package::{Package, PackageBuilder},
+    dht::DhtBackend,
+    package::{Package, PackageBuilder, SignaturePolicy, SigningRequest},
     store::{VeilidStore, AppStore},
+    crypto::{CryptoManager, KeyFormat},
+    error::CryptoError,
+    identity::{IdentityStore, KeyBackup, KeyStatement, DEFAULT_IDENTITY, IDENTITY_STORE_FILENAME},
+    keystore::{password_from_env, read_password_fd},
     types::{VeilUri, AppId},
+    veilid::VeilidConfig,
+    config_dir, Codec, KeyStore, PublicKey, RoseliteError, SecretKey,
 };
 use std::path::PathBuf;
 use url;
 use std::fs;
-use dirs;
-use dialoguer::Password;
+use dialoguer::{Confirm, Password};
+use std::cell::OnceCell;
 use std::env;
-use std::io::{self, BufRead, Write};
-use std::time::Duration;
-use std::collections::HashMap;
 
+mod config;
 mod gateway;
 
+use config::{ProjectConfig, PROJECT_CONFIG_FILENAME};
 use gateway::UniversalGateway;
 
 /// Roselite - P2P static site hosting via Veilid DHT
@@ -30,16 +36,32 @@ struct Cli {
 
     /// Base domain (host[:port]) of the universal gateway used to build shareable URLs
     /// (e.g. "example.com" or "localhost:8080"). Required for publish and access commands,
-    /// but optional for bundle.
+    /// unless roselite.toml sets it, but optional for bundle.
     #[arg(long = "gateway-url", global = true)]
     gateway_url: Option<String>,
+
+    /// Read the keystore password from this open file descriptor instead of prompting
+    /// (ROSELITE_PASSWORD and ROSELITE_PASSWORD_FD work too)
+    #[arg(long = "password-fd", global = true, value_name = "FD")]
+    password_fd: Option<i32>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Bundle a static site into a .veilidpkg package
+    /// Write a roselite.toml describing the site, so bundle and publish need no flags or prompts
+    Init {
+        /// Root directory of the site (default: current directory)
+        #[arg(value_name = "DIR")]
+        dir: Option<PathBuf>,
+
+        /// Replace an existing roselite.toml
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Bundle a static site into a .veilidpkg package, using roselite.toml in DIR for anything not given as a flag
     Bundle {
-        /// Source directory containing the static site
+        /// Root directory of the site (default: current directory)
         #[arg(value_name = "DIR")]
         source_dir: Option<PathBuf>,
         
@@ -70,13 +92,73 @@ enum Commands {
         /// Tags (comma-separated)
         #[arg(long)]
         tags: Option<String>,
+
+        /// Developer identity to sign with, created on first use (default: "default")
+        #[arg(long, value_name = "NAME")]
+        identity: Option<String>,
+
+        /// Leave the package unsigned and write a signing request next to it, for `roselite sign` on an offline machine
+        #[arg(long)]
+        unsigned: bool,
+
+        /// Developer key the offline signer holds (defaults to the identity's current key)
+        #[arg(long, value_name = "PUBKEY", requires = "unsigned")]
+        public_key: Option<String>,
+
+        /// Public key of a maintainer who approves releases (repeatable); the package only verifies once --threshold of them have signed
+        #[arg(long = "approver", value_name = "PUBKEY", requires = "threshold")]
+        approvers: Vec<String>,
+
+        /// How many approvers must sign the package
+        #[arg(long, value_name = "N", requires = "approvers")]
+        threshold: Option<usize>,
+
+        /// Build a byte-identical package from the same files and key, timestamped from SOURCE_DATE_EPOCH
+        #[arg(long)]
+        deterministic: bool,
+
+        /// Package files that .gitignore excludes (.roseliteignore still applies)
+        #[arg(long)]
+        no_gitignore: bool,
+
+        /// List the files that would be packaged and their total size, without building
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Codec to compress the package with: zstd, or gzip as packages used to be (default: zstd)
+        #[arg(long, value_name = "CODEC")]
+        compression: Option<Codec>,
+
+        /// Compression level (zstd: 1 to 22, gzip: 0 to 9); higher levels make smaller packages, so fewer DHT subkeys
+        #[arg(long, value_name = "LEVEL", allow_negative_numbers = true)]
+        level: Option<i32>,
+    },
+
+    /// Sign a package bundled with --unsigned, or co-sign one as an approver, without rebuilding it;
+    /// needs no network, so release keys can stay offline
+    Sign {
+        /// Package file to sign
+        #[arg(value_name = "PACKAGE")]
+        package: PathBuf,
+
+        /// Identity whose key signs the package (default: "default")
+        #[arg(long, value_name = "NAME")]
+        key: Option<String>,
+
+        /// Signing request written by `bundle --unsigned`; the package must match its digest
+        #[arg(long, value_name = "FILE")]
+        request: Option<PathBuf>,
+
+        /// Write the signed package here instead of replacing the input
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
     },
     
     /// Publish a package to the Veilid DHT for P2P hosting
     Publish {
-        /// Package file to publish
+        /// Package file to publish (default: the bundle output of roselite.toml in the current directory)
         #[arg(value_name = "PACKAGE")]
-        package: PathBuf,
+        package: Option<PathBuf>,
         
         /// Show all available gateways in output
         #[arg(short, long)]
@@ -85,14 +167,133 @@ enum Commands {
         /// Open the primary URL in browser after publishing
         #[arg(long)]
         open: bool,
+
+        /// Update an existing site in place, keeping its DHT key (requires its owner key in the local keystore)
+        #[arg(long, value_name = "KEY")]
+        update: Option<String>,
+
+        /// Continue an interrupted publish of this package, reusing the records it already created
+        #[arg(long, conflicts_with = "update")]
+        resume: bool,
+
+        /// Encrypt the site so only holders of the share link (which carries the key) can read it
+        #[arg(long, conflicts_with_all = ["update", "resume"])]
+        private: bool,
+
+        /// Encrypt the site for a recipient's X25519 public key (repeatable); only listed recipients can read it
+        #[arg(long = "recipient", value_name = "PUBKEY", conflicts_with_all = ["update", "resume"])]
+        recipients: Vec<String>,
+
+        /// Refuse the package unless it is signed by this identity or public key
+        #[arg(long, value_name = "NAME_OR_PUBKEY")]
+        identity: Option<String>,
     },
     
     /// Access a site directly from a DHT key or gateway URL
     Access {
-        /// DHT key or gateway URL of the site to access
+        /// DHT key, veil:///app/<KEY>[/<VERSION>][#key=<SITE_KEY>] URI, or gateway URL of the site to access
         #[arg(value_name = "KEY_OR_URL")]
         key_or_url: String,
     },
+
+    /// Remove a published site and all of its versions from the Veilid DHT
+    Unpublish {
+        /// DHT key of the site to remove (requires its owner key in the local keystore)
+        #[arg(value_name = "KEY")]
+        key: String,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Replace the key of a private site and set who can read it, locking out everyone else
+    Rekey {
+        /// DHT key of the private site (requires its owner key in the local keystore)
+        #[arg(value_name = "KEY")]
+        key: String,
+
+        /// X25519 public key of a recipient to keep or grant access (repeatable); without any, a new share link is issued
+        #[arg(long = "recipient", value_name = "PUBKEY")]
+        recipients: Vec<String>,
+    },
+
+    /// Show this machine's recipient public key, creating it on first use, so publishers can grant access
+    RecipientKey,
+
+    /// Manage package signing identities and DHT record owner keys
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// Generate a new signing identity
+    Create {
+        /// Name to select the identity with, e.g. in `bundle --identity`
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Use a Veilid typed key, which can also own the DHT records of the sites it signs
+        #[arg(long)]
+        veilid: bool,
+    },
+
+    /// List signing identities and record owner keys with their fingerprints
+    List,
+
+    /// Write every key to a backup file sealed under a passphrase
+    Export {
+        /// Backup file to write
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+
+    /// Restore keys from a backup file, keeping any key already present
+    Import {
+        /// Backup file written by `keys export`
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+
+    /// Delete a signing identity, or the owner key of a DHT record
+    Delete {
+        /// Identity name or DHT record key
+        #[arg(value_name = "NAME_OR_KEY")]
+        target: String,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Replace an identity's signing key, publishing a statement signed by the old key that endorses the new one
+    Rotate {
+        /// Identity to rotate
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+
+    /// Publish a statement revoking one of an identity's keys, so gateways stop trusting what it signed
+    Revoke {
+        /// Identity the key belongs to
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Retired public key to revoke (defaults to the identity's current key)
+        #[arg(long, value_name = "PUBKEY")]
+        key: Option<String>,
+
+        /// Why the key is revoked, e.g. "compromised"
+        #[arg(long)]
+        reason: Option<String>,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
 }
 
 #[tokio::main]
@@ -105,8 +306,12 @@ async fn main() -> Result<()> {
         .init();
 
     let cli = Cli::parse();
+    let password = KeystorePassword::new(cli.password_fd.map(read_password_fd).transpose()?);
 
     match cli.command {
+        Commands::Init { dir, force } => {
+            cmd_init(dir.unwrap_or_else(|| env::current_dir().unwrap()), force)?;
+        }
         Commands::Bundle { 
             source_dir, 
             output, 
@@ -115,40 +320,160 @@ async fn main() -> Result<()> {
             description, 
             developer, 
             entry, 
-            tags 
+            tags,
+            identity,
+            unsigned,
+            public_key,
+            approvers,
+            threshold,
+            deterministic,
+            no_gitignore,
+            dry_run,
+            compression,
+            level,
         } => {
+            let source_dir = source_dir.unwrap_or_else(|| env::current_dir().unwrap());
+            let project = ProjectConfig::load(&source_dir)?;
             cmd_bundle(
                 source_dir, 
+                project,
                 output, 
                 name, 
                 version, 
                 description, 
                 developer, 
                 entry, 
-                tags
+                tags,
+                identity,
+                unsigned,
+                public_key,
+                approvers,
+                threshold,
+                deterministic,
+                no_gitignore,
+                dry_run,
+                compression,
+                level,
+                &password,
             ).await?;
         }
-        Commands::Publish { package, gateways, open } => {
-            ensure_password()?;
-            let gw = cli.gateway_url.clone().ok_or_else(|| {
+        Commands::Sign { package, key, request, output } => {
+            cmd_sign(package, key, request, output, &password).await?;
+        }
+        Commands::Publish { package, gateways, open, update, resume, private, recipients, identity } => {
+            let project = ProjectConfig::load(&env::current_dir()?)?;
+            let gw = cli.gateway_url.clone().or_else(|| project.publish.gateway_url.clone()).ok_or_else(|| {
                 color_eyre::eyre::eyre!("--gateway-url must be provided for publish")
             })?;
-            cmd_publish(package, gateways, open, gw).await?;
+            let package = package
+                .or_else(|| project.build.output.clone())
+                .or_else(|| project.site.name.as_deref().map(default_package_path))
+                .ok_or_else(|| color_eyre::eyre::eyre!("No package given and no {} to find it from", PROJECT_CONFIG_FILENAME))?;
+            let identity = identity.or_else(|| project.publish.identity.clone());
+            // The configured site is updated in place; new private or recipient-scoped sites are not
+            let update = match update {
+                None if !resume && !private && recipients.is_empty() => project.publish.site_key.clone(),
+                update => update,
+            };
+            let mut store = connect_store(&password).await?;
+            add_identity_keys(&mut store, &password)?;
+            cmd_publish(store, package, gateways, open, update, resume, private, recipients, identity, gw, &password).await?;
         }
         Commands::Access { key_or_url } => {
-            ensure_password()?;
-            let gw = cli.gateway_url.clone().ok_or_else(|| {
+            let project = ProjectConfig::load(&env::current_dir()?)?;
+            let gw = cli.gateway_url.clone().or(project.publish.gateway_url).ok_or_else(|| {
                 color_eyre::eyre::eyre!("--gateway-url must be provided for access")
             })?;
-            cmd_access(key_or_url, gw).await?;
+            let store = connect_store(&password).await?;
+            cmd_access(store, key_or_url, gw).await?;
+        }
+        Commands::Unpublish { key, yes } => {
+            let store = connect_store(&password).await?;
+            cmd_unpublish(store, key, yes).await?;
+        }
+        Commands::Rekey { key, recipients } => {
+            let mut store = connect_store(&password).await?;
+            add_identity_keys(&mut store, &password)?;
+            cmd_rekey(store, key, recipients).await?;
+        }
+        Commands::RecipientKey => {
+            cmd_recipient_key(&password)?;
+        }
+        Commands::Keys { command } => {
+            cmd_keys(command, &password).await?;
         }
     }
 
     Ok(())
 }
 
+/// Print the files a bundle would contain, with their sizes and the total
+fn list_bundle_files(builder: &PackageBuilder) -> Result<()> {
+    let files = builder.source_files()?;
+    let mut total_size = 0;
+    for (path, full_path) in &files {
+        let size = std::fs::metadata(full_path)?.len();
+        total_size += size;
+        println!("{:>10}  {}", size, path);
+    }
+    println!("📦 {} files, {} bytes (plus the generated manifest and signature)", files.len(), total_size);
+    Ok(())
+}
+
+/// Package file written for a site when no output path is given
+fn default_package_path(name: &str) -> PathBuf {
+    PathBuf::from(format!("{}.veilidpkg", name.replace(' ', "-").to_lowercase()))
+}
+
+fn cmd_init(root: PathBuf, force: bool) -> Result<()> {
+    use dialoguer::Input;
+
+    let existing = root.join(PROJECT_CONFIG_FILENAME);
+    if existing.exists() && !force {
+        return Err(color_eyre::eyre::eyre!("{} already exists; pass --force to replace it", existing.display()));
+    }
+
+    // Optional answers are left out of the file when empty
+    fn optional(answer: String) -> Option<String> {
+        Some(answer.trim().to_string()).filter(|answer| !answer.is_empty())
+    }
+
+    let default_name = fs::canonicalize(&root).ok()
+        .and_then(|path| path.file_name().map(|name| name.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "my-site".to_string());
+    let name: String = Input::new().with_prompt("Site name").default(default_name).interact_text()?;
+    let version: String = Input::new().with_prompt("Version").default("1.0.0".to_string()).interact_text()?;
+    let description: String = Input::new().with_prompt("Description").allow_empty(true).interact_text()?;
+    let developer: String = Input::new().with_prompt("Developer").allow_empty(true).interact_text()?;
+    let entry: String = Input::new().with_prompt("Entry point").default("index.html".to_string()).interact_text()?;
+    let tags: String = Input::new().with_prompt("Tags (comma-separated)").allow_empty(true).interact_text()?;
+    let dir: String = Input::new().with_prompt("Build output directory").default(".".to_string()).interact_text()?;
+    let gateway_url: String = Input::new().with_prompt("Gateway URL").allow_empty(true).interact_text()?;
+    let identity: String = Input::new().with_prompt("Signing identity").default(DEFAULT_IDENTITY.to_string()).interact_text()?;
+    let site_key: String = Input::new().with_prompt("Site key to update (empty for a new site)").allow_empty(true).interact_text()?;
+
+    let mut project = ProjectConfig::default();
+    project.build.output = Some(default_package_path(&name));
+    project.site.name = Some(name);
+    project.site.version = Some(version);
+    project.site.description = optional(description);
+    project.site.developer = optional(developer);
+    project.site.entry = Some(entry);
+    project.site.tags = tags.split(',').filter_map(|tag| optional(tag.to_string())).collect();
+    project.build.dir = Some(PathBuf::from(dir)).filter(|dir| dir != std::path::Path::new("."));
+    project.publish.gateway_url = optional(gateway_url);
+    project.publish.identity = Some(identity);
+    project.publish.site_key = optional(site_key);
+
+    let path = project.save(&root)?;
+    println!("✅ Wrote {}", path.display());
+    println!("💡 Bundle with: roselite bundle, then publish with: roselite publish");
+    Ok(())
+}
+
 async fn cmd_bundle(
-    source_dir: Option<PathBuf>,
+    source_dir: PathBuf,
+    project: ProjectConfig,
     output: Option<PathBuf>,
     name: Option<String>,
     version: Option<String>,
@@ -156,9 +481,49 @@ async fn cmd_bundle(
     developer: Option<String>,
     entry: Option<String>,
     tags: Option<String>,
+    identity: Option<String>,
+    unsigned: bool,
+    public_key: Option<String>,
+    approvers: Vec<String>,
+    threshold: Option<usize>,
+    deterministic: bool,
+    no_gitignore: bool,
+    dry_run: bool,
+    compression: Option<Codec>,
+    level: Option<i32>,
+    password: &KeystorePassword,
 ) -> Result<()> {
-    let source_dir = source_dir.unwrap_or_else(|| std::env::current_dir().unwrap());
-    
+    // Flags take precedence over roselite.toml; its paths are relative to the site root
+    let output = output.or_else(|| project.build.output.as_ref().map(|output| source_dir.join(output)));
+    let project_file = source_dir.join(PROJECT_CONFIG_FILENAME);
+    let source_dir = match &project.build.dir {
+        Some(dir) => source_dir.join(dir),
+        None => source_dir,
+    };
+    let site = project.site;
+    let name = name.or(site.name);
+    let version = version.or(site.version);
+    let description = description.or(site.description);
+    let developer = developer.or(site.developer);
+    let entry = entry.or(site.entry);
+    let tags = tags
+        .map(|tags| tags.split(',').map(|tag| tag.trim().to_string()).collect())
+        .or_else(|| Some(site.tags).filter(|tags| !tags.is_empty()));
+    let identity = identity.or(project.publish.identity);
+    let compression = compression.or(project.build.compression);
+    let level = level.or(project.build.level);
+
+    // Neither the project config nor an earlier package belongs in the site
+    if dry_run {
+        let mut builder = PackageBuilder::new(name.clone().unwrap_or_default(), &source_dir)
+            .respect_gitignore(!no_gitignore)
+            .exclude(&project_file);
+        if let Some(output) = output.or_else(|| name.as_deref().map(default_package_path)) {
+            builder = builder.exclude(output);
+        }
+        return list_bundle_files(&builder);
+    }
+
     println!("🏗️  Bundling site from: {}", source_dir.display());
     
     // Interactive prompts for missing information
@@ -177,7 +542,17 @@ async fn cmd_bundle(
     
     // Build package
     let mut builder = PackageBuilder::new(name.clone(), &source_dir)
-        .version(version.clone());
+        .version(version.clone())
+        .deterministic(deterministic)
+        .respect_gitignore(!no_gitignore);
+
+    if let Some(codec) = compression {
+        builder = builder.compression(codec);
+    }
+    
+    if let Some(level) = level {
+        builder = builder.compression_level(level);
+    }
     
     if let Some(desc) = description {
         builder = builder.description(desc);
@@ -189,32 +564,194 @@ async fn cmd_bundle(
     
     builder = builder.entry(entry);
     
-    if let Some(tags_str) = tags {
-        let tag_list: Vec<String> = tags_str.split(',').map(|s| s.trim().to_string()).collect();
+    if let Some(tag_list) = tags {
         builder = builder.tags(tag_list);
     }
     
-    let package = builder.build().await?;
-    
+    if let Some(threshold) = threshold {
+        let keys = approvers.iter().map(|key| key.parse()).collect::<roselite_core::Result<Vec<PublicKey>>>()?;
+        builder = builder.signature_policy(SignaturePolicy { threshold, keys });
+    }
+
     // Output path
-    let output_path = output.unwrap_or_else(|| {
-        PathBuf::from(format!("{}.veilidpkg", name.replace(' ', "-").to_lowercase()))
-    });
+    let output_path = output.unwrap_or_else(|| default_package_path(&name));
+    builder = builder.exclude(&project_file).exclude(&output_path);
+
+    let identity_name = identity.unwrap_or_else(|| DEFAULT_IDENTITY.to_string());
+    if unsigned {
+        // Only the public key is needed here; the secret stays with the offline signer
+        let public_key: PublicKey = match public_key {
+            Some(key) => key.parse()?,
+            None => open_identity_store(password)?.get(&identity_name)
+                .ok_or_else(|| color_eyre::eyre::eyre!("No identity named '{}'; pass --public-key", identity_name))?
+                .public_key,
+        };
+        let package = builder
+            .identity(public_key.to_string())
+            .public_key(public_key)
+            .build()
+            .await?;
+        let request = package.signing_request(&CryptoManager::new()?)?;
+        let request_path = signing_request_path(&output_path);
+        tokio::fs::write(&output_path, &package.content).await?;
+        tokio::fs::write(&request_path, serde_json::to_vec_pretty(&request)?).await?;
+
+        println!("✅ Unsigned package created: {}", output_path.display());
+        println!("📝 Signing request: {}", request_path.display());
+        println!("   Digest: {}", request.digest);
+        println!("💡 On the signing machine: roselite sign {} --request {}", output_path.display(), request_path.display());
+        return Ok(());
+    }
+
+    // Sign with a persistent identity so every release carries the same publisher key
+    let identity = open_identity_store(password)?.get_or_create(&identity_name)?;
+    builder = builder
+        .identity(identity.record_key.clone().unwrap_or_else(|| identity.public_key.to_string()))
+        .keypair(identity.public_key, identity.secret_key.clone());
+
+    let package = builder.build().await?;
     
     // Save package to file
     tokio::fs::write(&output_path, &package.content).await?;
     
     println!("✅ Package created: {}", output_path.display());
-    println!("📦 Size: {} bytes", std::fs::metadata(&output_path)?.len());
+    println!("📦 Size: {} bytes ({})", std::fs::metadata(&output_path)?.len(), package.compression()?);
+    if deterministic {
+        println!("🧾 Package hash: {}", CryptoManager::new()?.hash(&package.content)?);
+    }
+    println!("🔏 Signed by identity '{}': {}", identity.name, identity.public_key);
+    if let Some(policy) = &package.manifest.signature_policy {
+        println!("⏳ Needs {} of {} approvers to sign: roselite sign {} --key <NAME>",
+            policy.threshold, policy.keys.len(), output_path.display());
+    }
     
     Ok(())
 }
 
-async fn cmd_publish(package_path: PathBuf, gateways: bool, open: bool, gateway_domain: String) -> Result<()> {
+/// Load a package whose detached signature verifies, made by `identity` when one is given
+async fn load_verified_package(package_path: &std::path::Path, identity: Option<String>, password: &KeystorePassword) -> Result<Package> {
+    let package = Package::from_file(package_path).await?;
+    if !package.verify_signature(&CryptoManager::new()?)? {
+        return Err(color_eyre::eyre::eyre!(
+            "{} is not signed or its signature does not verify; sign it with `roselite sign`", package_path.display()
+        ));
+    }
+
+    if let Some(identity) = identity {
+        let expected = match identity.parse::<PublicKey>() {
+            Ok(key) => key,
+            Err(_) => open_identity_store(password)?.get(&identity)
+                .ok_or_else(|| color_eyre::eyre::eyre!("No identity named '{}'", identity))?
+                .public_key,
+        };
+        if package.manifest.public_key != Some(expected) {
+            return Err(color_eyre::eyre::eyre!(
+                "{} is signed by {}, not by {}",
+                package_path.display(),
+                package.manifest.public_key.map(|key| key.to_string()).unwrap_or_default(),
+                identity
+            ));
+        }
+    }
+
+    Ok(package)
+}
+
+/// Where `bundle --unsigned` writes the signing request of a package
+fn signing_request_path(package_path: &std::path::Path) -> PathBuf {
+    let mut path = package_path.as_os_str().to_owned();
+    path.push(".sigreq");
+    PathBuf::from(path)
+}
+
+async fn cmd_sign(
+    package_path: PathBuf,
+    key: Option<String>,
+    request: Option<PathBuf>,
+    output: Option<PathBuf>,
+    password: &KeystorePassword,
+) -> Result<()> {
+    let crypto = CryptoManager::new()?;
+    let identity_name = key.unwrap_or_else(|| DEFAULT_IDENTITY.to_string());
+    let identity = open_identity_store(password)?.get(&identity_name).cloned()
+        .ok_or_else(|| color_eyre::eyre::eyre!("No identity named '{}'", identity_name))?;
+
+    let mut package = Package::from_file(&package_path).await?;
+    let expected = package.signing_request(&crypto)?;
+    println!("📦 Package: {} v{}", expected.name, expected.version);
+    println!("   Digest: {}", expected.digest);
+
+    // The package must be exactly what was sent for signing
+    if let Some(request_path) = request {
+        let request: SigningRequest = serde_json::from_slice(&fs::read(&request_path)?)?;
+        if request.digest != expected.digest || request.public_key != expected.public_key {
+            return Err(color_eyre::eyre::eyre!(
+                "{} does not match the signing request {}", package_path.display(), request_path.display()
+            ));
+        }
+    }
+
+    package.sign(&crypto, &identity.secret_key)?;
+    let output_path = output.unwrap_or(package_path);
+    tokio::fs::write(&output_path, &package.content).await?;
+    if identity.public_key == expected.public_key {
+        println!("🔏 Signed by identity '{}': {}", identity.name, identity.public_key);
+    } else {
+        println!("🤝 Co-signed by identity '{}': {}", identity.name, identity.public_key);
+    }
+
+    if package.verify_signature(&crypto)? {
+        println!("✅ Signed package written to {}", output_path.display());
+    } else {
+        println!("⏳ Package written to {}; it still needs more signatures before it can be published", output_path.display());
+    }
+    Ok(())
+}
+
+/// Connect to the Veilid network and open a store on top of it, keeping keys in the keystore
+async fn connect_store(password: &KeystorePassword) -> Result<VeilidStore> {
+    let keystore = open_keystore(password)?;
+    println!("🌐 Connecting to Veilid DHT...");
+    let store = VeilidStore::connect(keystore, VeilidConfig::default()).await.map_err(|e| {
+        println!("❌ Failed to connect to Veilid network: {}", e);
+        color_eyre::eyre::eyre!("Unable to establish Veilid connection")
+    })?;
+    println!("✅ Successfully connected to Veilid network!");
+    Ok(store)
+}
+
+/// Hand the store the signing keys of every local identity for this session only. Lookup
+/// records are signed with the developer key the package declares, so its identity must be local.
+fn add_identity_keys<D: DhtBackend>(store: &mut VeilidStore<D>, password: &KeystorePassword) -> Result<()> {
+    for identity in open_identity_store(password)?.list() {
+        store.add_signing_key(identity.secret_key.clone());
+    }
+    Ok(())
+}
+
+async fn cmd_publish<D: DhtBackend>(
+    mut store: VeilidStore<D>,
+    package_path: PathBuf,
+    gateways: bool,
+    open: bool,
+    update: Option<String>,
+    resume: bool,
+    private: bool,
+    recipients: Vec<String>,
+    identity: Option<String>,
+    gateway_domain: String,
+    password: &KeystorePassword,
+) -> Result<()> {
     println!("📤 Publishing package: {}", package_path.display());
     
-    // Load package
-    let package = Package::from_file(&package_path).await?;
+    // Load package, refusing it unless its signature verifies against the expected identity
+    let package = match load_verified_package(&package_path, identity, password).await {
+        Ok(package) => package,
+        Err(e) => {
+            let _ = store.shutdown().await;
+            return Err(e);
+        }
+    };
     
     println!("📦 Package: {} v{} by {}", 
         package.manifest.name, 
@@ -222,24 +759,55 @@ async fn cmd_publish(package_path: PathBuf, gateways: bool, open: bool, gateway_
         package.manifest.developer
     );
     
-    // Initialize Veilid connection and store
-    println!("🌐 Connecting to Veilid DHT...");
-    let mut store = VeilidStore::new().await.map_err(|e| {
-        println!("❌ Failed to connect to Veilid network: {}", e);
-        color_eyre::eyre::eyre!("Unable to establish Veilid connection")
-    })?;
-    println!("✅ Successfully connected to Veilid network!");
-    
+    if let Some(progress) = store.journal().pending().filter(|_| !resume) {
+        println!("⚠️  Discarding an interrupted publish of {} v{} started {}",
+            progress.app_name, progress.version, progress.started_at.format("%Y-%m-%d %H:%M:%S UTC"));
+    }
+
     // Publish to Veilid DHT
-    println!("📡 Publishing to Veilid DHT...");
+    let scoped = !recipients.is_empty();
+    let publish_result = match &update {
+        _ if resume => {
+            println!("📡 Resuming interrupted publish to Veilid DHT...");
+            store.resume(package).await
+        }
+        Some(key) => {
+            println!("📡 Updating site {} in Veilid DHT...", key);
+            store.update(&AppId(key.clone()), package).await
+        }
+        None if !recipients.is_empty() => {
+            println!("🔒 Publishing site encrypted for {} recipient(s) to Veilid DHT...", recipients.len());
+            store.publish_to_recipients(package, recipients).await
+        }
+        None if private => {
+            println!("🔒 Publishing encrypted private site to Veilid DHT...");
+            store.publish_private(package).await
+        }
+        None => {
+            println!("📡 Publishing to Veilid DHT...");
+            store.publish(package).await
+        }
+    };
     
-    let result = match store.publish(package).await {
+    let result = match publish_result {
         Ok((veil_uri, updated_package)) => {
             println!("✅ Package published successfully!");
+            if veil_uri.key.is_some() {
+                println!("🔒 Private site: the share link below carries the decryption key after '#'");
+                println!("   Only share it with people who should read the site; gateways need the key via --site-key");
+            } else if scoped {
+                println!("🔒 Recipient-scoped site: only the listed recipients can open it, using their own recipient key");
+                println!("   Change who has access with: roselite rekey {} --recipient <PUBKEY>...", veil_uri.app_id.0);
+            }
             
             // Get DHT key
             let dht_key = veil_uri.app_id.0.clone();
             println!("🔗 DHT key: {}", dht_key);
+            if update.is_some() {
+                println!("♻️  Site updated in place; existing DNS TXT records keep working");
+            } else if !resume {
+                println!("💡 Set site_key = \"{}\" under [publish] in {} to update this site on the next publish", dht_key, PROJECT_CONFIG_FILENAME);
+            }
             println!("💡 Configure your domain with a DNS TXT record:\n    example.com. IN TXT \"veilid-app={}\"", dht_key);
             println!("👉 Then point a CNAME to your gateway ({}).", gateway_domain);
             
@@ -305,7 +873,11 @@ async fn cmd_publish(package_path: PathBuf, gateways: bool, open: bool, gateway_
         },
         Err(e) => {
             println!("❌ Failed to publish package: {}", e);
-            println!("💡 Try again later or check your network connection");
+            if store.journal().pending().is_some() {
+                println!("💡 Progress was saved; continue with: roselite publish {} --resume", package_path.display());
+            } else {
+                println!("💡 Try again later or check your network connection");
+            }
             Err(e.into())
         }
     };
@@ -350,10 +922,26 @@ fn open_url(url: &str) -> Result<()> {
     Ok(())
 }
 
-async fn cmd_access(key_or_url: String, gateway_domain: String) -> Result<()> {
+async fn cmd_access<D: DhtBackend>(mut store: VeilidStore<D>, key_or_url: String, gateway_domain: String) -> Result<()> {
     println!("🌐 Accessing site: {}", key_or_url);
     
-    let app_id = if key_or_url.starts_with("https://") || key_or_url.starts_with("http://") {
+    let mut pinned_version = None;
+    let app_id = if key_or_url.starts_with("veil://") {
+        // Veil URI, optionally pinned to a version
+        let uri: VeilUri = match key_or_url.parse() {
+            Ok(uri) => uri,
+            Err(e) => {
+                let _ = store.shutdown().await;
+                return Err(e.into());
+            }
+        };
+        pinned_version = uri.version;
+        if let Some(key) = uri.key {
+            println!("🔑 Using the site key from the link to decrypt a private site");
+            store.add_site_key(&uri.app_id, key);
+        }
+        uri.app_id
+    } else if key_or_url.starts_with("https://") || key_or_url.starts_with("http://") {
         // Extract domain and look up TXT record
         println!("🔍 Looking up DNS TXT record for domain...");
         println!("💡 In a complete implementation, this would:");
@@ -361,7 +949,13 @@ async fn cmd_access(key_or_url: String, gateway_domain: String) -> Result<()> {
         println!("   • Use that as the DHT lookup key");
         
         // For now, extract from URL path or use domain as app ID
-        let url = url::Url::parse(&key_or_url).map_err(|e| color_eyre::eyre::eyre!("Invalid URL: {}", e))?;
+        let url = match url::Url::parse(&key_or_url) {
+            Ok(url) => url,
+            Err(e) => {
+                let _ = store.shutdown().await;
+                return Err(color_eyre::eyre::eyre!("Invalid URL: {}", e));
+            }
+        };
         let domain = url.host_str().unwrap_or("unknown");
         println!("📋 Domain: {}", domain);
         
@@ -374,13 +968,6 @@ async fn cmd_access(key_or_url: String, gateway_domain: String) -> Result<()> {
     
     println!("🔍 DHT Lookup Key: {}", app_id.0);
     
-    // Initialize Veilid store to fetch site data
-    println!("📡 Connecting to Veilid DHT...");
-    let mut store = VeilidStore::new().await.map_err(|e| {
-        println!("❌ Failed to connect to Veilid network: {}", e);
-        color_eyre::eyre::eyre!("Unable to establish Veilid connection")
-    })?;
-    
     let result = async {
         // Try to fetch site from Veilid DHT
         match store.get_app(&app_id).await? {
@@ -391,6 +978,15 @@ async fn cmd_access(key_or_url: String, gateway_domain: String) -> Result<()> {
                 println!("📈 Version: {}", app_info.version);
                 println!("📝 Description: {}", app_info.description);
                 
+                if let Ok(versions) = store.list_versions(&app_id).await {
+                    if versions.len() > 1 {
+                        println!("🗂️  Published versions:");
+                        for published in &versions {
+                            println!("   • {} ({})", published.version, published.published_at.format("%Y-%m-%d %H:%M UTC"));
+                        }
+                    }
+                }
+                
                 // Show DNS integration info
                 println!("\n🌐 DNS Integration:");
                 println!("   📋 DHT Key: {}", app_id.0);
@@ -409,7 +1005,10 @@ async fn cmd_access(key_or_url: String, gateway_domain: String) -> Result<()> {
                 }
                 
                 // Try to download package and show technical details
-                let uri = VeilUri::new(app_id.clone(), Some(app_info.version.clone()));
+                let uri = VeilUri::new(app_id.clone(), pinned_version.clone());
+                if let Some(version) = &pinned_version {
+                    println!("\n📌 Pinned to version {}", version);
+                }
                 match store.download(&uri).await {
                     Ok(package) => {
                         println!("\n📥 Successfully downloaded package from DHT");
@@ -484,29 +1083,303 @@ async fn cmd_access(key_or_url: String, gateway_domain: String) -> Result<()> {
     result
 }
 
-fn ensure_password() -> Result<()> {
-    if env::var("ROSELITE_PASSWORD").is_ok() {
-        return Ok(());
+async fn cmd_unpublish<D: DhtBackend>(mut store: VeilidStore<D>, key: String, yes: bool) -> Result<()> {
+    let app_id = AppId(key);
+    println!("🗑️  Unpublishing site: {}", app_id.0);
+
+    let result = async {
+        let app_info = store.get_app(&app_id).await?
+            .ok_or_else(|| color_eyre::eyre::eyre!("Site {} was not found in the Veilid DHT", app_id.0))?;
+        let versions = store.list_versions(&app_id).await?;
+
+        println!("📦 {} v{} by {}", app_info.name, app_info.version, app_info.developer);
+        println!("🗂️  {} published version(s) will be removed", versions.len());
+
+        let confirmed = yes || Confirm::new()
+            .with_prompt(format!("Permanently remove {} and all of its versions?", app_info.name))
+            .default(false)
+            .interact()?;
+        if !confirmed {
+            println!("❎ Unpublish cancelled");
+            return Ok(());
+        }
+
+        let deleted = store.unpublish(&app_id).await?;
+        println!("✅ Removed {} DHT records", deleted);
+        println!("💡 Remove any DNS TXT records pointing at {}", app_id.0);
+        Ok(())
+    }.await;
+
+    if let Err(e) = &result {
+        println!("❌ Failed to unpublish site: {}", e);
     }
 
-    let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from(".roselite-config"));
-    let file_path = config_dir.join("roselite").join("password.txt");
-    if let Some(parent) = file_path.parent() {
-        fs::create_dir_all(parent)?;
+    println!("\n🔄 Disconnecting from Veilid network...");
+    if let Err(e) = store.shutdown().await {
+        println!("⚠️  Warning: Failed to shutdown cleanly: {}", e);
+    } else {
+        println!("✅ Disconnected successfully");
     }
 
-    let password = if file_path.exists() {
-        fs::read_to_string(&file_path)?.trim().to_string()
+    result
+}
+
+async fn cmd_rekey<D: DhtBackend>(mut store: VeilidStore<D>, key: String, recipients: Vec<String>) -> Result<()> {
+    let app_id = AppId(key);
+    println!("🔑 Re-keying private site: {}", app_id.0);
+
+    let result = store.rekey(&app_id, recipients.clone()).await;
+    match &result {
+        Ok(veil_uri) if recipients.is_empty() => {
+            println!("✅ Site re-keyed; earlier share links no longer open it");
+            println!("🔗 New share link: {}", veil_uri);
+        }
+        Ok(_) => {
+            println!("✅ Site re-keyed for {} recipient(s); everyone else has lost access", recipients.len());
+        }
+        Err(e) => println!("❌ Failed to re-key site: {}", e),
+    }
+
+    println!("\n🔄 Disconnecting from Veilid network...");
+    if let Err(e) = store.shutdown().await {
+        println!("⚠️  Warning: Failed to shutdown cleanly: {}", e);
     } else {
-        let pass = Password::new()
-            .with_prompt("Set a Veilid keystore password (leave blank to store unencrypted)")
-            .with_confirmation("Confirm", "Passwords do not match")
-            .allow_empty_password(true)
-            .interact()?;
-        fs::write(&file_path, &pass)?;
-        pass
+        println!("✅ Disconnected successfully");
+    }
+
+    result.map(|_| ()).map_err(Into::into)
+}
+
+fn cmd_recipient_key(password: &KeystorePassword) -> Result<()> {
+    let mut keystore = open_keystore(password)?;
+    let recipient = match keystore.recipient_public_keys().into_iter().next() {
+        Some(recipient) => recipient,
+        None => {
+            println!("🔑 Creating a recipient key for this machine");
+            keystore.create_recipient_key()?
+        }
     };
 
-    env::set_var("ROSELITE_PASSWORD", password);
+    println!("📬 Recipient key: {}", recipient);
+    println!("💡 Share it with publishers so they can add you with --recipient {}", recipient);
     Ok(())
-} 
\ No newline at end of file
+}
+
+async fn cmd_keys(command: KeysCommand, password: &KeystorePassword) -> Result<()> {
+    let crypto = CryptoManager::new()?;
+
+    match command {
+        KeysCommand::Create { name, veilid } => {
+            let format = if veilid { KeyFormat::Veilid } else { KeyFormat::Hex };
+            let identity = open_identity_store(password)?.create_in(&name, format)?;
+            println!("🔑 Created identity '{}'", identity.name);
+            println!("   Public key:  {}", identity.public_key);
+            println!("   Fingerprint: {}", crypto.key_fingerprint(&identity.public_key));
+            println!("💡 Sign packages with it: roselite bundle --identity {}", identity.name);
+        }
+        KeysCommand::List => {
+            let identities = open_identity_store(password)?;
+            println!("🔏 Signing identities:");
+            if identities.list().is_empty() {
+                println!("   (none)");
+            }
+            for identity in identities.list() {
+                println!("   {}  {}  created {}",
+                    identity.name, crypto.key_fingerprint(&identity.public_key), identity.created_at.format("%Y-%m-%d"));
+                if let Some(record_key) = &identity.record_key {
+                    println!("      identity record: {}", record_key);
+                }
+                for retired in &identity.retired_keys {
+                    println!("      retired {}  on {}", crypto.key_fingerprint(&retired.public_key), retired.retired_at.format("%Y-%m-%d"));
+                }
+            }
+
+            let keystore = open_keystore(password)?;
+            println!("\n🗝️  Record owner keys:");
+            if keystore.owned_records().is_empty() {
+                println!("   (none)");
+            }
+            for record_key in keystore.owned_records() {
+                let owner = keystore.record_owner(&record_key).unwrap_or_default();
+                let fingerprint = owner.parse::<SecretKey>()
+                    .map(|owner| crypto.key_fingerprint(&owner.public_key()))
+                    .unwrap_or_else(|_| "(unreadable owner key)".to_string());
+                println!("   {}  {}", record_key, fingerprint);
+            }
+        }
+        KeysCommand::Export { file } => {
+            let passphrase = Password::new()
+                .with_prompt("Backup passphrase")
+                .with_confirmation("Confirm", "Passphrases do not match")
+                .interact()?;
+            let backup = KeyBackup::new(&open_identity_store(password)?, &open_keystore(password)?)?;
+            fs::write(&file, backup.seal(&passphrase)?)?;
+            println!("✅ Exported {} identities and the keystore to {}", backup.identities.len(), file.display());
+            println!("💡 Keep the passphrase separately; the backup cannot be opened without it");
+        }
+        KeysCommand::Import { file } => {
+            let passphrase = Password::new().with_prompt("Backup passphrase").interact()?;
+            let backup = KeyBackup::open(&fs::read(&file)?, &passphrase)?;
+            let summary = backup.restore(&mut open_identity_store(password)?, &mut open_keystore(password)?)?;
+            println!("✅ Imported {} identities and {} keystore entries", summary.identities, summary.keys);
+            if summary.skipped > 0 {
+                println!("⚠️  Skipped {} identities whose names already exist", summary.skipped);
+            }
+        }
+        KeysCommand::Delete { target, yes } => {
+            let mut identities = open_identity_store(password)?;
+            let mut keystore = open_keystore(password)?;
+            let is_identity = identities.get(&target).is_some();
+            let (what, warning) = if is_identity {
+                ("identity", "packages can no longer be signed with it")
+            } else if keystore.record_owner(&target).is_some() {
+                ("owner key of record", "the site can no longer be updated or unpublished")
+            } else {
+                return Err(color_eyre::eyre::eyre!("No identity or record owner key named {}", target));
+            };
+
+            let confirmed = yes || Confirm::new()
+                .with_prompt(format!("Delete the {} {}? {}", what, target, warning))
+                .default(false)
+                .interact()?;
+            if !confirmed {
+                println!("❎ Delete cancelled");
+                return Ok(());
+            }
+
+            if is_identity {
+                identities.delete(&target)?;
+            } else {
+                keystore.remove_record_owner(&target)?;
+            }
+            println!("🗑️  Deleted the {} {}", what, target);
+        }
+        KeysCommand::Rotate { name } => {
+            let mut identities = open_identity_store(password)?;
+            let identity = identities.get(&name).cloned()
+                .ok_or_else(|| color_eyre::eyre::eyre!("No identity named '{}'", name))?;
+            let (mut rotated, endorsement) = identity.rotated()?;
+
+            let mut store = connect_store(password).await?;
+            let published = store.publish_key_statement(identity.record_key.as_deref(), KeyStatement::Rotation(endorsement)).await;
+            let _ = store.shutdown().await;
+
+            rotated.record_key = Some(published?);
+            identities.insert(rotated.clone())?;
+            println!("🔄 Rotated identity '{}'", name);
+            println!("   Old key: {}", crypto.key_fingerprint(&identity.public_key));
+            println!("   New key: {}", crypto.key_fingerprint(&rotated.public_key));
+            println!("📜 Endorsement published to identity record {}", rotated.record_key.as_deref().unwrap_or_default());
+        }
+        KeysCommand::Revoke { name, key, reason, yes } => {
+            let mut identities = open_identity_store(password)?;
+            let mut identity = identities.get(&name).cloned()
+                .ok_or_else(|| color_eyre::eyre::eyre!("No identity named '{}'", name))?;
+            let key: Option<PublicKey> = key.map(|key| key.parse()).transpose()?;
+            let revocation = identity.revocation(key.as_ref(), reason)?;
+            let revokes_current = revocation.revoked_key == identity.public_key;
+
+            let confirmed = yes || Confirm::new()
+                .with_prompt(format!(
+                    "Revoke key {} of '{}'? Gateways will refuse everything it signed",
+                    crypto.key_fingerprint(&revocation.revoked_key), name
+                ))
+                .default(false)
+                .interact()?;
+            if !confirmed {
+                println!("❎ Revoke cancelled");
+                return Ok(());
+            }
+
+            let revoked_key = revocation.revoked_key;
+            let mut store = connect_store(password).await?;
+            let published = store.publish_key_statement(identity.record_key.as_deref(), KeyStatement::Revocation(revocation)).await;
+            let _ = store.shutdown().await;
+
+            identity.record_key = Some(published?);
+            identities.insert(identity.clone())?;
+            println!("⛔ Revoked key {} of identity '{}'", crypto.key_fingerprint(&revoked_key), name);
+            println!("📜 Revocation published to identity record {}", identity.record_key.as_deref().unwrap_or_default());
+            if revokes_current {
+                println!("💡 This was the identity's current key; run `roselite keys rotate {}` before signing again", name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Open the developer identity store, sealed under the keystore password
+fn open_identity_store(password: &KeystorePassword) -> Result<IdentityStore> {
+    Ok(IdentityStore::open(config_dir()?.join(IDENTITY_STORE_FILENAME), password.get()?)?)
+}
+
+/// Open the local keystore, sealed under the keystore password
+fn open_keystore(password: &KeystorePassword) -> Result<KeyStore> {
+    Ok(KeyStore::unlock(KeyStore::default_path()?, password.get()?)?)
+}
+
+/// The keystore password, handed to everything that unlocks the keystore or the identity
+/// store. Given with --password-fd, or found the first time it is needed.
+struct KeystorePassword {
+    password: OnceCell<String>,
+}
+
+impl KeystorePassword {
+    fn new(given: Option<String>) -> Self {
+        let password = OnceCell::new();
+        if let Some(given) = given {
+            let _ = password.set(given);
+        }
+        Self { password }
+    }
+
+    fn get(&self) -> Result<&str> {
+        if let Some(password) = self.password.get() {
+            return Ok(password);
+        }
+        let found = find_password()?;
+        Ok(self.password.get_or_init(|| found))
+    }
+}
+
+/// Find the keystore password: from the environment or a file descriptor, from a clear-text
+/// password.txt left by older versions, or from a prompt
+fn find_password() -> Result<String> {
+    if let Some(password) = password_from_env()? {
+        return Ok(password);
+    }
+
+    let legacy_path = config_dir()?.join("password.txt");
+    let legacy = if legacy_path.exists() {
+        Some(fs::read_to_string(&legacy_path)?.trim().to_string())
+    } else {
+        None
+    };
+
+    let password = match legacy.as_deref() {
+        Some(password) if !password.is_empty() => password.to_string(),
+        _ => match KeyStore::open_default() {
+            Err(RoseliteError::Crypto(CryptoError::KeystoreLocked)) => Password::new()
+                .with_prompt("Keystore password")
+                .interact()?,
+            Err(e) => return Err(e.into()),
+            Ok(_) => Password::new()
+                .with_prompt("Set a keystore password")
+                .with_confirmation("Confirm", "Passwords do not match")
+                .interact()?,
+        },
+    };
+
+    // The old file held the Veilid protected store password; keep it in the sealed keystore
+    if let Some(legacy) = legacy {
+        let mut keystore = KeyStore::unlock(KeyStore::default_path()?, &password)?;
+        if !legacy.is_empty() && keystore.device_password().is_none() {
+            keystore.set_device_password(legacy)?;
+        }
+        fs::remove_file(&legacy_path)?;
+        println!("🔐 Moved {} into the encrypted keystore; unlock it with the same password from now on", legacy_path.display());
+    }
+
+    Ok(password)
+}
@@ -0,0 +1,492 @@
+//! Indexed package layout
+//!
+//! A package starts with a header and a file table, followed by the data of every file,
+//! each compressed on its own. One file can be read by seeking to its offset, and files can
+//! be listed without decompressing anything. The header records the layout version and the
+//! codec the package was built with. Packages built before the table existed are a single
+//! tar.gz stream; they are still read, but never written.
+
+use crate::crypto::CryptoManager;
+use crate::error::*;
+use crate::keys::HashDigest;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::{Compression, GzBuilder};
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Read, Write};
+use std::path::{Component, Path};
+use std::str::FromStr;
+
+/// Bytes every indexed package starts with
+pub const MAGIC: &[u8; 6] = b"RLPKG\0";
+
+/// Version of the indexed layout, stored after the magic
+pub const LAYOUT_VERSION: u8 = 1;
+
+/// Length of the magic and the layout version
+const PREFIX_LEN: usize = MAGIC.len() + 1;
+
+/// Length of the whole header: magic, layout version, codec and file table length
+const HEADER_LEN: usize = PREFIX_LEN + 1 + 4;
+
+/// Largest file table a package may have; the table is read into memory before any file
+pub const MAX_TABLE_LEN: usize = 16 * 1024 * 1024;
+
+/// Largest file a package may hold, stored or decompressed
+pub const MAX_FILE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Codec a package is built with, recorded in its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// What packages were compressed with before zstd; still read and written
+    Gzip,
+    Zstd,
+}
+
+impl Codec {
+    /// Compression level used when none is given
+    pub fn default_level(self) -> i32 {
+        match self {
+            Codec::Gzip => 6,
+            Codec::Zstd => zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+
+    /// Refuse levels the codec does not have
+    pub fn check_level(self, level: i32) -> Result<()> {
+        let levels = match self {
+            Codec::Gzip => 0..=9,
+            Codec::Zstd => zstd::compression_level_range(),
+        };
+        if !levels.contains(&level) {
+            return Err(RoseliteError::ValidationError(format!(
+                "{} compression level must be between {} and {}, got {}", self, levels.start(), levels.end(), level
+            )));
+        }
+        Ok(())
+    }
+
+    fn id(self) -> u8 {
+        match self {
+            Codec::Gzip => 1,
+            Codec::Zstd => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self> {
+        match id {
+            1 => Ok(Codec::Gzip),
+            2 => Ok(Codec::Zstd),
+            _ => Err(PackageError::UnsupportedVersion { version: format!("codec {}", id) }.into()),
+        }
+    }
+}
+
+impl std::fmt::Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+        })
+    }
+}
+
+impl FromStr for Codec {
+    type Err = RoseliteError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "gzip" => Ok(Codec::Gzip),
+            "zstd" => Ok(Codec::Zstd),
+            _ => Err(RoseliteError::ValidationError(format!("unknown compression {:?}, expected gzip or zstd", s))),
+        }
+    }
+}
+
+/// How the data of one file is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileCompression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// Package header following the magic and layout version
+struct Header {
+    codec: Codec,
+    table_len: usize,
+}
+
+impl Header {
+    /// Parse the header at the start of `content`, which begins with the magic
+    fn parse(content: &[u8]) -> Result<Self> {
+        let version = *content.get(MAGIC.len()).ok_or(PackageError::InvalidFormat)?;
+        if version != LAYOUT_VERSION {
+            return Err(PackageError::UnsupportedVersion { version: version.to_string() }.into());
+        }
+        let header = content.get(..HEADER_LEN).ok_or(PackageError::InvalidFormat)?;
+        let codec = Codec::from_id(header[PREFIX_LEN])?;
+        let table_len = u32::from_le_bytes(header[PREFIX_LEN + 1..].try_into().unwrap()) as usize;
+        if table_len > MAX_TABLE_LEN {
+            return Err(PackageError::InvalidManifest {
+                reason: format!("file table of {} bytes is larger than {}", table_len, MAX_TABLE_LEN)
+            }.into());
+        }
+        Ok(Self { codec, table_len })
+    }
+}
+
+/// Codec a package was built with; legacy tar.gz packages are gzip
+pub fn codec(content: &[u8]) -> Result<Codec> {
+    if !content.starts_with(MAGIC) {
+        return Ok(Codec::Gzip);
+    }
+    Ok(Header::parse(content)?.codec)
+}
+
+/// Where one file lives in the package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub path: String,
+    /// Offset of the stored data from the end of the file table
+    pub offset: u64,
+    /// Length of the stored data
+    pub length: u64,
+    /// Length of the file once decompressed
+    pub size: u64,
+    pub compression: FileCompression,
+    /// BLAKE3 hash of the decompressed file
+    pub hash: HashDigest,
+}
+
+/// File table of an indexed package
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageIndex {
+    pub files: Vec<IndexEntry>,
+}
+
+impl PackageIndex {
+    /// Parse the file table of an indexed package, with where the file data begins.
+    /// `None` for a legacy tar.gz package.
+    pub fn read(content: &[u8]) -> Result<Option<(PackageIndex, usize)>> {
+        if !content.starts_with(MAGIC) {
+            return Ok(None);
+        }
+        let header = Header::parse(content)?;
+        let data_start = HEADER_LEN.checked_add(header.table_len).ok_or(PackageError::InvalidFormat)?;
+        let table = content.get(HEADER_LEN..data_start).ok_or(PackageError::InvalidFormat)?;
+        let index: PackageIndex = serde_json::from_slice(table)?;
+        for entry in &index.files {
+            check_path(&entry.path)?;
+        }
+        Ok(Some((index, data_start)))
+    }
+
+    /// Look up a file by its path in the package
+    pub fn get(&self, path: &str) -> Option<&IndexEntry> {
+        self.files.iter().find(|entry| entry.path == path)
+    }
+
+    /// The data of one file as stored in the package content, still compressed
+    pub fn stored<'a>(&self, content: &'a [u8], data_start: usize, entry: &IndexEntry) -> Result<&'a [u8]> {
+        let start = usize::try_from(entry.offset).ok()
+            .and_then(|offset| data_start.checked_add(offset))
+            .ok_or(PackageError::InvalidFormat)?;
+        let end = usize::try_from(entry.length).ok()
+            .and_then(|length| start.checked_add(length))
+            .ok_or(PackageError::InvalidFormat)?;
+        Ok(content.get(start..end).ok_or(PackageError::InvalidFormat)?)
+    }
+
+    /// Read and decompress one file from the package content, checking it against its hash
+    pub fn read_file(&self, content: &[u8], data_start: usize, entry: &IndexEntry) -> Result<Vec<u8>> {
+        decompress(entry, self.stored(content, data_start, entry)?)
+    }
+}
+
+/// Writes files into an indexed package
+pub struct IndexWriter {
+    codec: Codec,
+    level: i32,
+    index: PackageIndex,
+    data: Vec<u8>,
+}
+
+impl Default for IndexWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IndexWriter {
+    /// Writer compressing with zstd at its default level
+    pub fn new() -> Self {
+        Self { codec: Codec::Zstd, level: Codec::Zstd.default_level(), index: PackageIndex::default(), data: Vec::new() }
+    }
+
+    /// Writer compressing with `codec` at `level`
+    pub fn with_codec(codec: Codec, level: i32) -> Result<Self> {
+        codec.check_level(level)?;
+        Ok(Self { codec, level, ..Self::new() })
+    }
+
+    /// Add a file and return its path as stored and its hash. Files are stored
+    /// uncompressed when compressing would not make them smaller.
+    pub fn add(&mut self, crypto: &CryptoManager, path: &str, data: &[u8]) -> Result<(String, HashDigest)> {
+        let path = package_path(path);
+        check_path(&path)?;
+
+        let compressed = match self.codec {
+            Codec::Gzip => {
+                let mut encoder = gzip_encoder(Vec::new(), self.level);
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            Codec::Zstd => zstd::encode_all(data, self.level)?,
+        };
+        let (compression, stored) = match self.codec {
+            _ if compressed.len() >= data.len() => (FileCompression::None, data),
+            Codec::Gzip => (FileCompression::Gzip, compressed.as_slice()),
+            Codec::Zstd => (FileCompression::Zstd, compressed.as_slice()),
+        };
+
+        let hash = crypto.hash(data)?;
+        let entry = IndexEntry {
+            path: path.clone(),
+            offset: 0,
+            length: stored.len() as u64,
+            size: data.len() as u64,
+            compression,
+            hash,
+        };
+        self.push(entry, stored)?;
+        Ok((path, hash))
+    }
+
+    /// Add a file from another package as it is stored there, without recompressing it
+    pub fn copy(&mut self, entry: &IndexEntry, stored: &[u8]) -> Result<()> {
+        self.push(entry.clone(), stored)
+    }
+
+    fn push(&mut self, mut entry: IndexEntry, stored: &[u8]) -> Result<()> {
+        if self.index.get(&entry.path).is_some() {
+            return Err(PackageError::InvalidManifest { reason: format!("{} is in the package twice", entry.path) }.into());
+        }
+        entry.offset = self.data.len() as u64;
+        self.index.files.push(entry);
+        self.data.extend_from_slice(stored);
+        Ok(())
+    }
+
+    /// Lay out the header, file table and file data
+    pub fn finish(self) -> Result<Vec<u8>> {
+        let table = serde_json::to_vec(&self.index)?;
+        let table_len = u32::try_from(table.len()).map_err(|_| PackageError::InvalidFormat)?;
+        let mut content = Vec::with_capacity(HEADER_LEN + table.len() + self.data.len());
+        content.extend_from_slice(MAGIC);
+        content.push(LAYOUT_VERSION);
+        content.push(self.codec.id());
+        content.extend_from_slice(&table_len.to_le_bytes());
+        content.extend_from_slice(&table);
+        content.extend_from_slice(&self.data);
+        Ok(content)
+    }
+}
+
+/// Unpack a package read as a stream into `dir`, whichever layout it has. Files are
+/// written as their data arrives, so only one file is held in memory at a time.
+pub fn unpack<R: Read>(mut reader: R, dir: &Path) -> Result<()> {
+    let mut header = Vec::new();
+    (&mut reader).take(PREFIX_LEN as u64).read_to_end(&mut header)?;
+    if !header.starts_with(MAGIC) || header.len() < PREFIX_LEN {
+        let legacy = Cursor::new(header).chain(reader);
+        tar::Archive::new(GzDecoder::new(legacy)).unpack(dir)?;
+        return Ok(());
+    }
+
+    header.resize(HEADER_LEN, 0);
+    reader.read_exact(&mut header[PREFIX_LEN..])?;
+    let mut table = vec![0; Header::parse(&header)?.table_len];
+    reader.read_exact(&mut table)?;
+    header.extend_from_slice(&table);
+    let Some((index, _)) = PackageIndex::read(&header)? else {
+        return Err(PackageError::InvalidFormat.into());
+    };
+
+    // Data is written in table order, so it can be read front to back
+    let mut files: Vec<_> = index.files.iter().collect();
+    files.sort_by_key(|entry| entry.offset);
+    let mut position = 0;
+    for entry in files {
+        check_size(entry)?;
+        let skip = entry.offset.checked_sub(position).ok_or(PackageError::InvalidFormat)?;
+        std::io::copy(&mut (&mut reader).take(skip), &mut std::io::sink())?;
+        let mut stored = Vec::new();
+        (&mut reader).take(entry.length).read_to_end(&mut stored)?;
+        if stored.len() as u64 != entry.length {
+            return Err(PackageError::InvalidFormat.into());
+        }
+        position = entry.offset + entry.length;
+
+        let target = dir.join(&entry.path);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(target, decompress(entry, &stored)?)?;
+    }
+    Ok(())
+}
+
+/// Decompress the stored data of a file, checking it has the length and hash the table gives
+fn decompress(entry: &IndexEntry, stored: &[u8]) -> Result<Vec<u8>> {
+    check_size(entry)?;
+    let data = match entry.compression {
+        FileCompression::None => stored.to_vec(),
+        FileCompression::Gzip => {
+            let mut data = Vec::new();
+            GzDecoder::new(stored).take(entry.size).read_to_end(&mut data)?;
+            data
+        }
+        FileCompression::Zstd => {
+            let mut data = Vec::new();
+            zstd::Decoder::new(stored)?.take(entry.size).read_to_end(&mut data)?;
+            data
+        }
+    };
+    if data.len() as u64 != entry.size {
+        return Err(PackageError::InvalidFormat.into());
+    }
+    if blake3::hash(&data).as_bytes() != entry.hash.as_bytes() {
+        return Err(PackageError::InvalidManifest { reason: format!("{} does not match its hash", entry.path) }.into());
+    }
+    Ok(data)
+}
+
+/// Refuse files too large to hold in memory, before any of their data is read
+fn check_size(entry: &IndexEntry) -> Result<()> {
+    if entry.size > MAX_FILE_SIZE || entry.length > MAX_FILE_SIZE {
+        return Err(PackageError::InvalidManifest {
+            reason: format!("{} is larger than {} bytes", entry.path, MAX_FILE_SIZE)
+        }.into());
+    }
+    Ok(())
+}
+
+/// A file path as packages store it: separated by forward slashes, whatever the OS it was
+/// read or extracted on. Used wherever paths are hashed, signed or compared.
+pub fn package_path(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+/// Refuse paths that would land outside the directory a package is unpacked into
+fn check_path(path: &str) -> Result<()> {
+    let safe = !path.is_empty()
+        && Path::new(path).components().all(|component| matches!(component, Component::Normal(_)));
+    if !safe {
+        return Err(PackageError::InvalidManifest { reason: format!("unsafe path in package: {:?}", path) }.into());
+    }
+    Ok(())
+}
+
+/// Gzip stream with a fixed header, so the same input always compresses to the same bytes
+fn gzip_encoder<W: Write>(writer: W, level: i32) -> GzEncoder<W> {
+    GzBuilder::new()
+        .mtime(0)
+        .operating_system(255)
+        .write(writer, Compression::new(level as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_index_round_trip() {
+        let crypto = CryptoManager::new().unwrap();
+        let text = b"<html>".repeat(100);
+        for (codec, compression) in [(Codec::Gzip, FileCompression::Gzip), (Codec::Zstd, FileCompression::Zstd)] {
+            let mut writer = IndexWriter::with_codec(codec, codec.default_level()).unwrap();
+            writer.add(&crypto, "index.html", &text).unwrap();
+            writer.add(&crypto, "img\\logo.png", &[7, 1, 3]).unwrap();
+            assert!(writer.add(&crypto, "index.html", b"again").is_err());
+            assert!(writer.add(&crypto, "../escape", b"x").is_err());
+            let content = writer.finish().unwrap();
+            assert_eq!(super::codec(&content).unwrap(), codec);
+
+            let (index, data_start) = PackageIndex::read(&content).unwrap().unwrap();
+            let html = index.get("index.html").unwrap();
+            assert_eq!(html.compression, compression);
+            assert_eq!(index.read_file(&content, data_start, html).unwrap(), text);
+            let logo = index.get(&package_path("img\\logo.png")).unwrap();
+            assert_eq!(logo.compression, FileCompression::None);
+            assert_eq!(index.read_file(&content, data_start, logo).unwrap(), vec![7, 1, 3]);
+
+            // Streaming unpack gives the same files
+            let dir = TempDir::new().unwrap();
+            unpack(content.as_slice(), dir.path()).unwrap();
+            assert_eq!(std::fs::read(dir.path().join("index.html")).unwrap(), text);
+            assert_eq!(std::fs::read(dir.path().join("img").join("logo.png")).unwrap(), vec![7, 1, 3]);
+
+            // A truncated package is refused
+            assert!(index.read_file(&content[..content.len() - 1], data_start, logo).is_err());
+            assert!(unpack(&content[..content.len() - 1], TempDir::new().unwrap().path()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_untrusted_tables_are_bounded() {
+        let crypto = CryptoManager::new().unwrap();
+        let mut writer = IndexWriter::new();
+        writer.add(&crypto, "logo.png", &[7, 1, 3]).unwrap();
+        let content = writer.finish().unwrap();
+        let (index, data_start) = PackageIndex::read(&content).unwrap().unwrap();
+
+        // Data that does not match its hash is refused, whether read or unpacked
+        let mut tampered = content.clone();
+        *tampered.last_mut().unwrap() = 8;
+        assert!(index.read_file(&tampered, data_start, &index.files[0]).is_err());
+        assert!(unpack(tampered.as_slice(), TempDir::new().unwrap().path()).is_err());
+
+        // A table length or file size past the limits fails before anything is allocated
+        let mut huge_table = content.clone();
+        huge_table[HEADER_LEN - 4..HEADER_LEN].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(PackageIndex::read(&huge_table).is_err());
+        assert!(unpack(huge_table.as_slice(), TempDir::new().unwrap().path()).is_err());
+
+        let mut huge_file = index.files[0].clone();
+        huge_file.size = MAX_FILE_SIZE + 1;
+        assert!(index.read_file(&content, data_start, &huge_file).is_err());
+    }
+
+    #[test]
+    fn test_header() {
+        let crypto = CryptoManager::new().unwrap();
+        let mut writer = IndexWriter::with_codec(Codec::Gzip, 9).unwrap();
+        writer.add(&crypto, "index.html", &b"<html>".repeat(100)).unwrap();
+        let content = writer.finish().unwrap();
+
+        // The header records the layout version and the codec
+        assert_eq!(&content[..PREFIX_LEN], [MAGIC.as_slice(), &[LAYOUT_VERSION]].concat());
+        assert_eq!(codec(&content).unwrap(), Codec::Gzip);
+        let (index, data_start) = PackageIndex::read(&content).unwrap().unwrap();
+        assert_eq!(index.read_file(&content, data_start, &index.files[0]).unwrap(), b"<html>".repeat(100));
+
+        // Unknown versions and codecs are refused rather than misread
+        let mut unknown = content.clone();
+        unknown[PREFIX_LEN] = 9;
+        assert!(PackageIndex::read(&unknown).is_err());
+        unknown[MAGIC.len()] = 9;
+        assert!(matches!(
+            PackageIndex::read(&unknown),
+            Err(RoseliteError::Package(PackageError::UnsupportedVersion { .. }))
+        ));
+
+        assert_eq!("ZSTD".parse::<Codec>().unwrap(), Codec::Zstd);
+        assert!("brotli".parse::<Codec>().is_err());
+        assert!(IndexWriter::with_codec(Codec::Gzip, 19).is_err());
+        assert!(IndexWriter::with_codec(Codec::Zstd, 19).is_ok());
+    }
+}
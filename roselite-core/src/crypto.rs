@@ -1,45 +1,96 @@
 use crate::error::*;
+use crate::keys::{HashDigest, PublicKey, SecretKey, Signature};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-/// Cryptographic operations for package signing and verification
-/// Currently uses basic crypto libraries with Veilid-compatible algorithms (Ed25519, BLAKE3)
-/// POSSIBLE TODO: Replace with direct Veilid crypto API once available
+pub use crate::keys::{KeyFormat, TYPED_PREFIX};
+
+/// Length of an XChaCha20-Poly1305 nonce, prefixed to every sealed message
+pub const NONCE_LENGTH: usize = 24;
+
+/// Bytes added to a message by sealing it: the nonce plus the Poly1305 tag
+pub const SEAL_OVERHEAD: usize = NONCE_LENGTH + 16;
+
+/// BLAKE3 key derivation context for wrapping content keys to X25519 recipients
+const KEY_WRAP_CONTEXT: &str = "roselite content key wrap v1";
+
+/// Cryptographic operations for package signing and verification.
+///
+/// Keys are accepted as hex or in Veilid's typed form. VLD0 keys, signatures and hashes
+/// go through the Veilid node's crypto system once one is attached with `init_with_veilid`,
+/// and through the same algorithms locally until then.
+#[derive(Clone)]
 pub struct CryptoManager {
     initialized: bool,
+    veilid: Option<Arc<veilid_core::VeilidAPI>>,
 }
 
 impl CryptoManager {
     pub fn new() -> Result<Self> {
         Ok(Self {
             initialized: true,
+            veilid: None,
         })
     }
     
-    /// Initialize with Veilid (placeholder for future integration)
-    pub async fn init_with_veilid(&mut self, _veilid_api: Arc<veilid_core::VeilidAPI>) -> Result<()> {
-        // POSSIBLE TODO: Initialize with actual Veilid crypto system
-        self.initialized = true;
-        Ok(())
+    /// Create a manager that uses the crypto system of a running Veilid node
+    pub fn with_veilid(veilid_api: Arc<veilid_core::VeilidAPI>) -> Result<Self> {
+        let mut crypto = Self::new()?;
+        crypto.veilid = Some(veilid_api);
+        // Fail now rather than on first use if the node lacks the VLD0 crypto system
+        crypto.veilid_op(|_| Ok(())).unwrap_or(Ok(()))?;
+        Ok(crypto)
     }
     
-    /// Generate a new Ed25519 keypair (VLD0 compatible)
-    pub fn generate_keypair(&self) -> Result<(String, String)> {
+    /// Use the crypto system of a running Veilid node for VLD0 operations
+    pub async fn init_with_veilid(&mut self, veilid_api: Arc<veilid_core::VeilidAPI>) -> Result<()> {
+        *self = Self::with_veilid(veilid_api)?;
+        Ok(())
+    }
+
+    /// Whether VLD0 operations go through a Veilid node
+    pub fn uses_veilid(&self) -> bool {
+        self.veilid.is_some()
+    }
+
+    /// Run `op` against the Veilid VLD0 crypto system, if a node is attached
+    fn veilid_op<T>(
+        &self,
+        op: impl FnOnce(&veilid_core::CryptoSystemGuard<'_>) -> veilid_core::VeilidAPIResult<T>,
+    ) -> Option<Result<T>> {
+        let api = self.veilid.as_ref()?;
+        let result = api.crypto()
+            .map_err(|e| CryptoError::InitializationFailed(format!("Veilid crypto unavailable: {:?}", e)).into())
+            .and_then(|crypto| {
+                let vcrypto = crypto.get(veilid_core::CRYPTO_KIND_VLD0)
+                    .ok_or_else(|| CryptoError::InitializationFailed("Veilid has no VLD0 crypto system".to_string()))?;
+                op(&vcrypto).map_err(|e| CryptoError::InvalidKey(format!("{:?}", e)).into())
+            });
+        Some(result)
+    }
+
+    /// Generate a new Ed25519 keypair written as hex
+    pub fn generate_keypair(&self) -> Result<(PublicKey, SecretKey)> {
+        self.generate_keypair_in(KeyFormat::Hex)
+    }
+
+    /// Generate a new Ed25519 keypair in the given format. Veilid keys are generated by the
+    /// node's crypto system when one is attached.
+    pub fn generate_keypair_in(&self, format: KeyFormat) -> Result<(PublicKey, SecretKey)> {
         if !self.initialized {
             return Err(CryptoError::InitializationFailed("Crypto not initialized".to_string()).into());
         }
-        
-        // Generate Ed25519 keypair using ed25519-dalek
-        use ed25519_dalek::{SigningKey, VerifyingKey};
-        use rand::rngs::OsRng;
-        
-        let signing_key = SigningKey::generate(&mut OsRng);
-        let verifying_key: VerifyingKey = signing_key.verifying_key();
-        
-        // Return as hex-encoded strings
-        let public_key = hex::encode(verifying_key.to_bytes());
-        let secret_key = hex::encode(signing_key.to_bytes());
-        
-        Ok((public_key, secret_key))
+
+        let veilid_keypair = match format {
+            KeyFormat::Veilid => self.veilid_op(|vcrypto| Ok(vcrypto.generate_keypair())),
+            KeyFormat::Hex => None,
+        };
+        let secret = match veilid_keypair {
+            Some(keypair) => keypair?.secret.bytes,
+            None => ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng).to_bytes(),
+        };
+        let secret_key = SecretKey::from_bytes(secret, format);
+        Ok((secret_key.public_key(), secret_key))
     }
     
     /// Generate a new X25519 keypair for key exchange
@@ -61,73 +112,106 @@ impl CryptoManager {
         Ok((public_key_hex, secret_key_hex))
     }
     
-    /// Sign data with Ed25519 private key
-    pub fn sign(&self, data: &[u8], private_key: &str) -> Result<String> {
+    /// Sign data with an Ed25519 secret key.
+    /// Hex keys give a plain Ed25519 signature; Veilid keys give a VLD0 signature.
+    pub fn sign(&self, data: &[u8], secret_key: &SecretKey) -> Result<Signature> {
         if !self.initialized {
             return Err(CryptoError::InitializationFailed("Crypto not initialized".to_string()).into());
         }
         
-        use ed25519_dalek::{SigningKey, Signature, Signer};
-        
-        // Parse the secret key from hex
-        let secret_bytes = hex::decode(private_key)
-            .map_err(|e| CryptoError::InvalidKey(format!("Invalid secret key hex: {}", e)))?;
+        use ed25519_dalek::{SigningKey, Signer};
         
-        if secret_bytes.len() != 32 {
-            return Err(CryptoError::InvalidKey("Secret key must be 32 bytes".to_string()).into());
+        let signing_key = SigningKey::from_bytes(secret_key.as_bytes());
+        if secret_key.format() == KeyFormat::Hex {
+            return Ok(Signature::from_bytes(signing_key.sign(data).to_bytes(), KeyFormat::Hex));
         }
-        
-        let signing_key = SigningKey::from_bytes(&secret_bytes.try_into().unwrap());
-        
-        // Sign the data
-        let signature: Signature = signing_key.sign(data);
-        
-        Ok(hex::encode(signature.to_bytes()))
+
+        // VLD0 signs a SHA-512 prehash of the data (Ed25519ph)
+        let signature = match self.veilid_op(|vcrypto| vcrypto.sign(
+            &veilid_core::PublicKey::new(*secret_key.public_key().as_bytes()),
+            &veilid_core::SecretKey::new(*secret_key.as_bytes()),
+            data,
+        )) {
+            Some(signature) => signature?.bytes,
+            None => signing_key.sign_prehashed(Self::prehash(data), None)
+                .map_err(|_| CryptoError::SignatureGenerationFailed)?
+                .to_bytes(),
+        };
+        Ok(Signature::from_bytes(signature, KeyFormat::Veilid))
     }
     
-    /// Verify Ed25519 signature with public key
-    pub fn verify(&self, data: &[u8], signature: &str, public_key: &str) -> Result<bool> {
+    /// Verify a signature from `sign`. The public key may be written in either format;
+    /// the signature's format decides which scheme it is checked under.
+    pub fn verify(&self, data: &[u8], signature: &Signature, public_key: &PublicKey) -> Result<bool> {
         if !self.initialized {
             return Err(CryptoError::InitializationFailed("Crypto not initialized".to_string()).into());
         }
         
-        use ed25519_dalek::{VerifyingKey, Signature, Verifier};
-        
-        // Parse the public key and signature from hex
-        let public_bytes = hex::decode(public_key)
-            .map_err(|e| CryptoError::InvalidKey(format!("Invalid public key hex: {}", e)))?;
-        
-        let signature_bytes = hex::decode(signature)
-            .map_err(|e| CryptoError::InvalidKey(format!("Invalid signature hex: {}", e)))?;
-        
-        if public_bytes.len() != 32 {
-            return Err(CryptoError::InvalidKey("Public key must be 32 bytes".to_string()).into());
-        }
-        
-        if signature_bytes.len() != 64 {
-            return Err(CryptoError::InvalidKey("Signature must be 64 bytes".to_string()).into());
-        }
+        use ed25519_dalek::{VerifyingKey, Verifier};
         
-        let verifying_key = VerifyingKey::from_bytes(&public_bytes.try_into().unwrap())
+        let verifying_key = VerifyingKey::from_bytes(public_key.as_bytes())
             .map_err(|e| CryptoError::InvalidKey(format!("Invalid public key: {}", e)))?;
+        let ed_signature = ed25519_dalek::Signature::from_bytes(signature.as_bytes());
         
-        let signature = Signature::from_bytes(&signature_bytes.try_into().unwrap());
+        if signature.format() == KeyFormat::Hex {
+            return Ok(verifying_key.verify(data, &ed_signature).is_ok());
+        }
         
-        // Verify the signature
-        match verifying_key.verify(data, &signature) {
-            Ok(()) => Ok(true),
-            Err(_) => Ok(false),
+        match self.veilid_op(|vcrypto| vcrypto.verify(
+            &veilid_core::PublicKey::new(*public_key.as_bytes()),
+            data,
+            &veilid_core::Signature::new(*signature.as_bytes()),
+        )) {
+            Some(valid) => Ok(valid.unwrap_or(false)),
+            None => Ok(verifying_key
+                .verify_prehashed(Self::prehash(data), None, &ed_signature)
+                .is_ok()),
         }
     }
+
+    /// BLAKE3 hash of the data as a Veilid typed hash
+    pub fn typed_hash(&self, data: &[u8]) -> Result<HashDigest> {
+        let hash = match self.veilid_op(|vcrypto| Ok(vcrypto.generate_hash(data))) {
+            Some(hash) => hash?.bytes,
+            None => *blake3::hash(data).as_bytes(),
+        };
+        Ok(HashDigest::from_bytes(hash, KeyFormat::Veilid))
+    }
+
+    fn prehash(data: &[u8]) -> sha2::Sha512 {
+        use sha2::Digest;
+        sha2::Sha512::new().chain_update(data)
+    }
     
     /// Generate BLAKE3 hash (Veilid compatible)
-    pub fn hash(&self, data: &[u8]) -> Result<String> {
+    pub fn hash(&self, data: &[u8]) -> Result<HashDigest> {
         let hash = blake3::hash(data);
-        Ok(hex::encode(hash.as_bytes()))
+        Ok(HashDigest::from_bytes(*hash.as_bytes(), KeyFormat::Hex))
+    }
+    
+    /// Compute a BLAKE3 Merkle root over leaf hashes.
+    /// Pairs are hashed left to right; an odd node is carried up to the next level unchanged.
+    pub fn merkle_root(&self, leaves: &[HashDigest]) -> Result<HashDigest> {
+        if leaves.is_empty() {
+            return self.hash(&[]);
+        }
+
+        let mut level: Vec<[u8; 32]> = leaves.iter().map(|leaf| *leaf.as_bytes()).collect();
+        while level.len() > 1 {
+            level = level.chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => *blake3::hash(&[left.as_slice(), right.as_slice()].concat()).as_bytes(),
+                    [single] => *single,
+                    _ => unreachable!("chunks(2) yields one or two nodes"),
+                })
+                .collect();
+        }
+
+        Ok(HashDigest::from_bytes(level[0], KeyFormat::Hex))
     }
     
     /// Generate a cryptographic hash using BLAKE3
-    pub fn veilid_hash(&self, data: &[u8]) -> Result<String> {
+    pub fn veilid_hash(&self, data: &[u8]) -> Result<HashDigest> {
         // Use BLAKE3 as Veilid does
         self.hash(data)
     }
@@ -171,11 +255,232 @@ impl CryptoManager {
         let our_secret_key = StaticSecret::from(secret_array);
         let their_public_key = PublicKey::from(public_array);
         
-        // Perform the key exchange
+        // Perform the key exchange, refusing low-order public keys that would force a known secret
         let shared_secret = our_secret_key.diffie_hellman(&their_public_key);
+        if !shared_secret.was_contributory() {
+            return Err(CryptoError::InvalidKey("Public key is not a valid X25519 key".to_string()).into());
+        }
         
         Ok(hex::encode(shared_secret.as_bytes()))
     }
+
+    /// Generate a random 32-byte symmetric key for sealing private content
+    pub fn generate_content_key(&self) -> Result<String> {
+        use rand::RngCore;
+        let mut key = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut key);
+        Ok(hex::encode(key))
+    }
+
+    /// Encrypt data with XChaCha20-Poly1305 under a random nonce.
+    /// The output is the nonce followed by the ciphertext.
+    pub fn encrypt(&self, data: &[u8], key: &str) -> Result<Vec<u8>> {
+        use rand::RngCore;
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        self.encrypt_with_nonce(data, key, &nonce)
+    }
+
+    /// Encrypt data with XChaCha20-Poly1305 under the given nonce, which must never be
+    /// reused with the same key. The output is the nonce followed by the ciphertext.
+    pub fn encrypt_with_nonce(&self, data: &[u8], key: &str, nonce: &[u8; NONCE_LENGTH]) -> Result<Vec<u8>> {
+        use chacha20poly1305::{aead::Aead, KeyInit, XChaCha20Poly1305, XNonce};
+
+        let cipher = XChaCha20Poly1305::new(&Self::parse_content_key(key)?.into());
+        let ciphertext = cipher.encrypt(XNonce::from_slice(nonce), data)
+            .map_err(|_| CryptoError::EncryptionFailed)?;
+
+        let mut sealed = Vec::with_capacity(NONCE_LENGTH + ciphertext.len());
+        sealed.extend_from_slice(nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypt the output of `encrypt` or `encrypt_with_nonce`
+    pub fn decrypt(&self, sealed: &[u8], key: &str) -> Result<Vec<u8>> {
+        use chacha20poly1305::{aead::Aead, KeyInit, XChaCha20Poly1305, XNonce};
+
+        if sealed.len() < SEAL_OVERHEAD {
+            return Err(CryptoError::DecryptionFailed.into());
+        }
+
+        let cipher = XChaCha20Poly1305::new(&Self::parse_content_key(key)?.into());
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+        cipher.decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| CryptoError::DecryptionFailed.into())
+    }
+
+    /// Wrap a content key for one X25519 recipient using a fresh ephemeral keypair.
+    /// Returns the hex-encoded ephemeral public key and wrapped content key.
+    pub fn wrap_content_key(&self, content_key: &str, recipient_public: &str) -> Result<(String, String)> {
+        let (ephemeral_public, ephemeral_secret) = self.generate_x25519_keypair()?;
+        let wrapping_key = self.derive_wrapping_key(&ephemeral_secret, recipient_public, &ephemeral_public, recipient_public)?;
+        let wrapped = self.encrypt(&Self::parse_content_key(content_key)?, &wrapping_key)?;
+        Ok((ephemeral_public, hex::encode(wrapped)))
+    }
+
+    /// Recover a content key wrapped for us by `wrap_content_key`
+    pub fn unwrap_content_key(&self, ephemeral_public: &str, wrapped_key: &str, recipient_secret: &str) -> Result<String> {
+        let recipient_public = self.x25519_public_key(recipient_secret)?;
+        let wrapping_key = self.derive_wrapping_key(recipient_secret, ephemeral_public, ephemeral_public, &recipient_public)?;
+        let wrapped = hex::decode(wrapped_key)
+            .map_err(|_| CryptoError::DecryptionFailed)?;
+        Ok(hex::encode(self.decrypt(&wrapped, &wrapping_key)?))
+    }
+
+    /// X25519 public key belonging to a hex-encoded secret key
+    pub fn x25519_public_key(&self, secret_key: &str) -> Result<String> {
+        use x25519_dalek::{StaticSecret, PublicKey};
+
+        let secret_array: [u8; 32] = hex::decode(secret_key)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| CryptoError::InvalidKey("Secret key must be 32 bytes of hex".to_string()))?;
+        Ok(hex::encode(PublicKey::from(&StaticSecret::from(secret_array)).as_bytes()))
+    }
+
+    /// Derive the symmetric key protecting a wrapped content key, bound to both public keys
+    fn derive_wrapping_key(&self, our_secret: &str, their_public: &str, ephemeral_public: &str, recipient_public: &str) -> Result<String> {
+        let shared_secret = hex::decode(self.derive_shared_secret(our_secret, their_public)?)
+            .map_err(|_| CryptoError::HashComputationFailed)?;
+        let material = [
+            shared_secret.as_slice(),
+            ephemeral_public.as_bytes(),
+            recipient_public.as_bytes(),
+        ].concat();
+        Ok(hex::encode(blake3::derive_key(KEY_WRAP_CONTEXT, &material)))
+    }
+
+    /// Seal data under a key derived from a password and a fresh random salt
+    pub fn seal_with_password(&self, data: &[u8], password: &str) -> Result<PasswordSealed> {
+        self.seal_with_kdf(data, password, KdfParams::default())
+    }
+
+    /// Seal data under a key derived from a password with the given Argon2id costs
+    pub fn seal_with_kdf(&self, data: &[u8], password: &str, kdf: KdfParams) -> Result<PasswordSealed> {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+        use rand::RngCore;
+
+        let mut salt = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        let salt = hex::encode(salt);
+        let sealed = self.encrypt(data, &self.password_key(password, &salt, &kdf)?)?;
+        Ok(PasswordSealed {
+            salt,
+            kdf,
+            sealed: BASE64.encode(sealed),
+        })
+    }
+
+    /// Open data sealed by `seal_with_password` or `seal_with_kdf`
+    pub fn open_with_password(&self, sealed: &PasswordSealed, password: &str) -> Result<Vec<u8>> {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+        let data = BASE64.decode(&sealed.sealed).map_err(|_| CryptoError::DecryptionFailed)?;
+        self.decrypt(&data, &self.password_key(password, &sealed.salt, &sealed.kdf)?)
+    }
+
+    /// Derive a symmetric key from a password and a hex-encoded salt with Argon2id
+    fn password_key(&self, password: &str, salt: &str, kdf: &KdfParams) -> Result<String> {
+        let salt = hex::decode(salt)
+            .map_err(|_| CryptoError::InvalidKey("Salt must be hex".to_string()))?;
+
+        let params = argon2::Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+            .map_err(|e| CryptoError::InvalidKey(format!("Invalid KDF parameters: {}", e)))?;
+        let argon2 = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+        let mut key = zeroize::Zeroizing::new([0u8; 32]);
+        argon2.hash_password_into(password.as_bytes(), &salt, key.as_mut())
+            .map_err(|e| CryptoError::InvalidKey(format!("Password key derivation failed: {}", e)))?;
+        Ok(hex::encode(key.as_ref()))
+    }
+
+    /// Short fingerprint of a public key for display: the first 8 bytes of the BLAKE3 hash
+    /// of the raw key, so it is the same whether the key is written as hex or typed
+    pub fn key_fingerprint(&self, public_key: &PublicKey) -> String {
+        blake3::hash(public_key.as_bytes()).as_bytes()[..8].iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    fn parse_content_key(key: &str) -> Result<[u8; 32]> {
+        let key_bytes = hex::decode(key)
+            .map_err(|e| CryptoError::InvalidKey(format!("Invalid content key hex: {}", e)))?;
+        key_bytes.try_into()
+            .map_err(|_| CryptoError::InvalidKey("Content key must be 32 bytes".to_string()).into())
+    }
+}
+
+/// Data sealed under a key derived from a password
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordSealed {
+    /// Hex-encoded random salt the key was derived with
+    pub salt: String,
+    /// Argon2id parameters the key was derived with
+    pub kdf: KdfParams,
+    /// Base64 of the sealed data
+    pub sealed: String,
+}
+
+/// Argon2id cost parameters of a password-derived key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of passes over the memory
+    pub iterations: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+/// The cheapest costs Argon2id accepts, so tests do not pay for a real derivation
+#[cfg(test)]
+pub(crate) const TEST_KDF: KdfParams = KdfParams { memory_kib: 8, iterations: 1, parallelism: 1 };
+
+impl Default for KdfParams {
+    /// 19 MiB, two passes, one lane
+    fn default() -> Self {
+        Self { memory_kib: 19 * 1024, iterations: 2, parallelism: 1 }
+    }
+}
+
+/// Seals the chunks of one private publish.
+///
+/// Each chunk's nonce is a random per-publish prefix followed by the chunk index, so a
+/// resumed publish reproduces the chunks it already wrote while a later publish under
+/// the same key never repeats a nonce.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkSealer {
+    /// Hex-encoded content key of the site
+    pub key: String,
+    /// Hex-encoded random nonce prefix for this publish
+    pub nonce_prefix: String,
+}
+
+impl ChunkSealer {
+    /// Start sealing a new publish under `key`
+    pub fn new(key: String) -> Self {
+        use rand::RngCore;
+        let mut prefix = [0u8; NONCE_LENGTH - 8];
+        rand::rngs::OsRng.fill_bytes(&mut prefix);
+        Self {
+            key,
+            nonce_prefix: hex::encode(prefix),
+        }
+    }
+
+    /// Seal the chunk at `index` within the package
+    pub fn seal(&self, crypto: &CryptoManager, index: u64, chunk: &[u8]) -> Result<Vec<u8>> {
+        let prefix = hex::decode(&self.nonce_prefix)
+            .ok()
+            .filter(|prefix| prefix.len() == NONCE_LENGTH - 8)
+            .ok_or(CryptoError::EncryptionFailed)?;
+
+        let mut nonce = [0u8; NONCE_LENGTH];
+        nonce[..NONCE_LENGTH - 8].copy_from_slice(&prefix);
+        nonce[NONCE_LENGTH - 8..].copy_from_slice(&index.to_be_bytes());
+        crypto.encrypt_with_nonce(chunk, &self.key, &nonce)
+    }
 }
 
 #[cfg(test)]
@@ -194,12 +499,12 @@ mod tests {
         let (public_key, secret_key) = crypto.generate_keypair().unwrap();
         
         // Keys should be hex-encoded
-        assert_eq!(public_key.len(), 64); // 32 bytes * 2 hex chars
-        assert_eq!(secret_key.len(), 64); // 32 bytes * 2 hex chars
+        assert_eq!(public_key.to_string().len(), 64); // 32 bytes * 2 hex chars
+        assert_eq!(secret_key.expose().len(), 64); // 32 bytes * 2 hex chars
         
         // Should be valid hex
-        hex::decode(&public_key).unwrap();
-        hex::decode(&secret_key).unwrap();
+        hex::decode(public_key.to_string()).unwrap();
+        hex::decode(secret_key.expose()).unwrap();
     }
 
     #[test]
@@ -211,7 +516,7 @@ mod tests {
         let signature = crypto.sign(data, &secret_key).unwrap();
         
         // Signature should be 64 bytes hex-encoded
-        assert_eq!(signature.len(), 128); // 64 bytes * 2 hex chars
+        assert_eq!(signature.to_string().len(), 128); // 64 bytes * 2 hex chars
         
         // Should verify correctly
         let is_valid = crypto.verify(data, &signature, &public_key).unwrap();
@@ -223,6 +528,38 @@ mod tests {
         assert!(!is_valid);
     }
 
+    #[test]
+    fn test_veilid_keys_sign_and_verify() {
+        let crypto = CryptoManager::new().unwrap();
+        let (public_key, keypair) = crypto.generate_keypair_in(KeyFormat::Veilid).unwrap();
+        assert!(public_key.to_string().starts_with(TYPED_PREFIX));
+        assert_eq!(KeyFormat::of(&public_key.to_string()), KeyFormat::Veilid);
+        assert_eq!(KeyFormat::of(&keypair.expose()), KeyFormat::Veilid);
+
+        // The keypair signs, and its public half matches the typed public key
+        let public_half: PublicKey = keypair.expose().split_once(':').unwrap().0.parse().unwrap();
+        assert_eq!(public_half.to_typed().to_string(), public_key.to_string());
+        let signature = crypto.sign(b"package digest", &keypair).unwrap();
+        assert!(signature.to_string().starts_with(TYPED_PREFIX));
+        assert!(crypto.verify(b"package digest", &signature, &public_key).unwrap());
+        assert!(crypto.verify(b"package digest", &signature, &public_half).unwrap());
+        assert!(!crypto.verify(b"other digest", &signature, &public_key).unwrap());
+
+        // A VLD0 signature is not a plain Ed25519 signature in disguise
+        let untyped: Signature = signature.to_string().trim_start_matches(TYPED_PREFIX).parse().unwrap();
+        assert!(!crypto.verify(b"package digest", &untyped, &public_key).unwrap());
+
+        // Hex keys keep producing hex signatures, verifiable against the typed form of the key
+        let (hex_public, hex_secret) = crypto.generate_keypair().unwrap();
+        let hex_signature = crypto.sign(b"data", &hex_secret).unwrap();
+        assert_eq!(KeyFormat::of(&hex_public.to_string()), KeyFormat::Hex);
+        assert!(crypto.verify(b"data", &hex_signature, &hex_public.to_typed()).unwrap());
+
+        let hash = crypto.typed_hash(b"data").unwrap();
+        assert!(hash.to_string().starts_with(TYPED_PREFIX));
+        assert_eq!(hash, crypto.hash(b"data").unwrap());
+    }
+
     #[test]
     fn test_hash() {
         let crypto = CryptoManager::new().unwrap();
@@ -230,7 +567,7 @@ mod tests {
         let hash = crypto.hash(data).unwrap();
         
         // BLAKE3 hash should be 32 bytes hex-encoded
-        assert_eq!(hash.len(), 64); // 32 bytes * 2 hex chars
+        assert_eq!(hash.to_string().len(), 64); // 32 bytes * 2 hex chars
         
         // Same data should produce same hash
         let hash2 = crypto.hash(data).unwrap();
@@ -242,6 +579,30 @@ mod tests {
         assert_ne!(hash, hash3);
     }
 
+    #[test]
+    fn test_merkle_root() {
+        let crypto = CryptoManager::new().unwrap();
+        let leaves: Vec<HashDigest> = [b"a".as_slice(), b"b", b"c"].iter()
+            .map(|data| crypto.hash(data).unwrap())
+            .collect();
+
+        // A single leaf is its own root
+        assert_eq!(crypto.merkle_root(&leaves[..1]).unwrap(), leaves[0]);
+
+        // Root is deterministic and sensitive to order and content
+        let root = crypto.merkle_root(&leaves).unwrap();
+        assert_eq!(root.to_string().len(), 64);
+        assert_eq!(root, crypto.merkle_root(&leaves).unwrap());
+
+        let mut swapped = leaves.clone();
+        swapped.swap(0, 1);
+        assert_ne!(root, crypto.merkle_root(&swapped).unwrap());
+
+        let mut changed = leaves.clone();
+        changed[2] = crypto.hash(b"d").unwrap();
+        assert_ne!(root, crypto.merkle_root(&changed).unwrap());
+    }
+
     #[test]
     fn test_veilid_hash() {
         let crypto = CryptoManager::new().unwrap();
@@ -306,4 +667,91 @@ mod tests {
         // Should be valid hex
         hex::decode(&alice_shared).unwrap();
     }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let crypto = CryptoManager::new().unwrap();
+        let key = crypto.generate_content_key().unwrap();
+        let data = b"Private dashboard";
+
+        let sealed = crypto.encrypt(data, &key).unwrap();
+        assert_eq!(sealed.len(), data.len() + SEAL_OVERHEAD);
+        assert_ne!(crypto.encrypt(data, &key).unwrap(), sealed);
+        assert_eq!(crypto.decrypt(&sealed, &key).unwrap(), data);
+
+        // Wrong key, tampered ciphertext and truncated input are all rejected
+        let other_key = crypto.generate_content_key().unwrap();
+        assert!(crypto.decrypt(&sealed, &other_key).is_err());
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(crypto.decrypt(&tampered, &key).is_err());
+        assert!(crypto.decrypt(&sealed[..10], &key).is_err());
+        assert!(crypto.encrypt(data, "not hex").is_err());
+    }
+
+    #[test]
+    fn test_wrap_content_key() {
+        let crypto = CryptoManager::new().unwrap();
+        let content_key = crypto.generate_content_key().unwrap();
+        let (alice_public, alice_secret) = crypto.generate_x25519_keypair().unwrap();
+        let (_, bob_secret) = crypto.generate_x25519_keypair().unwrap();
+        assert_eq!(crypto.x25519_public_key(&alice_secret).unwrap(), alice_public);
+
+        let (ephemeral_public, wrapped) = crypto.wrap_content_key(&content_key, &alice_public).unwrap();
+        assert_eq!(crypto.unwrap_content_key(&ephemeral_public, &wrapped, &alice_secret).unwrap(), content_key);
+
+        // Only the intended recipient can unwrap
+        assert!(crypto.unwrap_content_key(&ephemeral_public, &wrapped, &bob_secret).is_err());
+
+        // Every wrap uses a fresh ephemeral key
+        let (other_ephemeral, other_wrapped) = crypto.wrap_content_key(&content_key, &alice_public).unwrap();
+        assert_ne!(other_ephemeral, ephemeral_public);
+        assert_ne!(other_wrapped, wrapped);
+
+        // Low-order keys would make the wrapping key predictable
+        let zero_key = hex::encode([0u8; 32]);
+        assert!(matches!(crypto.wrap_content_key(&content_key, &zero_key),
+            Err(RoseliteError::Crypto(CryptoError::InvalidKey(_)))));
+        assert!(crypto.unwrap_content_key(&zero_key, &wrapped, &alice_secret).is_err());
+    }
+
+    #[test]
+    fn test_password_sealing() {
+        let crypto = CryptoManager::new().unwrap();
+        let sealed = crypto.seal_with_kdf(b"secret keys", "correct horse", TEST_KDF).unwrap();
+        assert_eq!(crypto.open_with_password(&sealed, "correct horse").unwrap(), b"secret keys");
+        assert!(crypto.open_with_password(&sealed, "battery staple").is_err());
+
+        // Every seal uses a fresh salt and records its KDF
+        assert_ne!(crypto.seal_with_kdf(b"secret keys", "correct horse", TEST_KDF).unwrap().salt, sealed.salt);
+        assert_eq!(sealed.kdf, TEST_KDF);
+
+        // Seals without a recorded KDF are refused rather than derived some other way
+        let mut json = serde_json::to_value(&sealed).unwrap();
+        json.as_object_mut().unwrap().remove("kdf");
+        assert!(serde_json::from_value::<PasswordSealed>(json).is_err());
+
+        let (public_key, _) = crypto.generate_keypair().unwrap();
+        let (other_key, _) = crypto.generate_keypair().unwrap();
+        let fingerprint = crypto.key_fingerprint(&public_key);
+        assert_eq!(fingerprint.len(), 23);
+        assert_eq!(fingerprint, crypto.key_fingerprint(&public_key.to_typed()));
+        assert_ne!(fingerprint, crypto.key_fingerprint(&other_key));
+    }
+
+    #[test]
+    fn test_chunk_sealer() {
+        let crypto = CryptoManager::new().unwrap();
+        let key = crypto.generate_content_key().unwrap();
+        let sealer = ChunkSealer::new(key.clone());
+
+        // Sealing is repeatable per index, so resumed uploads match what was written before
+        let first = sealer.seal(&crypto, 0, b"chunk").unwrap();
+        assert_eq!(sealer.seal(&crypto, 0, b"chunk").unwrap(), first);
+        assert_ne!(sealer.seal(&crypto, 1, b"chunk").unwrap(), first);
+        assert_eq!(crypto.decrypt(&first, &key).unwrap(), b"chunk");
+
+        // A new publish under the same key uses fresh nonces
+        assert_ne!(ChunkSealer::new(key).seal(&crypto, 0, b"chunk").unwrap(), first);
+    }
 } 
\ No newline at end of file
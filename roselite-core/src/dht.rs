@@ -0,0 +1,287 @@
+use crate::error::*;
+use crate::crypto::CryptoManager;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use veilid_core::ValueSubkey;
+
+/// Maximum size of a single subkey value accepted by the Veilid DHT
+pub const MAX_SUBKEY_SIZE: usize = 32_768;
+
+/// Maximum total size of all subkey values in a single DHT record
+pub const MAX_RECORD_DATA_SIZE: usize = 1_048_576;
+
+/// Maximum number of subkeys a default (DFLT) schema can declare
+pub const MAX_SUBKEY_COUNT: usize = u16::MAX as usize;
+
+/// Storage operations Roselite needs from a DHT.
+///
+/// `VeilidConnection` implements this against the live Veilid network, while
+/// `MemoryDht` keeps records in process so stores can be exercised without a network.
+#[async_trait]
+pub trait DhtBackend: Send + Sync {
+    /// Create a new record with `cols` subkeys and return its key as a string
+    async fn create_dht_record_with_cols(&self, cols: usize) -> Result<String> {
+        Ok(self.create_owned_dht_record(cols).await?.0)
+    }
+
+    /// Create a new record with `cols` subkeys and return its key together with
+    /// the owner keypair needed to write to it from a later session
+    async fn create_owned_dht_record(&self, cols: usize) -> Result<(String, String)>;
+
+    /// Create a new record with `cols` subkeys owned by the given Veilid keypair, so the
+    /// key that signs a package can also own its records
+    async fn create_dht_record_owned_by(&self, cols: usize, owner: &str) -> Result<String>;
+
+    /// Store raw bytes in a record subkey
+    async fn dht_set_subkey(&self, key: &str, subkey: ValueSubkey, value: &[u8]) -> Result<()>;
+
+    /// Store raw bytes in a record subkey, writing as the given owner keypair
+    async fn dht_set_subkey_as(&self, key: &str, subkey: ValueSubkey, value: &[u8], owner: &str) -> Result<()>;
+
+    /// Retrieve raw bytes from a record subkey
+    async fn dht_get_subkey(&self, key: &str, subkey: ValueSubkey) -> Result<Option<Vec<u8>>>;
+
+    /// Retrieve raw bytes from a record subkey, bypassing any locally cached copy
+    async fn dht_refresh_subkey(&self, key: &str, subkey: ValueSubkey) -> Result<Option<Vec<u8>>> {
+        self.dht_get_subkey(key, subkey).await
+    }
+
+    /// Delete an entire record
+    async fn dht_delete_record(&self, key: &str) -> Result<()>;
+
+    /// Inspect a record to gauge replication consensus
+    async fn inspect_record(&self, key: &str) -> Result<()>;
+
+    /// Release any resources held by the backend
+    async fn disconnect(&mut self) -> Result<()>;
+
+    /// Crypto for signing and verifying records, backed by the network's crypto system where there is one
+    fn crypto(&self) -> Result<CryptoManager> {
+        CryptoManager::new()
+    }
+}
+
+/// In-memory record with a fixed number of subkeys
+#[derive(Debug, Clone)]
+struct MemoryRecord {
+    owner: String,
+    subkeys: Vec<Option<Vec<u8>>>,
+}
+
+impl MemoryRecord {
+    fn data_size(&self) -> usize {
+        self.subkeys.iter().flatten().map(|v| v.len()).sum()
+    }
+}
+
+/// In-process DHT backend for development and testing.
+///
+/// Enforces the same subkey-count and value-size limits as the Veilid DHT so that
+/// anything that round-trips here will also fit on the network.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryDht {
+    records: Arc<RwLock<HashMap<String, MemoryRecord>>>,
+}
+
+impl MemoryDht {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of records currently stored
+    pub async fn record_count(&self) -> usize {
+        self.records.read().await.len()
+    }
+
+    /// Generate a key in the same `VLD0:<base64>` form as Veilid typed keys
+    fn generate_key() -> String {
+        let mut bytes = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        format!("VLD0:{}", URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    fn check_cols(cols: usize) -> Result<()> {
+        if cols == 0 || cols > MAX_SUBKEY_COUNT {
+            return Err(RoseliteError::Veilid(VeilidError::DhtOperationFailed {
+                operation: format!("schema build failed: invalid subkey count {}", cols),
+            }));
+        }
+        Ok(())
+    }
+
+    fn check_subkey(record: &MemoryRecord, key: &str, subkey: ValueSubkey) -> Result<usize> {
+        let index = subkey as usize;
+        if index >= record.subkeys.len() {
+            return Err(RoseliteError::Veilid(VeilidError::DhtOperationFailed {
+                operation: format!(
+                    "subkey {} out of range for record {} ({} subkeys)",
+                    subkey, key, record.subkeys.len()
+                ),
+            }));
+        }
+        Ok(index)
+    }
+
+    async fn write_subkey(&self, key: &str, subkey: ValueSubkey, value: &[u8], owner: Option<&str>) -> Result<()> {
+        if value.len() > MAX_SUBKEY_SIZE {
+            return Err(RoseliteError::Veilid(VeilidError::DhtOperationFailed {
+                operation: format!(
+                    "set_dht_value failed: value of {} bytes exceeds subkey limit of {} bytes",
+                    value.len(), MAX_SUBKEY_SIZE
+                ),
+            }));
+        }
+
+        let mut records = self.records.write().await;
+        let record = records.get_mut(key)
+            .ok_or_else(|| RoseliteError::Veilid(VeilidError::DhtOperationFailed {
+                operation: format!("set_dht_value failed: record not found: {}", key),
+            }))?;
+        if owner.is_some_and(|owner| owner != record.owner) {
+            return Err(RoseliteError::Veilid(VeilidError::DhtOperationFailed {
+                operation: format!("set_dht_value failed: writer is not the owner of record {}", key),
+            }));
+        }
+        let index = Self::check_subkey(record, key, subkey)?;
+
+        let previous = record.subkeys[index].as_ref().map(|v| v.len()).unwrap_or(0);
+        let new_size = record.data_size() - previous + value.len();
+        if new_size > MAX_RECORD_DATA_SIZE {
+            return Err(RoseliteError::Veilid(VeilidError::DhtOperationFailed {
+                operation: format!(
+                    "set_dht_value failed: record {} would hold {} bytes, exceeding limit of {} bytes",
+                    key, new_size, MAX_RECORD_DATA_SIZE
+                ),
+            }));
+        }
+
+        record.subkeys[index] = Some(value.to_vec());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DhtBackend for MemoryDht {
+    async fn create_owned_dht_record(&self, cols: usize) -> Result<(String, String)> {
+        let (public_key, secret_key) = CryptoManager::new()?.generate_keypair()?;
+        let owner = format!("{}:{}", public_key, secret_key.expose());
+        let key = self.create_dht_record_owned_by(cols, &owner).await?;
+        Ok((key, owner))
+    }
+
+    async fn create_dht_record_owned_by(&self, cols: usize, owner: &str) -> Result<String> {
+        Self::check_cols(cols)?;
+        let key = Self::generate_key();
+        self.records.write().await.insert(key.clone(), MemoryRecord {
+            owner: owner.to_string(),
+            subkeys: vec![None; cols],
+        });
+        Ok(key)
+    }
+
+    async fn dht_set_subkey(&self, key: &str, subkey: ValueSubkey, value: &[u8]) -> Result<()> {
+        self.write_subkey(key, subkey, value, None).await
+    }
+
+    async fn dht_set_subkey_as(&self, key: &str, subkey: ValueSubkey, value: &[u8], owner: &str) -> Result<()> {
+        self.write_subkey(key, subkey, value, Some(owner)).await
+    }
+
+    async fn dht_get_subkey(&self, key: &str, subkey: ValueSubkey) -> Result<Option<Vec<u8>>> {
+        let records = self.records.read().await;
+        match records.get(key) {
+            Some(record) => {
+                let index = Self::check_subkey(record, key, subkey)?;
+                Ok(record.subkeys[index].clone())
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn dht_delete_record(&self, key: &str) -> Result<()> {
+        self.records.write().await.remove(key)
+            .map(|_| ())
+            .ok_or_else(|| RoseliteError::Veilid(VeilidError::DhtOperationFailed {
+                operation: format!("delete_dht_record failed: record not found: {}", key),
+            }))
+    }
+
+    async fn inspect_record(&self, key: &str) -> Result<()> {
+        let records = self.records.read().await;
+        let record = records.get(key)
+            .ok_or_else(|| RoseliteError::Veilid(VeilidError::DhtOperationFailed {
+                operation: format!("inspect_dht_record failed: record not found: {}", key),
+            }))?;
+        let written = record.subkeys.iter().filter(|v| v.is_some()).count();
+        tracing::info!("In-memory DHT record {}: {}/{} subkeys written ({} bytes)",
+            key, written, record.subkeys.len(), record.data_size());
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_dht_basic_ops() {
+        let dht = MemoryDht::new();
+        let key = dht.create_dht_record_with_cols(2).await.unwrap();
+        assert!(key.starts_with("VLD0:"));
+
+        dht.dht_set_subkey(&key, 0, b"test_value").await.unwrap();
+        assert_eq!(dht.dht_get_subkey(&key, 0).await.unwrap(), Some(b"test_value".to_vec()));
+        assert_eq!(dht.dht_get_subkey(&key, 1).await.unwrap(), None);
+        dht.inspect_record(&key).await.unwrap();
+
+        dht.dht_delete_record(&key).await.unwrap();
+        assert_eq!(dht.dht_get_subkey(&key, 0).await.unwrap(), None);
+        assert_eq!(dht.record_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_memory_dht_enforces_limits() {
+        let dht = MemoryDht::new();
+        assert!(dht.create_dht_record_with_cols(0).await.is_err());
+
+        let key = dht.create_dht_record_with_cols(40).await.unwrap();
+
+        // Subkey outside the schema
+        assert!(dht.dht_set_subkey(&key, 40, b"x").await.is_err());
+
+        // Single value over the subkey limit
+        let oversized = vec![0u8; MAX_SUBKEY_SIZE + 1];
+        assert!(dht.dht_set_subkey(&key, 0, &oversized).await.is_err());
+
+        // Total record size over the record limit
+        let full = vec![0u8; MAX_SUBKEY_SIZE];
+        for subkey in 0..32 {
+            dht.dht_set_subkey(&key, subkey, &full).await.unwrap();
+        }
+        assert!(dht.dht_set_subkey(&key, 32, b"overflow").await.is_err());
+
+        // Overwriting an existing subkey only counts the difference
+        dht.dht_set_subkey(&key, 0, b"small").await.unwrap();
+        dht.dht_set_subkey(&key, 32, b"fits now").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_memory_dht_owner_writes() {
+        let dht = MemoryDht::new();
+        let (key, owner) = dht.create_owned_dht_record(1).await.unwrap();
+        let (_, other_owner) = dht.create_owned_dht_record(1).await.unwrap();
+
+        dht.dht_set_subkey_as(&key, 0, b"v2", &owner).await.unwrap();
+        assert_eq!(dht.dht_get_subkey(&key, 0).await.unwrap(), Some(b"v2".to_vec()));
+
+        assert!(dht.dht_set_subkey_as(&key, 0, b"v3", &other_owner).await.is_err());
+    }
+}
@@ -79,6 +79,9 @@ pub enum VeilidError {
 
     #[error("Invalid Veilid URI: {uri}")]
     InvalidUri { uri: String },
+
+    #[error("Chunk hash mismatch in record {record_key} subkey {subkey}")]
+    ChunkHashMismatch { record_key: String, subkey: u32 },
 }
 
 /// Cryptographic errors
@@ -104,4 +107,28 @@ pub enum CryptoError {
 
     #[error("Crypto initialization failed: {0}")]
     InitializationFailed(String),
+
+    #[error("Encryption failed")]
+    EncryptionFailed,
+
+    #[error("Decryption failed: wrong key or corrupted data")]
+    DecryptionFailed,
+
+    #[error("Record is not signed")]
+    MissingSignature,
+
+    #[error("Keystore is encrypted: set ROSELITE_PASSWORD or ROSELITE_PASSWORD_FD to unlock it")]
+    KeystoreLocked,
+
+    #[error("No signing key for developer key {0}")]
+    SigningKeyUnavailable(String),
+
+    #[error("Publisher key mismatch: record signed by {signer}, package manifest declares {manifest}")]
+    PublisherKeyMismatch { signer: String, manifest: String },
+
+    #[error("Publisher key has been revoked: {0}")]
+    KeyRevoked(String),
+
+    #[error("Publisher key is not trusted: {0}")]
+    UntrustedKey(String),
 } 
\ No newline at end of file
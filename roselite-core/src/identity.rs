@@ -0,0 +1,554 @@
+use crate::crypto::{CryptoManager, KdfParams, KeyFormat, PasswordSealed};
+use crate::error::*;
+use crate::keys::{PublicKey, SecretKey, Signature};
+use crate::keystore::{write_private_file, KeyStore};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// File name of the identity store inside the Roselite config directory
+pub const IDENTITY_STORE_FILENAME: &str = "identities.json";
+
+/// Name of the identity used when none is chosen
+pub const DEFAULT_IDENTITY: &str = "default";
+
+/// Current identity record schema version
+pub const IDENTITY_RECORD_SCHEMA_VERSION: &str = "1.0";
+
+/// A signing key an identity no longer signs with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetiredKey {
+    /// Ed25519 public key
+    pub public_key: PublicKey,
+    /// Ed25519 secret key, kept so the key can still sign statements about itself
+    #[serde(serialize_with = "crate::keys::expose_secret")]
+    pub secret_key: SecretKey,
+    /// When the key was replaced
+    pub retired_at: DateTime<Utc>,
+}
+
+/// A named developer identity whose Ed25519 key signs packages and lookup records
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Identity {
+    /// Name the identity is chosen by
+    pub name: String,
+    /// Ed25519 public key (hex or Veilid typed), published as the manifest `public_key`
+    pub public_key: PublicKey,
+    /// Ed25519 secret key (hex), or `public:secret` keypair for a Veilid key
+    #[serde(serialize_with = "crate::keys::expose_secret")]
+    pub secret_key: SecretKey,
+    /// When the identity was created
+    pub created_at: DateTime<Utc>,
+    /// DHT record where statements about this identity's keys are published
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_key: Option<String>,
+    /// Keys replaced by rotation, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retired_keys: Vec<RetiredKey>,
+}
+
+impl Identity {
+    /// Generate a new identity with a fresh hex keypair
+    pub fn generate(name: &str) -> Result<Self> {
+        Self::generate_in(name, KeyFormat::Hex)
+    }
+
+    /// Generate a new identity with a fresh keypair in the given format
+    pub fn generate_in(name: &str, format: KeyFormat) -> Result<Self> {
+        let (public_key, secret_key) = CryptoManager::new()?.generate_keypair_in(format)?;
+        Ok(Self {
+            name: name.to_string(),
+            public_key,
+            secret_key,
+            created_at: Utc::now(),
+            record_key: None,
+            retired_keys: Vec::new(),
+        })
+    }
+
+    /// This identity with a fresh signing key in the same format, and the statement by the
+    /// current key endorsing it
+    pub fn rotated(&self) -> Result<(Identity, KeyEndorsement)> {
+        let crypto = CryptoManager::new()?;
+        let (public_key, secret_key) = crypto.generate_keypair_in(self.public_key.format())?;
+        let endorsement = KeyEndorsement::sign(&crypto, &self.public_key, &self.secret_key, &public_key)?;
+
+        let mut rotated = self.clone();
+        rotated.retired_keys.push(RetiredKey {
+            public_key: std::mem::replace(&mut rotated.public_key, public_key),
+            secret_key: std::mem::replace(&mut rotated.secret_key, secret_key),
+            retired_at: endorsement.issued_at,
+        });
+        Ok((rotated, endorsement))
+    }
+
+    /// A statement revoking `key`, or the current key when none is given, signed by the current key
+    pub fn revocation(&self, key: Option<&PublicKey>, reason: Option<String>) -> Result<KeyRevocation> {
+        let revoked_key = key.unwrap_or(&self.public_key);
+        if *revoked_key != self.public_key && !self.retired_keys.iter().any(|k| k.public_key == *revoked_key) {
+            return Err(RoseliteError::ValidationError(format!(
+                "Key {} never belonged to identity '{}'", revoked_key, self.name
+            )));
+        }
+        KeyRevocation::sign(&CryptoManager::new()?, revoked_key, reason, &self.public_key, &self.secret_key)
+    }
+}
+
+/// Statement, signed by an old key, that a new key speaks for the same publisher
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyEndorsement {
+    /// Key being replaced
+    pub old_key: PublicKey,
+    /// Key taking over
+    pub new_key: PublicKey,
+    /// When the statement was made
+    pub issued_at: DateTime<Utc>,
+    /// Ed25519 signature by `old_key` over the statement with this field left empty
+    #[serde(with = "crate::keys::empty_as_none")]
+    pub signature: Option<Signature>,
+}
+
+impl KeyEndorsement {
+    /// Endorse `new_key` with the old keypair
+    pub fn sign(crypto: &CryptoManager, old_key: &PublicKey, old_secret: &SecretKey, new_key: &PublicKey) -> Result<Self> {
+        let mut endorsement = Self {
+            old_key: *old_key,
+            new_key: *new_key,
+            issued_at: Utc::now(),
+            signature: None,
+        };
+        endorsement.signature = Some(crypto.sign(&endorsement.signed_bytes()?, old_secret)?);
+        Ok(endorsement)
+    }
+
+    /// Check the statement was signed by its old key
+    pub fn verify(&self, crypto: &CryptoManager) -> Result<()> {
+        verify_statement(crypto, &self.signed_bytes()?, self.signature.as_ref(), &self.old_key)
+    }
+
+    fn signed_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&Self { signature: None, ..self.clone() })?)
+    }
+}
+
+/// Statement that a key must no longer be trusted, whatever it has signed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRevocation {
+    /// Key being revoked
+    pub revoked_key: PublicKey,
+    /// Why the key was revoked, e.g. "compromised"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// When the statement was made
+    pub issued_at: DateTime<Utc>,
+    /// Key that signed the statement: the revoked key itself or a key it was rotated into
+    pub signer: PublicKey,
+    /// Ed25519 signature by `signer` over the statement with this field left empty
+    #[serde(with = "crate::keys::empty_as_none")]
+    pub signature: Option<Signature>,
+}
+
+impl KeyRevocation {
+    /// Revoke `revoked_key` with the signer's keypair
+    pub fn sign(crypto: &CryptoManager, revoked_key: &PublicKey, reason: Option<String>, signer: &PublicKey, signer_secret: &SecretKey) -> Result<Self> {
+        let mut revocation = Self {
+            revoked_key: *revoked_key,
+            reason,
+            issued_at: Utc::now(),
+            signer: *signer,
+            signature: None,
+        };
+        revocation.signature = Some(crypto.sign(&revocation.signed_bytes()?, signer_secret)?);
+        Ok(revocation)
+    }
+
+    /// Check the statement was signed by its signer
+    pub fn verify(&self, crypto: &CryptoManager) -> Result<()> {
+        verify_statement(crypto, &self.signed_bytes()?, self.signature.as_ref(), &self.signer)
+    }
+
+    fn signed_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&Self { signature: None, ..self.clone() })?)
+    }
+}
+
+/// Check a key statement carries a valid signature by `signer`
+fn verify_statement(crypto: &CryptoManager, signed_bytes: &[u8], signature: Option<&Signature>, signer: &PublicKey) -> Result<()> {
+    match signature {
+        Some(signature) if crypto.verify(signed_bytes, signature, signer)? => Ok(()),
+        _ => Err(CryptoError::SignatureVerificationFailed.into()),
+    }
+}
+
+/// A signed statement about a publisher's keys
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeyStatement {
+    /// An old key hands over to a new one
+    Rotation(KeyEndorsement),
+    /// A key is withdrawn
+    Revocation(KeyRevocation),
+}
+
+/// DHT record listing every statement a publisher has made about its keys, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityRecord {
+    /// Schema version for future compatibility
+    pub schema_version: String,
+    /// Published statements, oldest first
+    #[serde(default)]
+    pub statements: Vec<KeyStatement>,
+}
+
+impl IdentityRecord {
+    /// Rotations whose signature checks out, oldest first
+    pub fn rotations(&self, crypto: &CryptoManager) -> Vec<&KeyEndorsement> {
+        self.statements.iter()
+            .filter_map(|statement| match statement {
+                KeyStatement::Rotation(endorsement) if endorsement.verify(crypto).is_ok() => Some(endorsement),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Revocations signed by the revoked key or by a key it was rotated into.
+    /// Anyone can append to a record they own, so a revocation signed by an unrelated key is ignored.
+    pub fn revocations(&self, crypto: &CryptoManager) -> Vec<&KeyRevocation> {
+        let rotations: Vec<(PublicKey, PublicKey)> = self.rotations(crypto).into_iter()
+            .map(|e| (e.old_key, e.new_key))
+            .collect();
+        self.statements.iter()
+            .filter_map(|statement| match statement {
+                KeyStatement::Revocation(revocation) => Some(revocation),
+                _ => None,
+            })
+            .filter(|r| r.verify(crypto).is_ok() && rotated_into(&rotations, &r.revoked_key, &r.signer))
+            .collect()
+    }
+}
+
+/// Whether `to` is `from` or was reached from it through a chain of `(old, new)` rotations
+pub(crate) fn rotated_into(rotations: &[(PublicKey, PublicKey)], from: &PublicKey, to: &PublicKey) -> bool {
+    let mut seen = std::collections::HashSet::new();
+    let mut pending = vec![from];
+    while let Some(key) = pending.pop() {
+        if key == to {
+            return true;
+        }
+        if seen.insert(key) {
+            pending.extend(rotations.iter().filter(|(old, _)| old == key).map(|(_, new)| new));
+        }
+    }
+    false
+}
+
+impl Default for IdentityRecord {
+    fn default() -> Self {
+        Self {
+            schema_version: IDENTITY_RECORD_SCHEMA_VERSION.to_string(),
+            statements: Vec::new(),
+        }
+    }
+}
+
+/// On-disk layout of the identity store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct IdentityFile {
+    /// The identity list sealed under the password, when one is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed: Option<PasswordSealed>,
+    /// Identities kept in the clear when the password is blank
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    identities: BTreeMap<String, Identity>,
+}
+
+/// Named developer identities kept between runs, so every release of a site is signed
+/// by the same key. The file is sealed under a key derived from the keystore password.
+#[derive(Debug, Clone)]
+pub struct IdentityStore {
+    path: PathBuf,
+    password: String,
+    kdf: KdfParams,
+    identities: BTreeMap<String, Identity>,
+}
+
+impl IdentityStore {
+    /// Open the identity store at `path` with `password`, starting empty if the file does
+    /// not exist yet. A blank password keeps the identities unencrypted.
+    pub fn open<P: AsRef<Path>>(path: P, password: &str) -> Result<Self> {
+        Self::open_with_kdf(path, password, KdfParams::default())
+    }
+
+    /// Like `open`, sealing the identities under a key derived with the given Argon2id costs
+    pub fn open_with_kdf<P: AsRef<Path>>(path: P, password: &str, kdf: KdfParams) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let identities = if path.exists() {
+            let file: IdentityFile = serde_json::from_slice(&std::fs::read(&path)?)?;
+            match file.sealed {
+                Some(sealed) => serde_json::from_slice(&CryptoManager::new()?.open_with_password(&sealed, password)?)?,
+                None => file.identities,
+            }
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path,
+            password: password.to_string(),
+            kdf,
+            identities,
+        })
+    }
+
+    /// The identity called `name`
+    pub fn get(&self, name: &str) -> Option<&Identity> {
+        self.identities.get(name)
+    }
+
+    /// The identity called `name`, creating it with a fresh keypair if it does not exist
+    pub fn get_or_create(&mut self, name: &str) -> Result<Identity> {
+        match self.identities.get(name) {
+            Some(identity) => Ok(identity.clone()),
+            None => self.create(name),
+        }
+    }
+
+    /// Create a new identity called `name`
+    pub fn create(&mut self, name: &str) -> Result<Identity> {
+        self.create_in(name, KeyFormat::Hex)
+    }
+
+    /// Create a new identity called `name` with a key in the given format
+    pub fn create_in(&mut self, name: &str, format: KeyFormat) -> Result<Identity> {
+        if self.identities.contains_key(name) {
+            return Err(RoseliteError::ValidationError(format!("Identity '{}' already exists", name)));
+        }
+        let identity = Identity::generate_in(name, format)?;
+        self.insert(identity.clone())?;
+        Ok(identity)
+    }
+
+    /// Add or replace an identity
+    pub fn insert(&mut self, identity: Identity) -> Result<()> {
+        self.identities.insert(identity.name.clone(), identity);
+        self.save()
+    }
+
+    /// Remove the identity called `name`
+    pub fn delete(&mut self, name: &str) -> Result<Identity> {
+        let identity = self.identities.remove(name)
+            .ok_or_else(|| RoseliteError::ValidationError(format!("No identity named '{}'", name)))?;
+        self.save()?;
+        Ok(identity)
+    }
+
+    /// All identities, ordered by name
+    pub fn list(&self) -> Vec<&Identity> {
+        self.identities.values().collect()
+    }
+
+    /// Write the identities back, sealed under the password unless it is blank
+    fn save(&self) -> Result<()> {
+        let file = if self.password.is_empty() {
+            IdentityFile {
+                identities: self.identities.clone(),
+                ..IdentityFile::default()
+            }
+        } else {
+            let identities = serde_json::to_vec(&self.identities)?;
+            IdentityFile {
+                sealed: Some(CryptoManager::new()?.seal_with_kdf(&identities, &self.password, self.kdf)?),
+                ..IdentityFile::default()
+            }
+        };
+
+        write_private_file(&self.path, &serde_json::to_vec_pretty(&file)?)
+    }
+}
+
+/// Backup of every key Roselite holds: signing identities and the local keystore
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyBackup {
+    /// Signing identities
+    pub identities: Vec<Identity>,
+    /// Keystore contents: record owners, site keys, recipient and signing keys
+    pub keystore: serde_json::Value,
+    /// When the backup was taken
+    pub created_at: DateTime<Utc>,
+}
+
+/// How much a backup restored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Identities added
+    pub identities: usize,
+    /// Identities skipped because one with the same name already exists
+    pub skipped: usize,
+    /// Keystore entries added
+    pub keys: usize,
+}
+
+impl KeyBackup {
+    /// Snapshot the given identities and keystore
+    pub fn new(identities: &IdentityStore, keystore: &KeyStore) -> Result<Self> {
+        Ok(Self {
+            identities: identities.list().into_iter().cloned().collect(),
+            keystore: keystore.export()?,
+            created_at: Utc::now(),
+        })
+    }
+
+    /// Seal the backup under a passphrase
+    pub fn seal(&self, passphrase: &str) -> Result<Vec<u8>> {
+        self.seal_with_kdf(passphrase, KdfParams::default())
+    }
+
+    /// Seal the backup under a key derived from the passphrase with the given Argon2id costs
+    pub fn seal_with_kdf(&self, passphrase: &str, kdf: KdfParams) -> Result<Vec<u8>> {
+        let sealed = CryptoManager::new()?.seal_with_kdf(&serde_json::to_vec(self)?, passphrase, kdf)?;
+        Ok(serde_json::to_vec_pretty(&sealed)?)
+    }
+
+    /// Open a backup sealed by `seal`
+    pub fn open(bytes: &[u8], passphrase: &str) -> Result<Self> {
+        let sealed: PasswordSealed = serde_json::from_slice(bytes)?;
+        Ok(serde_json::from_slice(&CryptoManager::new()?.open_with_password(&sealed, passphrase)?)?)
+    }
+
+    /// Merge the backup into the local stores without overwriting existing identities
+    pub fn restore(self, identities: &mut IdentityStore, keystore: &mut KeyStore) -> Result<ImportSummary> {
+        let mut summary = ImportSummary {
+            keys: keystore.import(self.keystore)?,
+            ..ImportSummary::default()
+        };
+        for identity in self.identities {
+            if identities.get(&identity.name).is_some() {
+                summary.skipped += 1;
+            } else {
+                identities.insert(identity)?;
+                summary.identities += 1;
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::TEST_KDF;
+    use crate::error::CryptoError;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_identities_persist_encrypted() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("roselite").join(IDENTITY_STORE_FILENAME);
+
+        let mut store = IdentityStore::open_with_kdf(&path, "hunter2", TEST_KDF).unwrap();
+        let created = store.get_or_create("work").unwrap();
+        assert_eq!(store.get_or_create("work").unwrap().public_key, created.public_key);
+        assert!(store.create("work").is_err());
+
+        // The secret key never reaches the disk in the clear
+        let on_disk = std::fs::read_to_string(&path).unwrap();
+        assert!(!on_disk.contains(&created.secret_key.expose()));
+
+        let reopened = IdentityStore::open(&path, "hunter2").unwrap();
+        assert_eq!(reopened.get("work").unwrap().secret_key, created.secret_key);
+        assert_eq!(reopened.list().len(), 1);
+
+        assert!(matches!(
+            IdentityStore::open(&path, "wrong"),
+            Err(RoseliteError::Crypto(CryptoError::DecryptionFailed))
+        ));
+
+        // A blank password keeps the identities readable without one
+        let plain_path = temp_dir.path().join("plain.json");
+        let mut plain = IdentityStore::open(&plain_path, "").unwrap();
+        let identity = plain.get_or_create(DEFAULT_IDENTITY).unwrap();
+        assert_eq!(IdentityStore::open(&plain_path, "").unwrap().get(DEFAULT_IDENTITY).unwrap().public_key, identity.public_key);
+    }
+
+    #[test]
+    fn test_rotation_endorses_new_key() {
+        let crypto = CryptoManager::new().unwrap();
+        let identity = Identity::generate("work").unwrap();
+        let (rotated, endorsement) = identity.rotated().unwrap();
+
+        assert_ne!(rotated.public_key, identity.public_key);
+        assert_eq!(rotated.retired_keys.len(), 1);
+        assert_eq!(rotated.retired_keys[0].public_key, identity.public_key);
+        assert_eq!(endorsement.old_key, identity.public_key);
+        assert_eq!(endorsement.new_key, rotated.public_key);
+        endorsement.verify(&crypto).unwrap();
+
+        let mut forged = endorsement.clone();
+        forged.new_key = crypto.generate_keypair().unwrap().0;
+        assert!(forged.verify(&crypto).is_err());
+        forged = endorsement.clone();
+        forged.signature = None;
+        assert!(forged.verify(&crypto).is_err());
+
+        // Veilid identities rotate into another Veilid key
+        let veilid = Identity::generate_in("veilid", KeyFormat::Veilid).unwrap();
+        let (rotated, endorsement) = veilid.rotated().unwrap();
+        assert_eq!(rotated.public_key.format(), KeyFormat::Veilid);
+        endorsement.verify(&crypto).unwrap();
+    }
+
+    #[test]
+    fn test_revocations_need_a_linked_signer() {
+        let crypto = CryptoManager::new().unwrap();
+        let identity = Identity::generate("work").unwrap();
+        let (rotated, endorsement) = identity.rotated().unwrap();
+        let stranger = Identity::generate("stranger").unwrap();
+
+        // The successor revokes the old key; a stranger tries to revoke the new one
+        let by_successor = rotated.revocation(Some(&identity.public_key), Some("compromised".to_string())).unwrap();
+        assert_eq!(by_successor.signer, rotated.public_key);
+        let by_stranger = KeyRevocation::sign(&crypto, &rotated.public_key, None, &stranger.public_key, &stranger.secret_key).unwrap();
+        assert!(identity.revocation(Some(&stranger.public_key), None).is_err());
+
+        let record = IdentityRecord {
+            statements: vec![
+                KeyStatement::Rotation(endorsement),
+                KeyStatement::Revocation(by_successor),
+                KeyStatement::Revocation(by_stranger),
+            ],
+            ..IdentityRecord::default()
+        };
+        let revoked: Vec<_> = record.revocations(&crypto).iter().map(|r| r.revoked_key).collect();
+        assert_eq!(revoked, vec![identity.public_key]);
+
+        // A key can always revoke itself
+        let own = rotated.revocation(None, None).unwrap();
+        own.verify(&crypto).unwrap();
+        assert_eq!(own.revoked_key, rotated.public_key);
+    }
+
+    #[test]
+    fn test_backup_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut identities = IdentityStore::open_with_kdf(temp_dir.path().join("a.json"), "pw", TEST_KDF).unwrap();
+        let work = identities.create("work").unwrap();
+        let mut keystore = KeyStore::in_memory();
+        keystore.set_record_owner("VLD0:record", "owner:secret".to_string()).unwrap();
+
+        let sealed = KeyBackup::new(&identities, &keystore).unwrap().seal_with_kdf("backup passphrase", TEST_KDF).unwrap();
+        assert!(!String::from_utf8_lossy(&sealed).contains(&work.secret_key.expose()));
+        assert!(KeyBackup::open(&sealed, "wrong").is_err());
+
+        let mut restored_identities = IdentityStore::open_with_kdf(temp_dir.path().join("b.json"), "other", TEST_KDF).unwrap();
+        restored_identities.create("home").unwrap();
+        let mut restored_keystore = KeyStore::in_memory();
+        let summary = KeyBackup::open(&sealed, "backup passphrase").unwrap()
+            .restore(&mut restored_identities, &mut restored_keystore)
+            .unwrap();
+
+        assert_eq!(summary, ImportSummary { identities: 1, skipped: 0, keys: 1 });
+        assert_eq!(restored_identities.get("work").unwrap().secret_key, work.secret_key);
+        assert_eq!(restored_keystore.record_owner("VLD0:record"), Some("owner:secret"));
+    }
+}
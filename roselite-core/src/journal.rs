@@ -0,0 +1,223 @@
+use crate::error::*;
+use crate::keystore::write_private_file;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// File name of the publish journal inside the Roselite config directory, next to the keystore
+pub const JOURNAL_FILENAME: &str = "publish-journal.json";
+
+/// A package record created by an in-progress publish
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalRecord {
+    /// DHT key of the package record; its owner keypair is kept in the keystore
+    pub record_key: String,
+    /// Number of subkeys the record was created with
+    pub chunk_count: usize,
+    /// Subkeys that have been written successfully
+    #[serde(default)]
+    pub written: BTreeSet<u32>,
+}
+
+/// Progress of a single publish or update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishProgress {
+    /// BLAKE3 hash of the package content being published
+    pub package_hash: String,
+    /// App name from the package manifest
+    pub app_name: String,
+    /// Version from the package manifest
+    pub version: String,
+    /// Whether this publish updates an existing app
+    pub is_update: bool,
+    /// Lookup record of the app, once it exists
+    pub lookup_key: Option<String>,
+    /// Package records created so far, in content order
+    pub records: Vec<JournalRecord>,
+    /// Nonce prefix chunks are sealed under when publishing a private site. The site key
+    /// itself is kept in the keystore under the lookup key.
+    #[serde(default)]
+    pub nonce_prefix: Option<String>,
+    /// X25519 public keys the site key is wrapped for, when publishing a recipient-scoped site
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    /// When the publish was started
+    pub started_at: DateTime<Utc>,
+}
+
+impl PublishProgress {
+    /// Number of chunks written so far across all records
+    pub fn written_chunks(&self) -> usize {
+        self.records.iter().map(|r| r.written.len()).sum()
+    }
+}
+
+/// Local record of the publish currently in flight.
+///
+/// Every created record and written subkey is saved as it happens, so an interrupted
+/// publish can be resumed into the same DHT records instead of starting over. Only record
+/// keys and progress are journaled; the owner keypairs and site keys needed to resume are
+/// looked up in the keystore.
+#[derive(Debug, Clone, Default)]
+pub struct PublishJournal {
+    path: Option<PathBuf>,
+    progress: Option<PublishProgress>,
+}
+
+impl PublishJournal {
+    /// Create a journal that only lives in memory
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Open the journal at `path`, starting empty if the file does not exist yet
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let progress = if path.exists() {
+            let bytes = std::fs::read(&path)?;
+            Some(serde_json::from_slice(&bytes)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            path: Some(path),
+            progress,
+        })
+    }
+
+    /// Default location of the journal, in the Roselite config directory
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(crate::config_dir()?.join(JOURNAL_FILENAME))
+    }
+
+    /// Open the journal at the default location
+    pub fn open_default() -> Result<Self> {
+        Self::open(Self::default_path()?)
+    }
+
+    /// The interrupted publish, if there is one
+    pub fn pending(&self) -> Option<&PublishProgress> {
+        self.progress.as_ref()
+    }
+
+    /// Start tracking a new publish, discarding any previous progress
+    pub fn begin(
+        &mut self,
+        package_hash: String,
+        app_name: &str,
+        version: &str,
+        update_of: Option<&str>,
+        nonce_prefix: Option<String>,
+    ) -> Result<()> {
+        self.progress = Some(PublishProgress {
+            package_hash,
+            app_name: app_name.to_string(),
+            version: version.to_string(),
+            is_update: update_of.is_some(),
+            lookup_key: update_of.map(|key| key.to_string()),
+            records: Vec::new(),
+            nonce_prefix,
+            recipients: Vec::new(),
+            started_at: Utc::now(),
+        });
+        self.save()
+    }
+
+    /// Package record at `index`, if it was created by an earlier attempt
+    pub fn record(&self, index: usize) -> Option<&JournalRecord> {
+        self.progress.as_ref()?.records.get(index)
+    }
+
+    /// Remember a newly created package record
+    pub fn add_record(&mut self, record_key: String, chunk_count: usize) -> Result<()> {
+        self.progress_mut()?.records.push(JournalRecord {
+            record_key,
+            chunk_count,
+            written: BTreeSet::new(),
+        });
+        self.save()
+    }
+
+    /// Remember that a subkey of the package record at `index` has been written
+    pub fn mark_written(&mut self, index: usize, subkey: u32) -> Result<()> {
+        let record = self.progress_mut()?.records.get_mut(index)
+            .ok_or_else(|| RoseliteError::ValidationError(format!(
+                "Publish journal has no package record {}", index
+            )))?;
+        record.written.insert(subkey);
+        self.save()
+    }
+
+    /// Remember who a recipient-scoped site is being published for
+    pub fn set_recipients(&mut self, recipients: Vec<String>) -> Result<()> {
+        self.progress_mut()?.recipients = recipients;
+        self.save()
+    }
+
+    /// Remember the lookup record created for a new app
+    pub fn set_lookup_key(&mut self, lookup_key: &str) -> Result<()> {
+        self.progress_mut()?.lookup_key = Some(lookup_key.to_string());
+        self.save()
+    }
+
+    /// Forget the publish once its lookup record is written
+    pub fn finish(&mut self) -> Result<()> {
+        self.progress = None;
+        if let Some(path) = &self.path {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn progress_mut(&mut self) -> Result<&mut PublishProgress> {
+        self.progress.as_mut()
+            .ok_or_else(|| RoseliteError::ValidationError("No publish in progress".to_string()))
+    }
+
+    /// Persist the journal if it is backed by a file
+    fn save(&self) -> Result<()> {
+        let (Some(path), Some(progress)) = (&self.path, &self.progress) else {
+            return Ok(());
+        };
+        // Nothing secret is journaled, but the file stays private like the keystore next to it
+        write_private_file(path, &serde_json::to_vec_pretty(progress)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_journal_persists_progress() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("journal.json");
+
+        let mut journal = PublishJournal::open(&path).unwrap();
+        assert!(journal.pending().is_none());
+        journal.begin("hash".to_string(), "app", "1.0.0", None, None).unwrap();
+        journal.add_record("VLD0:record".to_string(), 3).unwrap();
+        journal.mark_written(0, 2).unwrap();
+        journal.mark_written(0, 0).unwrap();
+        assert!(journal.mark_written(1, 0).is_err());
+
+        let reopened = PublishJournal::open(&path).unwrap();
+        let progress = reopened.pending().unwrap();
+        assert_eq!(progress.package_hash, "hash");
+        assert!(!progress.is_update);
+        assert_eq!(progress.written_chunks(), 2);
+        let record = reopened.record(0).unwrap();
+        assert_eq!(record.record_key, "VLD0:record");
+        assert_eq!(record.written.iter().copied().collect::<Vec<_>>(), vec![0, 2]);
+
+        let mut reopened = reopened;
+        reopened.finish().unwrap();
+        assert!(!path.exists());
+        assert!(PublishJournal::open(&path).unwrap().pending().is_none());
+    }
+}
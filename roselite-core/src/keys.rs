@@ -0,0 +1,394 @@
+use crate::error::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+/// Prefix of Veilid typed keys, signatures and hashes of the VLD0 crypto kind
+pub const TYPED_PREFIX: &str = "VLD0:";
+
+/// How signing keys, signatures and hashes are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyFormat {
+    /// Bare hex keys with plain Ed25519 signatures
+    #[default]
+    Hex,
+    /// Veilid typed `VLD0:` keys, `public:secret` keypairs and VLD0 signatures.
+    /// A keypair in this form can also own DHT records.
+    Veilid,
+}
+
+impl KeyFormat {
+    /// The format a public key, secret key or keypair is written in
+    pub fn of(key: &str) -> Self {
+        if !key.starts_with(TYPED_PREFIX) && key.bytes().all(|b| b.is_ascii_hexdigit() || b == b':') {
+            Self::Hex
+        } else {
+            Self::Veilid
+        }
+    }
+}
+
+/// Ed25519 public key. Written as hex or as a Veilid typed key, and compared in constant time
+/// by value, so the same key matches whichever way it was written.
+#[derive(Clone, Copy)]
+pub struct PublicKey {
+    bytes: [u8; 32],
+    format: KeyFormat,
+}
+
+impl PublicKey {
+    /// Wrap raw key bytes, to be written in `format`
+    pub fn from_bytes(bytes: [u8; 32], format: KeyFormat) -> Self {
+        Self { bytes, format }
+    }
+
+    /// Raw key bytes
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.bytes
+    }
+
+    /// How the key is written
+    pub fn format(&self) -> KeyFormat {
+        self.format
+    }
+
+    /// The same key written as a Veilid typed key
+    pub fn to_typed(&self) -> Self {
+        Self { format: KeyFormat::Veilid, ..*self }
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&encode_as(&self.bytes, self.format))
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = RoseliteError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self {
+            bytes: decode(s, "public key")?,
+            format: KeyFormat::of(s),
+        })
+    }
+}
+
+/// Ed25519 secret key, wiped from memory when dropped. Hex secrets are written as hex;
+/// Veilid secrets as the `public:secret` keypair Veilid accepts as a record owner.
+#[derive(Clone)]
+pub struct SecretKey {
+    bytes: [u8; 32],
+    format: KeyFormat,
+}
+
+impl SecretKey {
+    /// Wrap raw secret key bytes, to be written in `format`
+    pub fn from_bytes(bytes: [u8; 32], format: KeyFormat) -> Self {
+        Self { bytes, format }
+    }
+
+    /// Raw secret key bytes
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.bytes
+    }
+
+    /// How the key is written
+    pub fn format(&self) -> KeyFormat {
+        self.format
+    }
+
+    /// The public key belonging to this secret, in the same format
+    pub fn public_key(&self) -> PublicKey {
+        let public = ed25519_dalek::SigningKey::from_bytes(&self.bytes).verifying_key().to_bytes();
+        PublicKey::from_bytes(public, self.format)
+    }
+
+    /// The secret in its text form, for writing to protected storage only
+    pub fn expose(&self) -> String {
+        match self.format {
+            KeyFormat::Hex => hex::encode(self.bytes),
+            KeyFormat::Veilid => format!("{}:{}", encode(self.public_key().as_bytes()), encode(&self.bytes)),
+        }
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretKey({})", self.public_key())
+    }
+}
+
+impl FromStr for SecretKey {
+    type Err = RoseliteError;
+
+    /// Parse a hex secret, or a Veilid secret on its own or as a `public:secret` keypair
+    fn from_str(s: &str) -> Result<Self> {
+        let untyped = s.strip_prefix(TYPED_PREFIX).unwrap_or(s);
+        let (public, secret) = match untyped.rsplit_once(':') {
+            Some((public, secret)) => (Some(public), secret),
+            None => (None, untyped),
+        };
+        let key = Self {
+            bytes: decode(secret, "secret key")?,
+            format: KeyFormat::of(s),
+        };
+        if let Some(public) = public {
+            if public.parse::<PublicKey>()? != key.public_key() {
+                return Err(CryptoError::InvalidKey("Keypair halves do not belong together".to_string()).into());
+            }
+        }
+        Ok(key)
+    }
+}
+
+/// Ed25519 signature. Hex signatures are plain Ed25519; Veilid typed signatures are VLD0
+/// signatures over a SHA-512 prehash of the data.
+#[derive(Clone, Copy)]
+pub struct Signature {
+    bytes: [u8; 64],
+    format: KeyFormat,
+}
+
+impl Signature {
+    /// Wrap raw signature bytes made with the scheme of `format`
+    pub fn from_bytes(bytes: [u8; 64], format: KeyFormat) -> Self {
+        Self { bytes, format }
+    }
+
+    /// Raw signature bytes
+    pub fn as_bytes(&self) -> &[u8; 64] {
+        &self.bytes
+    }
+
+    /// Which scheme made the signature
+    pub fn format(&self) -> KeyFormat {
+        self.format
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&encode_as(&self.bytes, self.format))
+    }
+}
+
+impl FromStr for Signature {
+    type Err = RoseliteError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let format = if s.starts_with(TYPED_PREFIX) { KeyFormat::Veilid } else { KeyFormat::Hex };
+        Ok(Self {
+            bytes: decode(s, "signature")?,
+            format,
+        })
+    }
+}
+
+/// BLAKE3 hash, written as hex or as a Veilid typed hash
+#[derive(Clone, Copy)]
+pub struct HashDigest {
+    bytes: [u8; 32],
+    format: KeyFormat,
+}
+
+impl HashDigest {
+    /// Wrap raw hash bytes, to be written in `format`
+    pub fn from_bytes(bytes: [u8; 32], format: KeyFormat) -> Self {
+        Self { bytes, format }
+    }
+
+    /// Raw hash bytes
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.bytes
+    }
+
+    /// The same hash written as a Veilid typed hash
+    pub fn to_typed(&self) -> Self {
+        Self { format: KeyFormat::Veilid, ..*self }
+    }
+}
+
+impl fmt::Display for HashDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&encode_as(&self.bytes, self.format))
+    }
+}
+
+impl FromStr for HashDigest {
+    type Err = RoseliteError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let format = if s.starts_with(TYPED_PREFIX) { KeyFormat::Veilid } else { KeyFormat::Hex };
+        Ok(Self {
+            bytes: decode(s, "hash")?,
+            format,
+        })
+    }
+}
+
+/// Constant-time equality and hashing by value, and deserializing from text
+macro_rules! text_value {
+    ($($name:ident),*) => {$(
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.bytes.ct_eq(&other.bytes).into()
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl std::hash::Hash for $name {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.bytes.hash(state);
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+                String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+            }
+        }
+    )*};
+}
+
+text_value!(PublicKey, SecretKey, Signature, HashDigest);
+
+/// Serde writing the public text form. Secret keys have no `Serialize`, so they are only
+/// written by fields that opt in with `expose_secret`.
+macro_rules! serialize_as_text {
+    ($($name:ident),*) => {$(
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.to_string())
+            }
+        }
+    )*};
+}
+
+serialize_as_text!(PublicKey, Signature, HashDigest);
+
+/// Write a secret key as its text form, for `#[serde(serialize_with)]` on fields that are
+/// only ever stored sealed, such as identities in the identity store and key backups
+pub fn expose_secret<S: Serializer>(secret_key: &SecretKey, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&secret_key.expose())
+}
+
+/// `Debug` showing the public text form
+macro_rules! debug_as_text {
+    ($($name:ident),*) => {$(
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self)
+            }
+        }
+    )*};
+}
+
+debug_as_text!(PublicKey, Signature, HashDigest);
+
+/// Serde for an optional key written as an empty string when absent, as older manifests do
+pub mod empty_as_none {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub fn serialize<T: Serialize, S: Serializer>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => value.serialize(serializer),
+            None => serializer.serialize_str(""),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        if text.is_empty() {
+            return Ok(None);
+        }
+        text.parse().map(Some).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Encode bytes the way Veilid writes keys: unpadded URL-safe base64
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Encode bytes as hex or as a VLD0 typed value
+fn encode_as(bytes: &[u8], format: KeyFormat) -> String {
+    match format {
+        KeyFormat::Hex => hex::encode(bytes),
+        KeyFormat::Veilid => format!("{}{}", TYPED_PREFIX, encode(bytes)),
+    }
+}
+
+/// Decode a value written as hex, as Veilid base64, or as a typed VLD0 value
+fn decode<const N: usize>(text: &str, what: &str) -> Result<[u8; N]> {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+    let text = text.strip_prefix(TYPED_PREFIX).unwrap_or(text);
+    let bytes = if text.len() == N * 2 && text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        hex::decode(text).map_err(|e| CryptoError::InvalidKey(format!("Invalid {} hex: {}", what, e)))?
+    } else {
+        URL_SAFE_NO_PAD.decode(text).map_err(|e| CryptoError::InvalidKey(format!("Invalid {} encoding: {}", what, e)))?
+    };
+    bytes.try_into()
+        .map_err(|_| CryptoError::InvalidKey(format!("{} must be {} bytes", what, N)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::CryptoManager;
+
+    #[test]
+    fn test_keys_round_trip_through_text() {
+        let crypto = CryptoManager::new().unwrap();
+        for format in [KeyFormat::Hex, KeyFormat::Veilid] {
+            let (public_key, secret_key) = crypto.generate_keypair_in(format).unwrap();
+            assert_eq!(secret_key.public_key(), public_key);
+
+            let public_text = public_key.to_string();
+            assert_eq!(public_text.parse::<PublicKey>().unwrap(), public_key);
+            assert_eq!(serde_json::to_string(&public_key).unwrap(), format!("\"{}\"", public_text));
+            // Secrets are only written through an explicit opt-in
+            let secret_json = expose_secret(&secret_key, serde_json::value::Serializer).unwrap();
+            let restored: SecretKey = serde_json::from_value(secret_json).unwrap();
+            assert_eq!(restored.as_bytes(), secret_key.as_bytes());
+            assert_eq!(restored.format(), format);
+
+            // Secrets never show up in debug output
+            assert!(!format!("{:?}", secret_key).contains(&secret_key.expose()));
+        }
+
+        // The same key written two ways is still the same key
+        let (public_key, _) = crypto.generate_keypair().unwrap();
+        assert_eq!(public_key.to_typed(), public_key);
+        assert!(public_key.to_typed().to_string().starts_with(TYPED_PREFIX));
+
+        // Mismatched keypair halves and malformed values are refused
+        let (_, veilid_secret) = crypto.generate_keypair_in(KeyFormat::Veilid).unwrap();
+        let (other_public, _) = crypto.generate_keypair_in(KeyFormat::Veilid).unwrap();
+        let secret = veilid_secret.expose();
+        let forged = format!("{}:{}", other_public.to_string().trim_start_matches(TYPED_PREFIX), secret.split(':').nth(1).unwrap());
+        assert!(forged.parse::<SecretKey>().is_err());
+        assert!("abcd".parse::<PublicKey>().is_err());
+        assert!("not a hash".parse::<HashDigest>().is_err());
+    }
+}
@@ -0,0 +1,429 @@
+use crate::crypto::{CryptoManager, KdfParams, PasswordSealed};
+use crate::error::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// File name of the keystore inside the Roselite config directory
+pub const KEYSTORE_FILENAME: &str = "keystore.json";
+
+/// Environment variable holding the keystore password
+pub const PASSWORD_ENV: &str = "ROSELITE_PASSWORD";
+
+/// Environment variable naming an open file descriptor to read the keystore password from
+pub const PASSWORD_FD_ENV: &str = "ROSELITE_PASSWORD_FD";
+
+/// On-disk layout of the keystore
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct KeyStoreData {
+    /// Owner keypair of each DHT record we created, keyed by record key
+    #[serde(default)]
+    record_owners: HashMap<String, String>,
+    /// Content key of each private site we published, keyed by lookup key
+    #[serde(default)]
+    site_keys: HashMap<String, String>,
+    /// Our X25519 recipient keypairs for recipient-scoped sites, secret keyed by public key
+    #[serde(default)]
+    recipient_keys: HashMap<String, String>,
+    /// Password the Veilid protected store encrypts its device key under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    device_password: Option<String>,
+}
+
+/// Keystore file: the keys sealed under the keystore password, or in the clear as older
+/// versions wrote them
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeyStoreFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed: Option<PasswordSealed>,
+    #[serde(flatten)]
+    data: KeyStoreData,
+}
+
+/// Local store for the secrets Roselite needs to keep between runs.
+///
+/// Holds the owner keypair of every lookup record we publish so the record can be
+/// rewritten later and a site keeps its key across deploys, and the content key of
+/// every private site so later versions stay readable under the same share link.
+/// Also holds the X25519 recipient keys that let us open sites shared with us. Developer
+/// signing keys are not kept here; they stay in the identity store.
+///
+/// A keystore unlocked with a password is sealed under an Argon2id key derived from it,
+/// and also keeps the password of the Veilid protected store.
+#[derive(Debug, Clone, Default)]
+pub struct KeyStore {
+    path: Option<PathBuf>,
+    password: Option<String>,
+    kdf: KdfParams,
+    data: KeyStoreData,
+}
+
+impl KeyStore {
+    /// Create a keystore that only lives in memory
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Open an unencrypted keystore at `path`, starting empty if the file does not exist yet.
+    /// Fails with `KeystoreLocked` if the keystore is sealed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = Self::read_file(&path)?;
+        if file.sealed.is_some() {
+            return Err(CryptoError::KeystoreLocked.into());
+        }
+
+        Ok(Self {
+            path: Some(path),
+            password: None,
+            data: file.data,
+            ..Self::default()
+        })
+    }
+
+    /// Open the keystore at `path` with `password`, starting empty if the file does not exist
+    /// yet. An unencrypted keystore is sealed under the password straight away.
+    pub fn unlock<P: AsRef<Path>>(path: P, password: &str) -> Result<Self> {
+        Self::unlock_with_kdf(path, password, KdfParams::default())
+    }
+
+    /// Like `unlock`, sealing the keystore under a key derived with the given Argon2id costs
+    pub fn unlock_with_kdf<P: AsRef<Path>>(path: P, password: &str, kdf: KdfParams) -> Result<Self> {
+        if password.is_empty() {
+            return Err(RoseliteError::ValidationError("Keystore password must not be empty".to_string()));
+        }
+
+        let path = path.as_ref().to_path_buf();
+        let file = Self::read_file(&path)?;
+        let migrate = file.sealed.is_none() && path.exists();
+        let data = match file.sealed {
+            Some(sealed) => serde_json::from_slice(&CryptoManager::new()?.open_with_password(&sealed, password)?)?,
+            None => file.data,
+        };
+
+        let keystore = Self {
+            path: Some(path),
+            password: Some(password.to_string()),
+            kdf,
+            data,
+        };
+        if migrate {
+            keystore.save()?;
+        }
+        Ok(keystore)
+    }
+
+    /// Default location of the keystore, in the Roselite config directory
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(crate::config_dir()?.join(KEYSTORE_FILENAME))
+    }
+
+    /// Open the keystore at the default location
+    pub fn open_default() -> Result<Self> {
+        Self::open(Self::default_path()?)
+    }
+
+    /// Open the keystore at the default location, unlocking it with the password from
+    /// the environment when one is given
+    pub fn open_default_unlocked() -> Result<Self> {
+        match password_from_env()? {
+            Some(password) => Self::unlock(Self::default_path()?, &password),
+            None => Self::open_default(),
+        }
+    }
+
+    /// Whether the keystore is sealed under a password
+    pub fn is_sealed(&self) -> bool {
+        self.password.is_some()
+    }
+
+    /// Password of the Veilid protected store, if one has been set
+    pub fn device_password(&self) -> Option<&str> {
+        self.data.device_password.as_deref()
+    }
+
+    /// Remember the password of the Veilid protected store
+    pub fn set_device_password(&mut self, password: String) -> Result<()> {
+        self.data.device_password = Some(password);
+        self.save()
+    }
+
+    /// Password of the Veilid protected store, generating a random one the first time.
+    /// Only a sealed keystore may hold it.
+    pub fn ensure_device_password(&mut self) -> Result<String> {
+        if !self.is_sealed() {
+            return Err(CryptoError::KeystoreLocked.into());
+        }
+        if let Some(password) = self.device_password() {
+            return Ok(password.to_string());
+        }
+
+        let password = CryptoManager::new()?.generate_content_key()?;
+        self.set_device_password(password.clone())?;
+        Ok(password)
+    }
+
+    /// Owner keypair for a DHT record, if we own it
+    pub fn record_owner(&self, record_key: &str) -> Option<&str> {
+        self.data.record_owners.get(record_key).map(|s| s.as_str())
+    }
+
+    /// Remember the owner keypair of a DHT record
+    pub fn set_record_owner(&mut self, record_key: &str, owner: String) -> Result<()> {
+        self.data.record_owners.insert(record_key.to_string(), owner);
+        self.save()
+    }
+
+    /// Forget the owner keypair of a DHT record
+    pub fn remove_record_owner(&mut self, record_key: &str) -> Result<()> {
+        if self.data.record_owners.remove(record_key).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Forget the owner keypairs of several DHT records at once
+    pub fn remove_record_owners(&mut self, record_keys: &[String]) -> Result<()> {
+        let before = self.data.record_owners.len();
+        for record_key in record_keys {
+            self.data.record_owners.remove(record_key);
+        }
+        if self.data.record_owners.len() != before {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Content key of a private site, if we have it
+    pub fn site_key(&self, lookup_key: &str) -> Option<&str> {
+        self.data.site_keys.get(lookup_key).map(|s| s.as_str())
+    }
+
+    /// Remember the content key of a private site
+    pub fn set_site_key(&mut self, lookup_key: &str, key: String) -> Result<()> {
+        self.data.site_keys.insert(lookup_key.to_string(), key);
+        self.save()
+    }
+
+    /// Forget the content key of a private site
+    pub fn remove_site_key(&mut self, lookup_key: &str) -> Result<()> {
+        if self.data.site_keys.remove(lookup_key).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Generate a new X25519 recipient keypair and return its public key
+    pub fn create_recipient_key(&mut self) -> Result<String> {
+        let (public_key, secret_key) = CryptoManager::new()?.generate_x25519_keypair()?;
+        self.data.recipient_keys.insert(public_key.clone(), secret_key);
+        self.save()?;
+        Ok(public_key)
+    }
+
+    /// Public keys of all our recipient keypairs
+    pub fn recipient_public_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.data.recipient_keys.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Secret key of one of our recipient keypairs
+    pub fn recipient_secret(&self, public_key: &str) -> Option<&str> {
+        self.data.recipient_keys.get(public_key).map(|s| s.as_str())
+    }
+
+    /// Keys of all records we hold an owner keypair for
+    pub fn owned_records(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.data.record_owners.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Every key in the keystore, for a backup
+    pub fn export(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(&self.data)?)
+    }
+
+    /// Merge keys from a backup made by `export`, keeping any entry we already have.
+    /// Returns how many entries were added.
+    pub fn import(&mut self, backup: serde_json::Value) -> Result<usize> {
+        let backup: KeyStoreData = serde_json::from_value(backup)?;
+        let mut added = 0;
+        for (ours, theirs) in [
+            (&mut self.data.record_owners, backup.record_owners),
+            (&mut self.data.site_keys, backup.site_keys),
+            (&mut self.data.recipient_keys, backup.recipient_keys),
+        ] {
+            for (key, value) in theirs {
+                if let std::collections::hash_map::Entry::Vacant(entry) = ours.entry(key) {
+                    entry.insert(value);
+                    added += 1;
+                }
+            }
+        }
+        if self.data.device_password.is_none() && backup.device_password.is_some() {
+            self.data.device_password = backup.device_password;
+            added += 1;
+        }
+        self.save()?;
+        Ok(added)
+    }
+
+    /// Persist the keystore if it is backed by a file
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let file = match &self.password {
+            Some(password) => KeyStoreFile {
+                sealed: Some(CryptoManager::new()?.seal_with_kdf(&serde_json::to_vec(&self.data)?, password, self.kdf)?),
+                ..KeyStoreFile::default()
+            },
+            None => KeyStoreFile {
+                data: self.data.clone(),
+                ..KeyStoreFile::default()
+            },
+        };
+        write_private_file(path, &serde_json::to_vec_pretty(&file)?)
+    }
+
+    fn read_file(path: &Path) -> Result<KeyStoreFile> {
+        if !path.exists() {
+            return Ok(KeyStoreFile::default());
+        }
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+}
+
+/// The keystore password from `ROSELITE_PASSWORD`, or read from the file descriptor named
+/// by `ROSELITE_PASSWORD_FD`, so scripts and CI can unlock without a prompt
+pub fn password_from_env() -> Result<Option<String>> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(Some(password));
+    }
+    match std::env::var(PASSWORD_FD_ENV) {
+        Ok(fd) => {
+            let fd = fd.trim().parse()
+                .map_err(|_| RoseliteError::ValidationError(format!("{} must be a file descriptor number", PASSWORD_FD_ENV)))?;
+            read_password_fd(fd).map(Some)
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Read a password from an open file descriptor, up to the first newline.
+///
+/// The descriptor is reopened through `/dev/fd` rather than adopted with `from_raw_fd`,
+/// so a number that is not an open descriptor fails to open instead of aliasing
+/// whatever file we later open under it, and the caller's descriptor is left alone.
+#[cfg(unix)]
+pub fn read_password_fd(fd: i32) -> Result<String> {
+    if fd < 0 {
+        return Err(RoseliteError::ValidationError(format!("{} must be a file descriptor number", PASSWORD_FD_ENV)));
+    }
+    let password = std::fs::read_to_string(format!("/dev/fd/{}", fd))
+        .map_err(|e| RoseliteError::ValidationError(format!("Cannot read the password from file descriptor {}: {}", fd, e)))?;
+    Ok(password.lines().next().unwrap_or_default().to_string())
+}
+
+/// Read a password from an open file descriptor, up to the first newline
+#[cfg(not(unix))]
+pub fn read_password_fd(_fd: i32) -> Result<String> {
+    Err(RoseliteError::ValidationError("Reading a password from a file descriptor needs a Unix platform".to_string()))
+}
+
+/// Write a file holding secrets, creating its directory and restricting it to the current user
+pub(crate) fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(path, contents)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::TEST_KDF;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_owners_persist() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested").join("keystore.json");
+
+        let mut keystore = KeyStore::open(&path).unwrap();
+        assert!(keystore.record_owner("VLD0:record").is_none());
+        keystore.set_record_owner("VLD0:record", "owner:secret".to_string()).unwrap();
+
+        let reopened = KeyStore::open(&path).unwrap();
+        assert_eq!(reopened.record_owner("VLD0:record"), Some("owner:secret"));
+        assert_eq!(reopened.owned_records(), vec!["VLD0:record".to_string()]);
+
+        let mut reopened = reopened;
+        reopened.remove_record_owner("VLD0:record").unwrap();
+        assert!(KeyStore::open(&path).unwrap().record_owner("VLD0:record").is_none());
+
+        reopened.set_site_key("VLD0:record", "00ff".to_string()).unwrap();
+        assert_eq!(KeyStore::open(&path).unwrap().site_key("VLD0:record"), Some("00ff"));
+        reopened.remove_site_key("VLD0:record").unwrap();
+        assert!(KeyStore::open(&path).unwrap().site_key("VLD0:record").is_none());
+
+        let recipient = reopened.create_recipient_key().unwrap();
+        let reopened = KeyStore::open(&path).unwrap();
+        assert_eq!(reopened.recipient_public_keys(), vec![recipient.clone()]);
+        let secret = reopened.recipient_secret(&recipient).unwrap();
+        assert_eq!(CryptoManager::new().unwrap().x25519_public_key(secret).unwrap(), recipient);
+    }
+
+    #[test]
+    fn test_keystore_sealed_under_password() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("keystore.json");
+
+        // A plaintext keystore is sealed as soon as it is unlocked
+        KeyStore::open(&path).unwrap().set_site_key("VLD0:site", "secret".to_string()).unwrap();
+        let mut keystore = KeyStore::unlock_with_kdf(&path, "correct horse", TEST_KDF).unwrap();
+        assert!(keystore.is_sealed());
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("secret"));
+
+        // The device password is generated once and kept
+        let device_password = keystore.ensure_device_password().unwrap();
+        assert_eq!(keystore.ensure_device_password().unwrap(), device_password);
+
+        let reopened = KeyStore::unlock(&path, "correct horse").unwrap();
+        assert_eq!(reopened.site_key("VLD0:site"), Some("secret"));
+        assert_eq!(reopened.device_password(), Some(device_password.as_str()));
+
+        assert!(matches!(KeyStore::open(&path), Err(RoseliteError::Crypto(CryptoError::KeystoreLocked))));
+        assert!(KeyStore::unlock(&path, "battery staple").is_err());
+        assert!(KeyStore::unlock(&path, "").is_err());
+        assert!(KeyStore::in_memory().ensure_device_password().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_password_fd() {
+        use std::os::fd::AsRawFd;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("password");
+        std::fs::write(&path, "correct horse\nignored\n").unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        assert_eq!(read_password_fd(file.as_raw_fd()).unwrap(), "correct horse");
+
+        // A descriptor that is not open is refused rather than adopted
+        assert!(read_password_fd(i32::MAX).is_err());
+        assert!(read_password_fd(-1).is_err());
+    }
+}
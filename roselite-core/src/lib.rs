@@ -7,19 +7,35 @@
 //! - Cryptographic signing and verification
 //! - App metadata management
 
+pub mod archive;
 pub mod error;
 pub mod package;
 pub mod store;
 pub mod crypto;
+pub mod dht;
+pub mod identity;
+pub mod journal;
+pub mod keys;
+pub mod keystore;
+pub mod trust;
 pub mod types;
 pub mod veilid;
 
 // Re-export commonly used types
+pub use archive::Codec;
+pub use dht::{DhtBackend, MemoryDht};
 pub use error::{Result, RoseliteError};
-pub use package::{Package, PackageBuilder, PackageManifest};
-pub use store::{AppStore, VeilidStore};
+pub use identity::{Identity, IdentityStore, KeyBackup};
+pub use journal::PublishJournal;
+pub use keys::{HashDigest, PublicKey, SecretKey, Signature};
+pub use keystore::KeyStore;
+pub use package::{Package, PackageBuilder, PackageManifest, SignaturePolicy, SigningRequest};
+pub use store::{AppStore, TransferConfig, VeilidStore};
+pub use trust::{TrustDecision, TrustMode, TrustPolicy};
 pub use types::{AppId, AppInfo, VeilUri};
 
+use std::path::PathBuf;
+
 /// Current version of the Roselite package format
 pub const PACKAGE_FORMAT_VERSION: &str = "1.0.0";
 
@@ -27,4 +43,17 @@ pub const PACKAGE_FORMAT_VERSION: &str = "1.0.0";
 pub const PACKAGE_EXTENSION: &str = ".veilidpkg";
 
 /// Manifest filename within packages
-pub const MANIFEST_FILENAME: &str = "veilid.json"; 
\ No newline at end of file
+pub const MANIFEST_FILENAME: &str = "veilid.json"; 
+
+/// Detached package signature filename within packages
+pub const SIGNATURE_FILENAME: &str = "veilid.sig";
+
+/// Directory holding Roselite's per-user configuration, keystore and trust store. An error when
+/// the platform has no per-user config directory, rather than falling back to the working directory.
+pub fn config_dir() -> Result<PathBuf> {
+    let dir = dirs::config_dir().ok_or_else(|| std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "no per-user config directory for the keystore (is HOME set?)",
+    ))?;
+    Ok(dir.join("roselite"))
+}
@@ -1,13 +1,14 @@
 use serde::{Deserialize, Serialize};
 use flate2::read::GzDecoder;
-use flate2::write::GzEncoder;
-use flate2::Compression;
-use tar::{Archive, Builder};
+use tar::Archive;
 use std::path::Path;
+use crate::archive::{package_path, Codec, IndexWriter, PackageIndex};
 use crate::error::*;
 use crate::types::*;
 use crate::crypto::CryptoManager;
-use std::io::{Read, Write};
+use crate::keys::{HashDigest, PublicKey, SecretKey, Signature};
+use crate::trust::{TrustDecision, TrustPolicy};
+use std::io::Read;
 use chrono::{DateTime, Utc};
 use std::io::Cursor;
 
@@ -35,15 +36,151 @@ pub struct PackageManifest {
     pub created_at: DateTime<Utc>,
     #[serde(default = "Utc::now")]
     pub updated_at: DateTime<Utc>,
-    /// Public key for signature verification
-    #[serde(default)]
-    pub public_key: String,
+    /// Public key for signature verification, absent for unsigned packages
+    #[serde(default, with = "crate::keys::empty_as_none")]
+    pub public_key: Option<PublicKey>,
+    /// Keys that must also sign the package, for sites whose releases need approval
+    /// from several maintainers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_policy: Option<SignaturePolicy>,
 }
 
 impl PackageManifest {
     // No additional methods needed for slug functionality
 }
 
+/// Release approval rule: at least `threshold` of `keys` must sign the package digest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignaturePolicy {
+    pub threshold: usize,
+    pub keys: Vec<PublicKey>,
+}
+
+impl SignaturePolicy {
+    /// How many distinct policy keys made a valid signature of `digest`
+    pub fn approvals<'a>(
+        &self,
+        crypto: &CryptoManager,
+        digest: &HashDigest,
+        signatures: impl IntoIterator<Item = (&'a PublicKey, &'a Signature)>,
+    ) -> Result<usize> {
+        let mut approved = std::collections::HashSet::new();
+        for (public_key, signature) in signatures {
+            if self.keys.contains(public_key)
+                && !approved.contains(public_key)
+                && crypto.verify(digest.to_string().as_bytes(), signature, public_key)?
+            {
+                approved.insert(*public_key);
+            }
+        }
+        Ok(approved.len())
+    }
+
+    /// Whether any set of signatures meeting this policy also meets `other`: it draws on no
+    /// keys outside `other` and needs at least as many of them
+    pub fn is_as_strict_as(&self, other: &SignaturePolicy) -> bool {
+        self.threshold >= other.threshold && self.keys.iter().all(|key| other.keys.contains(key))
+    }
+}
+
+/// Detached signature stored as the last entry of a package archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageSignature {
+    /// Canonical digest over the path and BLAKE3 hash of every other archive entry
+    pub digest: HashDigest,
+    /// Ed25519 signature of the digest's hex text by the manifest's `public_key`, absent
+    /// while only co-signatures have been collected
+    #[serde(default, with = "crate::keys::empty_as_none")]
+    pub signature: Option<Signature>,
+    /// Signatures of the digest by keys of the manifest's signature policy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cosignatures: Vec<CoSignature>,
+}
+
+/// Signature of a package digest by one of the keys of its signature policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoSignature {
+    pub public_key: PublicKey,
+    pub signature: Signature,
+}
+
+impl PackageSignature {
+    /// Check the signatures against the manifest they were made for: the manifest's key must
+    /// have signed the digest, and enough keys to meet its signature policy
+    pub fn verify(&self, crypto: &CryptoManager, manifest: &PackageManifest) -> Result<bool> {
+        let (Some(public_key), Some(signature)) = (&manifest.public_key, &self.signature) else {
+            return Ok(false);
+        };
+        if !crypto.verify(self.digest.to_string().as_bytes(), signature, public_key)? {
+            return Ok(false);
+        }
+        let Some(policy) = &manifest.signature_policy else {
+            return Ok(true);
+        };
+
+        let signatures = std::iter::once((public_key, signature))
+            .chain(self.cosignatures.iter().map(|cosignature| (&cosignature.public_key, &cosignature.signature)));
+        Ok(policy.approvals(crypto, &self.digest, signatures)? >= policy.threshold)
+    }
+}
+
+/// What `roselite sign` is asked to sign for a package bundled without its developer secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningRequest {
+    pub name: String,
+    pub version: String,
+    /// Developer key the manifest declares, which must make the signature
+    pub public_key: PublicKey,
+    /// Canonical digest of the package, as `PackageSignature` will record it
+    pub digest: HashDigest,
+}
+
+/// The files of a package as a detached signature sees them
+#[derive(Default)]
+struct SignedParts {
+    /// Path and hash of every entry but the detached signature
+    entries: Vec<(String, HashDigest)>,
+    /// The manifest inside the package, which is what was signed
+    manifest: Option<PackageManifest>,
+    detached: Option<PackageSignature>,
+}
+
+impl SignedParts {
+    fn add(&mut self, crypto: &CryptoManager, path: String, data: &[u8]) -> Result<()> {
+        if path == crate::SIGNATURE_FILENAME {
+            self.detached = serde_json::from_slice(data).ok();
+            return Ok(());
+        }
+        if path == crate::MANIFEST_FILENAME {
+            self.manifest = serde_json::from_slice(data).ok();
+        }
+        self.entries.push((path, crypto.hash(data)?));
+        Ok(())
+    }
+
+    fn digest(&self, crypto: &CryptoManager) -> Result<HashDigest> {
+        Package::content_digest(crypto, &self.entries)
+    }
+
+    /// Whether every file matches the signed digest and the signatures satisfy the manifest
+    /// that declares `public_key`
+    fn verify(&self, crypto: &CryptoManager, public_key: &PublicKey) -> Result<bool> {
+        let (Some(manifest), Some(detached)) = (&self.manifest, &self.detached) else {
+            return Ok(false);
+        };
+        if manifest.public_key != Some(*public_key) || self.digest(crypto)? != detached.digest {
+            return Ok(false);
+        }
+        detached.verify(crypto, manifest)
+    }
+}
+
+/// Path and contents of a file stored in a package
+type StoredFile = (String, Vec<u8>);
+
+/// Stored manifest and detached signature of a package, when present
+type StoredMetadata = (Option<Vec<u8>>, Option<Vec<u8>>);
+
 /// App permissions for sandboxing
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Permission {
@@ -55,12 +192,25 @@ pub enum Permission {
 }
 
 /// Complete package with manifest and content
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Package {
     pub manifest: PackageManifest,
     pub content: Vec<u8>,
     pub size_bytes: u64,
     pub data: Vec<u8>,
+    /// Developer secret key, kept in memory by `PackageBuilder` so a freshly built
+    /// package can be published and signed without a keystore
+    #[serde(skip)]
+    pub signing_key: Option<SecretKey>,
+}
+
+impl std::fmt::Debug for Package {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Package")
+            .field("manifest", &self.manifest)
+            .field("size_bytes", &self.size_bytes)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Package {
@@ -74,31 +224,27 @@ impl Package {
     pub async fn from_bytes(content: Vec<u8>) -> Result<Self> {
         let size_bytes = content.len() as u64;
         
-        // Decompress the package
-        let decoder = GzDecoder::new(&content[..]);
-        let mut archive = Archive::new(decoder);
-        
-        // Find and read the manifest
-        let mut manifest_content = Vec::new();
-        let mut found_manifest = false;
-        
-        for entry in archive.entries()? {
-            let mut entry = entry?;
-            let path = entry.path()?.to_path_buf();
-            
-            if path.file_name().and_then(|n| n.to_str()) == Some(crate::MANIFEST_FILENAME) {
-                entry.read_to_end(&mut manifest_content)?;
-                found_manifest = true;
-                break;
+        // Find and read the manifest and the detached signature
+        let (manifest_content, signature) = match PackageIndex::read(&content)? {
+            Some((index, data_start)) => {
+                let read = |path: &str| index.get(path)
+                    .map(|entry| index.read_file(&content, data_start, entry))
+                    .transpose();
+                (read(crate::MANIFEST_FILENAME)?, read(crate::SIGNATURE_FILENAME)?)
             }
-        }
+            None => Self::read_legacy_metadata(&content)?,
+        };
+        let signature = signature.and_then(|signature| serde_json::from_slice::<PackageSignature>(&signature).ok());
         
-        if !found_manifest {
+        let Some(manifest_content) = manifest_content else {
             return Err(PackageError::MissingManifest.into());
-        }
+        };
         
-        // Parse manifest
-        let manifest: PackageManifest = serde_json::from_slice(&manifest_content)?;
+        // Parse manifest; the signature inside the archive is detached from it
+        let mut manifest: PackageManifest = serde_json::from_slice(&manifest_content)?;
+        if let Some(signature) = signature.and_then(|signature| signature.signature) {
+            manifest.signature = signature.to_string();
+        }
         
         // Validate manifest
         Self::validate_manifest(&manifest)?;
@@ -108,9 +254,69 @@ impl Package {
             content,
             size_bytes,
             data: Vec::new(),
+            signing_key: None,
         })
     }
 
+    /// Manifest and detached signature of a package built as a single tar.gz stream
+    fn read_legacy_metadata(content: &[u8]) -> Result<StoredMetadata> {
+        let mut archive = Archive::new(GzDecoder::new(content));
+        let mut manifest = None;
+        let mut signature = None;
+        
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_path_buf();
+            
+            if path.as_os_str() == crate::SIGNATURE_FILENAME {
+                let mut signature_content = Vec::new();
+                entry.read_to_end(&mut signature_content)?;
+                signature = Some(signature_content);
+            } else if manifest.is_none() && path.file_name().and_then(|n| n.to_str()) == Some(crate::MANIFEST_FILENAME) {
+                let mut manifest_content = Vec::new();
+                entry.read_to_end(&mut manifest_content)?;
+                manifest = Some(manifest_content);
+            }
+        }
+        Ok((manifest, signature))
+    }
+
+    /// Every file stored in the package with its contents. `None` when a legacy archive
+    /// holds links or special files, which a content hash cannot cover.
+    fn entries(&self) -> Result<Option<Vec<StoredFile>>> {
+        if let Some((index, data_start)) = PackageIndex::read(&self.content)? {
+            let entries = index.files.iter()
+                .map(|entry| Ok((entry.path.clone(), index.read_file(&self.content, data_start, entry)?)))
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(Some(entries));
+        }
+
+        let mut entries = Vec::new();
+        let mut archive = Archive::new(GzDecoder::new(Cursor::new(&self.content)));
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = package_path(&entry.path()?.to_string_lossy());
+            let entry_type = entry.header().entry_type();
+            if entry_type.is_dir() {
+                continue;
+            }
+            if !entry_type.is_file() {
+                tracing::warn!("Package entry {} is not a regular file", path);
+                return Ok(None);
+            }
+
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            entries.push((path, data));
+        }
+        Ok(Some(entries))
+    }
+
+    /// Codec the package was built with, as its header records it
+    pub fn compression(&self) -> Result<Codec> {
+        crate::archive::codec(&self.content)
+    }
+
     /// Convert to app info for listings
     pub fn to_app_info(&self) -> AppInfo {
         let now = Utc::now();
@@ -134,25 +340,143 @@ impl Package {
         }
     }
 
-    /// Validate package signature using crypto manager
+    /// Validate the detached package signature: every archive entry must match the signed
+    /// digest, the digest must be signed by the key in the archive's manifest, and by enough
+    /// keys to meet the manifest's signature policy if it has one.
     pub fn verify_signature(&self, crypto: &CryptoManager) -> Result<bool> {
-        if self.manifest.signature.is_empty() || self.manifest.public_key.is_empty() {
+        let Some(public_key) = &self.manifest.public_key else {
             return Ok(false);
+        };
+        match self.signed_parts(crypto)? {
+            Some(parts) => parts.verify(crypto, public_key),
+            None => Ok(false),
         }
-        
-        // Create the signed data (manifest without signature)
-        let mut unsigned_manifest = self.manifest.clone();
-        unsigned_manifest.signature = String::new();
-        
-        let manifest_data = serde_json::to_vec(&unsigned_manifest)
-            .map_err(|e| CryptoError::InvalidKey(format!("Failed to serialize manifest: {}", e)))?;
-        
-        // Verify the signature
-        crypto.verify(
-            &manifest_data,
-            &self.manifest.signature,
-            &self.manifest.public_key
-        )
+    }
+
+    /// Validate an unpacked package the way `verify_signature` validates the archive
+    pub fn verify_extracted(crypto: &CryptoManager, dir: &Path) -> Result<bool> {
+        let mut files = Vec::new();
+        PackageBuilder::collect_files_recursive(dir, dir, &mut files)?;
+        let mut parts = SignedParts::default();
+        for (path, full_path) in files {
+            parts.add(crypto, path, &std::fs::read(&full_path)?)?;
+        }
+
+        let Some(public_key) = parts.manifest.as_ref().and_then(|manifest| manifest.public_key) else {
+            return Ok(false);
+        };
+        parts.verify(crypto, &public_key)
+    }
+
+    /// What has to be signed for a package built with `PackageBuilder::public_key`
+    pub fn signing_request(&self, crypto: &CryptoManager) -> Result<SigningRequest> {
+        let public_key = self.manifest.public_key.ok_or_else(|| PackageError::InvalidManifest {
+            reason: "package declares no developer key to sign with".to_string(),
+        })?;
+        let parts = self.signed_parts(crypto)?.ok_or(PackageError::InvalidFormat)?;
+        if parts.manifest.as_ref().map(|manifest| manifest.public_key) != Some(Some(public_key)) {
+            return Err(PackageError::InvalidSignature.into());
+        }
+
+        Ok(SigningRequest {
+            name: self.manifest.name.clone(),
+            version: self.manifest.version.clone(),
+            public_key,
+            digest: parts.digest(crypto)?,
+        })
+    }
+
+    /// Sign the package and attach the detached signature. The manifest's own key signs as
+    /// the publisher; any other key of its signature policy adds a co-signature. Signatures
+    /// already attached are kept while they cover the same digest. Needs no network, so
+    /// release keys can stay on an offline machine, and the files are not rebuilt.
+    pub fn sign(&mut self, crypto: &CryptoManager, secret_key: &SecretKey) -> Result<SigningRequest> {
+        let request = self.signing_request(crypto)?;
+        let signer = secret_key.public_key();
+        let cosigner = self.manifest.signature_policy.as_ref()
+            .is_some_and(|policy| policy.keys.contains(&signer));
+        if signer != request.public_key && !cosigner {
+            return Err(CryptoError::InvalidKey(format!(
+                "{} is neither the package's developer key {} nor in its signature policy", signer, request.public_key
+            )).into());
+        }
+        let signature = crypto.sign(request.digest.to_string().as_bytes(), secret_key)?;
+
+        let mut detached = self.signed_parts(crypto)?
+            .and_then(|parts| parts.detached)
+            .filter(|detached| detached.digest == request.digest)
+            .unwrap_or(PackageSignature { digest: request.digest, signature: None, cosignatures: Vec::new() });
+        if signer == request.public_key {
+            detached.signature = Some(signature);
+        } else {
+            detached.cosignatures.retain(|cosignature| cosignature.public_key != signer);
+            detached.cosignatures.push(CoSignature { public_key: signer, signature });
+        }
+
+        // Files are kept as they are stored, in the package's own codec; legacy packages are
+        // rewritten in the indexed layout. Either way the signed digest stays the same.
+        let codec = self.compression()?;
+        let mut writer = IndexWriter::with_codec(codec, codec.default_level())?;
+        match PackageIndex::read(&self.content)? {
+            Some((index, data_start)) => {
+                for entry in index.files.iter().filter(|entry| entry.path != crate::SIGNATURE_FILENAME) {
+                    writer.copy(entry, index.stored(&self.content, data_start, entry)?)?;
+                }
+            }
+            None => {
+                for (path, data) in self.entries()?.ok_or(PackageError::InvalidFormat)? {
+                    if path != crate::SIGNATURE_FILENAME {
+                        writer.add(crypto, &path, &data)?;
+                    }
+                }
+            }
+        }
+        writer.add(crypto, crate::SIGNATURE_FILENAME, &serde_json::to_vec(&detached)?)?;
+        let content = writer.finish()?;
+
+        self.manifest.signature = detached.signature.map(|signature| signature.to_string()).unwrap_or_default();
+        self.size_bytes = content.len() as u64;
+        self.content = content;
+        Ok(request)
+    }
+
+    /// Read the package entries a signature covers. `None` when the package holds links or
+    /// special files, which a content hash cannot cover.
+    fn signed_parts(&self, crypto: &CryptoManager) -> Result<Option<SignedParts>> {
+        let Some(entries) = self.entries()? else {
+            return Ok(None);
+        };
+        let mut parts = SignedParts::default();
+        for (path, data) in entries {
+            parts.add(crypto, path, &data)?;
+        }
+        Ok(Some(parts))
+    }
+
+    /// Validate the signature as `verify_signature` does, then check the signing key against
+    /// the trust policy as the publisher key of `subject`, and the manifest's signature policy
+    /// against the one pinned for it. Revoked and untrusted keys and weakened policies are an
+    /// error unless the policy only warns, in which case the decision is returned for flagging.
+    pub fn verify_trusted(&self, crypto: &CryptoManager, policy: &mut TrustPolicy, subject: &str) -> Result<TrustDecision> {
+        if !self.verify_signature(crypto)? {
+            return Err(CryptoError::SignatureVerificationFailed.into());
+        }
+        let Some(public_key) = &self.manifest.public_key else {
+            return Err(CryptoError::SignatureVerificationFailed.into());
+        };
+        let decision = policy.check(subject, public_key)?;
+        let decision = policy.enforce(decision)?;
+        let policy_decision = policy.check_policy(subject, self.manifest.signature_policy.as_ref())?;
+        let policy_decision = policy.enforce(policy_decision)?;
+        Ok(if policy_decision.is_trusted() { decision } else { policy_decision })
+    }
+
+    /// Canonical digest over archive entries: BLAKE3 of the JSON list of
+    /// `(path, BLAKE3 hash)` pairs sorted by path.
+    pub fn content_digest(crypto: &CryptoManager, entries: &[(String, HashDigest)]) -> Result<HashDigest> {
+        let mut entries = entries.to_vec();
+        entries.sort_by(|(a, a_hash), (b, b_hash)| (a, a_hash.as_bytes()).cmp(&(b, b_hash.as_bytes())));
+        crypto.hash(&serde_json::to_vec(&entries)?)
     }
 
     fn validate_manifest(manifest: &PackageManifest) -> Result<()> {
@@ -179,60 +503,65 @@ impl Package {
                 reason: "identity cannot be empty".to_string() 
             }.into());
         }
+
+        if let Some(policy) = &manifest.signature_policy {
+            let distinct: std::collections::HashSet<_> = policy.keys.iter().collect();
+            if policy.threshold == 0 || policy.threshold > distinct.len() {
+                return Err(PackageError::InvalidManifest {
+                    reason: format!("signature policy needs {} of {} distinct keys", policy.threshold, distinct.len())
+                }.into());
+            }
+        }
         
         Ok(())
     }
 
+    /// Whether a path holds package metadata rather than a file of the site
+    fn is_metadata(path: &str) -> bool {
+        Path::new(path).file_name().and_then(|n| n.to_str()) == Some(crate::MANIFEST_FILENAME)
+            || path == crate::SIGNATURE_FILENAME
+    }
+
     /// Extract individual files from the package for direct serving
     pub async fn extract_files(&self) -> Result<std::collections::HashMap<String, Vec<u8>>> {
-        let mut files = std::collections::HashMap::new();
-        
-        // Decompress the package
-        let decoder = GzDecoder::new(Cursor::new(&self.content));
-        let mut archive = Archive::new(decoder);
-        
-        // Extract all files
-        for entry in archive.entries()? {
-            let mut entry = entry?;
-            let path = entry.path()?.to_path_buf();
-            
-            // Skip the manifest file since it's metadata
-            if path.file_name().and_then(|n| n.to_str()) == Some(crate::MANIFEST_FILENAME) {
-                continue;
-            }
-            
-            let mut content = Vec::new();
-            entry.read_to_end(&mut content)?;
-            
-            // Use forward slashes for web compatibility
-            let web_path = path.to_string_lossy().replace('\\', "/");
-            files.insert(web_path, content);
-        }
-        
-        Ok(files)
+        // Skip the manifest and signature since they're metadata
+        Ok(self.entries()?
+            .ok_or(PackageError::InvalidFormat)?
+            .into_iter()
+            .filter(|(path, _)| !Self::is_metadata(path))
+            .collect())
     }
 
     /// Get the entry point file content directly
     pub async fn get_entry_file(&self) -> Result<Vec<u8>> {
-        let files = self.extract_files().await?;
-        
-        files.get(&self.manifest.entry)
-            .cloned()
+        self.get_file(&self.manifest.entry).await?
             .ok_or_else(|| PackageError::InvalidManifest {
                 reason: format!("Entry file '{}' not found in package", self.manifest.entry)
             }.into())
     }
 
-    /// Get a specific file from the package
+    /// Get a specific file from the package, decompressing only that file
     pub async fn get_file(&self, path: &str) -> Result<Option<Vec<u8>>> {
-        let files = self.extract_files().await?;
-        Ok(files.get(path).cloned())
+        if Self::is_metadata(path) {
+            return Ok(None);
+        }
+        match PackageIndex::read(&self.content)? {
+            Some((index, data_start)) => index.get(path)
+                .map(|entry| index.read_file(&self.content, data_start, entry))
+                .transpose(),
+            None => Ok(self.extract_files().await?.remove(path)),
+        }
     }
 
-    /// List all files in the package
+    /// List all files in the package, from its file table without decompressing anything
     pub async fn list_files(&self) -> Result<Vec<String>> {
-        let files = self.extract_files().await?;
-        Ok(files.keys().cloned().collect())
+        match PackageIndex::read(&self.content)? {
+            Some((index, _)) => Ok(index.files.into_iter()
+                .map(|entry| entry.path)
+                .filter(|path| !Self::is_metadata(path))
+                .collect()),
+            None => Ok(self.extract_files().await?.into_keys().collect()),
+        }
     }
 
     /// Set the DHT key for this package
@@ -256,10 +585,20 @@ pub struct PackageBuilder {
     tags: Vec<String>,
     source_dir: std::path::PathBuf,
     identity: Option<String>,
-    private_key: Option<String>,
-    public_key: Option<String>,
+    private_key: Option<SecretKey>,
+    public_key: Option<PublicKey>,
+    signature_policy: Option<SignaturePolicy>,
+    deterministic: bool,
+    timestamp: Option<DateTime<Utc>>,
+    respect_gitignore: bool,
+    excludes: Vec<std::path::PathBuf>,
+    compression: Codec,
+    compression_level: Option<i32>,
 }
 
+/// Ignore file read from every directory of a site, in gitignore syntax
+pub const IGNORE_FILENAME: &str = ".roseliteignore";
+
 impl PackageBuilder {
     /// Create a new package builder
     pub fn new<P: AsRef<Path>>(name: String, source_dir: P) -> Self {
@@ -274,6 +613,13 @@ impl PackageBuilder {
             identity: None,
             private_key: None,
             public_key: None,
+            signature_policy: None,
+            deterministic: false,
+            timestamp: None,
+            respect_gitignore: true,
+            excludes: Vec::new(),
+            compression: Codec::Zstd,
+            compression_level: None,
         }
     }
 
@@ -314,31 +660,145 @@ impl PackageBuilder {
     }
     
     /// Set the signing keypair
-    pub fn keypair(mut self, public_key: String, private_key: String) -> Self {
+    pub fn keypair(mut self, public_key: PublicKey, private_key: SecretKey) -> Self {
         self.public_key = Some(public_key);
         self.private_key = Some(private_key);
         self
     }
 
+    /// Declare the developer key without its secret. The package is built unsigned, to be
+    /// signed later with `Package::sign`.
+    pub fn public_key(mut self, public_key: PublicKey) -> Self {
+        self.public_key = Some(public_key);
+        self.private_key = None;
+        self
+    }
+
+    /// Require co-signatures from enough keys of `policy` before the package verifies
+    pub fn signature_policy(mut self, policy: SignaturePolicy) -> Self {
+        self.signature_policy = Some(policy);
+        self
+    }
+
+    /// Build byte-identical packages from identical sources and keys, so independent builds
+    /// can be cross-checked. The manifest records the time from `SOURCE_DATE_EPOCH`, or the
+    /// Unix epoch when it is unset, unless `timestamp` gives one.
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Record this time in the manifest instead of the build time
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Whether `.gitignore` files in the source directory exclude files, as they do by default.
+    /// `.roseliteignore` files always apply.
+    pub fn respect_gitignore(mut self, respect: bool) -> Self {
+        self.respect_gitignore = respect;
+        self
+    }
+
+    /// Codec to compress the package's files with (default: zstd)
+    pub fn compression(mut self, codec: Codec) -> Self {
+        self.compression = codec;
+        self
+    }
+
+    /// Compression level, within the codec's range (default: the codec's own default)
+    pub fn compression_level(mut self, level: i32) -> Self {
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// Files of the source directory that go into the package, as their path in the package
+    /// and on disk, in path order. Files matched by `.roseliteignore` or `.gitignore` files
+    /// inside the source directory are left out, and so are dotfiles unless an ignore file
+    /// includes them with a `!` pattern.
+    pub fn source_files(&self) -> Result<Vec<(String, std::path::PathBuf)>> {
+        if !self.source_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        // Only ignore files inside the site count, so a build does not depend on the machine
+        let walker = ignore::WalkBuilder::new(&self.source_dir)
+            .hidden(true)
+            .parents(false)
+            .git_ignore(self.respect_gitignore)
+            .git_global(false)
+            .git_exclude(false)
+            .require_git(false)
+            .follow_links(true)
+            .add_custom_ignore_filename(IGNORE_FILENAME)
+            .build();
+        // Excluded files that do not exist yet cannot be in the walk
+        let excluded: Vec<_> = self.excludes.iter().filter_map(|path| path.canonicalize().ok()).collect();
+
+        let mut files = Vec::new();
+        for entry in walker {
+            let entry = entry.map_err(|e| std::io::Error::other(e.to_string()))?;
+            if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+                continue;
+            }
+            if !excluded.is_empty() && entry.path().canonicalize().is_ok_and(|path| excluded.contains(&path)) {
+                continue;
+            }
+            let relative_path = entry.path().strip_prefix(&self.source_dir)
+                .map_err(|_| PackageError::InvalidFormat)?;
+            files.push((package_path(&relative_path.to_string_lossy()), entry.path().to_path_buf()));
+        }
+        // Directory listing order differs between filesystems
+        files.sort();
+        Ok(files)
+    }
+
+    /// Leave a file out of the package even if it sits in the source directory, such as the
+    /// project config or the package being written
+    pub fn exclude<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.excludes.push(path.as_ref().to_path_buf());
+        self
+    }
+
     /// Build the package
     pub async fn build(self) -> Result<Package> {
         // Initialize crypto manager for keypair and signing operations
         let crypto = CryptoManager::new()?;
+
+        // A random key would make every deterministic build different
+        if self.deterministic && self.public_key.is_none() {
+            return Err(RoseliteError::ValidationError(
+                "A deterministic build needs a developer key".to_string()
+            ));
+        }
+        let files = self.source_files()?;
+        let mut writer = IndexWriter::with_codec(
+            self.compression,
+            self.compression_level.unwrap_or(self.compression.default_level()),
+        )?;
+        let timestamp = match self.timestamp {
+            Some(timestamp) => timestamp,
+            None if self.deterministic => source_date_epoch(std::env::var(SOURCE_DATE_EPOCH_ENV).ok().as_deref())?,
+            None => Utc::now(),
+        };
         
         // Generate identity if not provided
-        let identity = match self.identity {
-            Some(id) => id,
-            None => {
+        let identity = match (self.identity, self.public_key) {
+            (Some(id), _) => id,
+            (None, Some(public_key)) if self.deterministic => public_key.to_string(),
+            (None, _) => {
                 let (public_key, _) = crypto.generate_keypair()?;
-                public_key
+                public_key.to_string()
             }
         };
 
-        // Generate keypair if not provided
+        // Generate keypair if not provided; a key declared without its secret is signed offline
         let (public_key, private_key) = match (self.public_key, self.private_key) {
-            (Some(pub_key), Some(priv_key)) => (pub_key, priv_key),
+            (Some(pub_key), priv_key) => (pub_key, priv_key),
             _ => {
-            crypto.generate_keypair()?
+                let (pub_key, priv_key) = crypto.generate_keypair()?;
+                (pub_key, Some(priv_key))
             }
         };
         
@@ -358,82 +818,60 @@ impl PackageBuilder {
             format_version: "1.0.0".to_string(),
             dependencies: Vec::new(),
             permissions: Vec::new(),
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-            public_key: public_key.clone(),
+            created_at: timestamp,
+            updated_at: timestamp,
+            public_key: Some(public_key),
+            signature_policy: self.signature_policy,
         };
+        Package::validate_manifest(&manifest)?;
 
-        // Create tarball from source directory
-        let mut tar_data = Vec::new();
-        {
-            let encoder = GzEncoder::new(&mut tar_data, Compression::default());
-            let mut tar_builder = Builder::new(encoder);
+        // Lay out the site's files, then the manifest and the detached signature
+        let mut entries = Self::add_files(&mut writer, files, &crypto).await?;
 
-            // Add all files from source directory
-            if self.source_dir.exists() {
-                Self::add_directory_to_tar(&mut tar_builder, &self.source_dir, &self.source_dir).await?;
-            }
+        let manifest_json = serde_json::to_vec(&manifest)
+            .map_err(|e| PackageError::InvalidManifest { 
+                reason: format!("Failed to serialize manifest: {}", e) 
+            })?;
+        entries.push(writer.add(&crypto, crate::MANIFEST_FILENAME, &manifest_json)?);
 
-            // Add manifest to the tar
-            let manifest_json = serde_json::to_vec(&manifest)
-                .map_err(|e| PackageError::InvalidManifest { 
-                    reason: format!("Failed to serialize manifest: {}", e) 
-                })?;
-            
-            let mut header = tar::Header::new_gnu();
-            header.set_path(crate::MANIFEST_FILENAME)?;
-            header.set_size(manifest_json.len() as u64);
-            header.set_mode(0o644);
-            header.set_cksum();
-            
-            tar_builder.append(&header, manifest_json.as_slice())?;
-            
-            // Finish the tar
-            let encoder = tar_builder.into_inner()?;
-            encoder.finish()?;
+        // Sign the digest of every entry and store it detached, as the last entry
+        if let Some(private_key) = &private_key {
+            let digest = Package::content_digest(&crypto, &entries)?;
+            let signed = crypto.sign(digest.to_string().as_bytes(), private_key)?;
+            let detached = serde_json::to_vec(&PackageSignature {
+                digest,
+                signature: Some(signed),
+                cosignatures: Vec::new(),
+            })?;
+            writer.add(&crypto, crate::SIGNATURE_FILENAME, &detached)?;
+            manifest.signature = signed.to_string();
         }
+        let content = writer.finish()?;
 
-        // Sign the manifest
-        let manifest_data = serde_json::to_vec(&manifest)
-            .map_err(|e| CryptoError::InvalidKey(format!("Failed to serialize manifest for signing: {}", e)))?;
-        
-        let signature = crypto.sign(&manifest_data, &private_key)?;
-        manifest.signature = signature;
-
-        let size_bytes = tar_data.len() as u64;
+        let size_bytes = content.len() as u64;
         
         Ok(Package {
             manifest,
-            content: tar_data.clone(),
+            content: content.clone(),
             size_bytes,
-            data: tar_data,
+            data: content,
+            signing_key: private_key,
         })
     }
     
-    /// Add directory contents to tar using walkdir for simplicity
-    async fn add_directory_to_tar<W: Write>(
-        tar_builder: &mut Builder<W>,
-        dir_path: &Path,
-        base_path: &Path,
-    ) -> Result<()> {
-        // Collect all files first to avoid async recursion
-        let mut files_to_add = Vec::new();
-        Self::collect_files_recursive(dir_path, base_path, &mut files_to_add)?;
-        
-        // Add all collected files to tar
-        for (relative_path, full_path) in files_to_add {
+    /// Add the site's files, returning the path and hash of every entry written
+    async fn add_files(
+        writer: &mut IndexWriter,
+        files: Vec<(String, std::path::PathBuf)>,
+        crypto: &CryptoManager,
+    ) -> Result<Vec<(String, HashDigest)>> {
+        let mut entries = Vec::new();
+        for (relative_path, full_path) in files {
             let file_data = tokio::fs::read(&full_path).await?;
-            
-            let mut header = tar::Header::new_gnu();
-            header.set_path(&relative_path)?;
-            header.set_size(file_data.len() as u64);
-            header.set_mode(0o644);
-            header.set_cksum();
-            
-            tar_builder.append(&header, file_data.as_slice())?;
+            entries.push(writer.add(crypto, &relative_path, &file_data)?);
         }
         
-        Ok(())
+        Ok(entries)
     }
     
     /// Recursively collect all files (synchronous)
@@ -457,7 +895,7 @@ impl PackageBuilder {
                     .map_err(|_| PackageError::InvalidFormat)?;
                 
                 files.push((
-                    relative_path.to_string_lossy().to_string(),
+                    package_path(&relative_path.to_string_lossy()),
                     entry_path,
                 ));
             }
@@ -467,6 +905,22 @@ impl PackageBuilder {
     }
 }
 
+/// Environment variable overriding the timestamp of a deterministic build, in seconds since
+/// the Unix epoch as reproducible-builds.org defines it
+pub const SOURCE_DATE_EPOCH_ENV: &str = "SOURCE_DATE_EPOCH";
+
+/// Timestamp of a deterministic build: the given `SOURCE_DATE_EPOCH` value, or the Unix epoch
+fn source_date_epoch(value: Option<&str>) -> Result<DateTime<Utc>> {
+    let Some(value) = value else {
+        return Ok(DateTime::UNIX_EPOCH);
+    };
+    value.trim().parse().ok()
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .ok_or_else(|| RoseliteError::ValidationError(format!(
+            "{} must be a number of seconds since the Unix epoch, got {:?}", SOURCE_DATE_EPOCH_ENV, value
+        )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -508,7 +962,7 @@ mod tests {
         assert_eq!(package.manifest.entry, "index.html");
         assert_eq!(package.manifest.tags, vec!["test", "demo"]);
         assert!(!package.manifest.signature.is_empty());
-        assert!(!package.manifest.public_key.is_empty());
+        assert!(package.manifest.public_key.is_some());
         assert!(package.size_bytes > 0);
         assert!(!package.content.is_empty());
 
@@ -516,6 +970,333 @@ mod tests {
         let crypto = CryptoManager::new().unwrap();
         let is_valid = package.verify_signature(&crypto).unwrap();
         assert!(is_valid);
+
+        // The signature travels inside the archive
+        let loaded = Package::from_bytes(package.content.clone()).await.unwrap();
+        assert_eq!(loaded.manifest.signature, package.manifest.signature);
+        assert!(loaded.verify_signature(&crypto).unwrap());
+    }
+
+    /// Rewrite a package, letting `edit` replace or drop entries
+    fn rewrite_archive(content: &[u8], edit: impl Fn(&str, Vec<u8>) -> Option<Vec<u8>>) -> Vec<u8> {
+        let crypto = CryptoManager::new().unwrap();
+        let (index, data_start) = PackageIndex::read(content).unwrap().unwrap();
+        let mut writer = IndexWriter::new();
+        for entry in &index.files {
+            if let Some(data) = edit(&entry.path, index.read_file(content, data_start, entry).unwrap()) {
+                writer.add(&crypto, &entry.path, &data).unwrap();
+            }
+        }
+        writer.finish().unwrap()
+    }
+
+    /// The same files as a single tar.gz stream, as packages were laid out before the file table
+    fn legacy_archive(content: &[u8]) -> Vec<u8> {
+        use flate2::{write::GzEncoder, Compression};
+
+        let (index, data_start) = PackageIndex::read(content).unwrap().unwrap();
+        let mut rewritten = Vec::new();
+        {
+            let mut tar_builder = tar::Builder::new(GzEncoder::new(&mut rewritten, Compression::default()));
+            for entry in &index.files {
+                let data = index.read_file(content, data_start, entry).unwrap();
+                let mut header = tar::Header::new_gnu();
+                header.set_path(&entry.path).unwrap();
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                tar_builder.append(&header, data.as_slice()).unwrap();
+            }
+            tar_builder.into_inner().unwrap().finish().unwrap();
+        }
+        rewritten
+    }
+
+    #[tokio::test]
+    async fn test_file_index() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("css")).unwrap();
+        fs::write(temp_dir.path().join("index.html"), b"<html>indexed</html>").unwrap();
+        fs::write(temp_dir.path().join("css").join("site.css"), b"body { color: red; }".repeat(50)).unwrap();
+        let package = PackageBuilder::new("indexed".to_string(), temp_dir.path())
+            .build()
+            .await
+            .unwrap();
+
+        // Files are listed from the table and read one at a time; metadata is not a site file
+        let mut files = package.list_files().await.unwrap();
+        files.sort();
+        assert_eq!(files, vec!["css/site.css", "index.html"]);
+        assert_eq!(package.get_entry_file().await.unwrap(), b"<html>indexed</html>");
+        assert_eq!(package.get_file("css/site.css").await.unwrap().unwrap(), b"body { color: red; }".repeat(50));
+        assert_eq!(package.get_file("missing.html").await.unwrap(), None);
+        assert_eq!(package.get_file(crate::MANIFEST_FILENAME).await.unwrap(), None);
+
+        // Packages laid out as a single tar.gz stream still load, verify and sign
+        let crypto = CryptoManager::new().unwrap();
+        let mut legacy = Package::from_bytes(legacy_archive(&package.content)).await.unwrap();
+        assert!(PackageIndex::read(&legacy.content).unwrap().is_none());
+        assert_eq!(legacy.manifest.signature, package.manifest.signature);
+        assert!(legacy.verify_signature(&crypto).unwrap());
+        assert_eq!(legacy.get_file("css/site.css").await.unwrap().unwrap(), b"body { color: red; }".repeat(50));
+        assert_eq!(legacy.extract_files().await.unwrap(), package.extract_files().await.unwrap());
+
+        let signing_key = package.signing_key.clone().unwrap();
+        legacy.sign(&crypto, &signing_key).unwrap();
+        assert!(PackageIndex::read(&legacy.content).unwrap().is_some());
+        assert_eq!(legacy.compression().unwrap(), Codec::Gzip);
+        assert!(legacy.verify_signature(&crypto).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_compression_codecs() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("index.html"), "<p>Compressible text</p>\n".repeat(500)).unwrap();
+        let build = |codec: Codec, level: i32| PackageBuilder::new("codecs".to_string(), temp_dir.path())
+            .compression(codec)
+            .compression_level(level)
+            .build();
+
+        let gzip = build(Codec::Gzip, 9).await.unwrap();
+        let zstd = build(Codec::Zstd, 19).await.unwrap();
+        assert!(zstd.content.len() < gzip.content.len());
+
+        // The codec is read back from the header, and files come out the same either way
+        let crypto = CryptoManager::new().unwrap();
+        for package in [gzip, zstd] {
+            let loaded = Package::from_bytes(package.content.clone()).await.unwrap();
+            assert_eq!(loaded.compression().unwrap(), package.compression().unwrap());
+            assert_eq!(loaded.get_entry_file().await.unwrap(), "<p>Compressible text</p>\n".repeat(500).into_bytes());
+            assert!(loaded.verify_signature(&crypto).unwrap());
+        }
+        assert_eq!(PackageBuilder::new("codecs".to_string(), temp_dir.path()).build().await.unwrap().compression().unwrap(), Codec::Zstd);
+
+        // Signing keeps the files as compressed
+        let (public_key, secret_key) = crypto.generate_keypair().unwrap();
+        let mut unsigned = PackageBuilder::new("codecs".to_string(), temp_dir.path())
+            .public_key(public_key)
+            .compression(Codec::Zstd)
+            .compression_level(19)
+            .build()
+            .await
+            .unwrap();
+        let stored = |package: &Package| {
+            let (index, data_start) = PackageIndex::read(&package.content).unwrap().unwrap();
+            index.stored(&package.content, data_start, index.get("index.html").unwrap()).unwrap().to_vec()
+        };
+        let before = stored(&unsigned);
+        unsigned.sign(&crypto, &secret_key).unwrap();
+        assert_eq!(unsigned.compression().unwrap(), Codec::Zstd);
+        assert_eq!(stored(&unsigned), before);
+        assert!(unsigned.verify_signature(&crypto).unwrap());
+
+        assert!(build(Codec::Gzip, 19).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_signature_covers_every_file() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("index.html"), b"<html>original</html>").unwrap();
+        let package = PackageBuilder::new("signed".to_string(), temp_dir.path())
+            .build()
+            .await
+            .unwrap();
+        let crypto = CryptoManager::new().unwrap();
+
+        // Rewriting the archive unchanged keeps it valid
+        let unchanged = rewrite_archive(&package.content, |_, data| Some(data));
+        assert!(Package::from_bytes(unchanged).await.unwrap().verify_signature(&crypto).unwrap());
+
+        // Tampered HTML
+        let tampered = rewrite_archive(&package.content, |path, data| {
+            Some(if path == "index.html" { b"<html>tampered</html>".to_vec() } else { data })
+        });
+        assert!(!Package::from_bytes(tampered).await.unwrap().verify_signature(&crypto).unwrap());
+
+        // A removed file
+        let removed = rewrite_archive(&package.content, |path, data| (path != "index.html").then_some(data));
+        assert!(!Package::from_bytes(removed).await.unwrap().verify_signature(&crypto).unwrap());
+
+        // A missing signature
+        let unsigned = rewrite_archive(&package.content, |path, data| (path != crate::SIGNATURE_FILENAME).then_some(data));
+        assert!(!Package::from_bytes(unsigned).await.unwrap().verify_signature(&crypto).unwrap());
+
+        // The trust policy pins the signer on first use and refuses anyone else for the app
+        let mut policy = TrustPolicy::in_memory();
+        assert_eq!(package.verify_trusted(&crypto, &mut policy, "app").unwrap(), TrustDecision::FirstUse);
+        policy.pin("app", &crypto.generate_keypair().unwrap().0, None).unwrap();
+        assert!(matches!(
+            package.verify_trusted(&crypto, &mut policy, "app"),
+            Err(RoseliteError::Crypto(CryptoError::UntrustedKey(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_offline_signing() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("index.html"), b"<html>release</html>").unwrap();
+        let crypto = CryptoManager::new().unwrap();
+        let (public_key, secret_key) = crypto.generate_keypair().unwrap();
+
+        // Bundled with only the public key, the package carries no signature yet
+        let package = PackageBuilder::new("release".to_string(), temp_dir.path())
+            .public_key(public_key)
+            .build()
+            .await
+            .unwrap();
+        assert!(package.signing_key.is_none());
+        assert!(package.manifest.signature.is_empty());
+        assert!(!package.verify_signature(&crypto).unwrap());
+        let request = package.signing_request(&crypto).unwrap();
+        assert_eq!(request.public_key, public_key);
+
+        // Signing on the offline machine attaches a signature over the requested digest
+        let mut loaded = Package::from_bytes(package.content.clone()).await.unwrap();
+        let (_, someone_else) = crypto.generate_keypair().unwrap();
+        assert!(loaded.sign(&crypto, &someone_else).is_err());
+        assert_eq!(loaded.sign(&crypto, &secret_key).unwrap().digest, request.digest);
+        assert!(loaded.verify_signature(&crypto).unwrap());
+
+        let reloaded = Package::from_bytes(loaded.content.clone()).await.unwrap();
+        assert_eq!(reloaded.manifest.signature, loaded.manifest.signature);
+        assert!(reloaded.verify_signature(&crypto).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_threshold_signatures() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("index.html"), b"<html>shared</html>").unwrap();
+        let crypto = CryptoManager::new().unwrap();
+        let maintainers: Vec<_> = (0..3).map(|_| crypto.generate_keypair().unwrap()).collect();
+        let (publisher, publisher_secret) = maintainers[0].clone();
+        let policy = SignaturePolicy {
+            threshold: 2,
+            keys: maintainers.iter().map(|(public_key, _)| *public_key).collect(),
+        };
+
+        // The publisher's own signature is one approval of the two needed
+        let mut package = PackageBuilder::new("shared".to_string(), temp_dir.path())
+            .keypair(publisher, publisher_secret)
+            .signature_policy(policy.clone())
+            .build()
+            .await
+            .unwrap();
+        assert!(!package.verify_signature(&crypto).unwrap());
+
+        // Outsiders cannot co-sign, and a maintainer signing twice counts once
+        let (_, outsider) = crypto.generate_keypair().unwrap();
+        assert!(package.sign(&crypto, &outsider).is_err());
+        package.sign(&crypto, &maintainers[0].1).unwrap();
+        assert!(!package.verify_signature(&crypto).unwrap());
+
+        // A second maintainer co-signs the existing package
+        package.sign(&crypto, &maintainers[2].1).unwrap();
+        assert!(package.verify_signature(&crypto).unwrap());
+        let loaded = Package::from_bytes(package.content.clone()).await.unwrap();
+        assert!(loaded.verify_signature(&crypto).unwrap());
+
+        // Unpacked, the files verify the same way, and tampering with them is caught
+        let unpacked = temp_dir.path().join("unpacked");
+        crate::archive::unpack(&package.content[..], &unpacked).unwrap();
+        assert!(Package::verify_extracted(&crypto, &unpacked).unwrap());
+        fs::write(unpacked.join("index.html"), b"<html>tampered</html>").unwrap();
+        assert!(!Package::verify_extracted(&crypto, &unpacked).unwrap());
+
+        // The first trusted release pins its policy, so the publisher cannot drop it alone
+        let mut trust = TrustPolicy::in_memory();
+        assert_eq!(package.verify_trusted(&crypto, &mut trust, "app").unwrap(), TrustDecision::FirstUse);
+        let solo = PackageBuilder::new("shared".to_string(), temp_dir.path())
+            .keypair(publisher, maintainers[0].1.clone())
+            .build()
+            .await
+            .unwrap();
+        assert!(solo.verify_signature(&crypto).unwrap());
+        assert!(matches!(
+            solo.verify_trusted(&crypto, &mut trust, "app"),
+            Err(RoseliteError::Crypto(CryptoError::UntrustedKey(_)))
+        ));
+
+        // Policies no set of keys can meet are refused
+        let impossible = SignaturePolicy { threshold: 3, keys: vec![publisher, publisher] };
+        assert!(PackageBuilder::new("shared".to_string(), temp_dir.path())
+            .signature_policy(impossible)
+            .build()
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_builds_are_identical() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["b.html", "a.html", "index.html", "z/nested.css", "c/deep/file.js"] {
+            let path = temp_dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, name.as_bytes()).unwrap();
+        }
+        let (public_key, secret_key) = CryptoManager::new().unwrap().generate_keypair().unwrap();
+        let build = || PackageBuilder::new("reproducible".to_string(), temp_dir.path())
+            .keypair(public_key, secret_key.clone())
+            .deterministic(true)
+            .build();
+
+        let first = build().await.unwrap();
+        let second = build().await.unwrap();
+        assert_eq!(first.content, second.content);
+        assert_eq!(first.manifest.created_at, DateTime::UNIX_EPOCH);
+
+        // Entries are written in path order
+        let (index, _) = PackageIndex::read(&first.content).unwrap().unwrap();
+        let paths: Vec<String> = index.files.into_iter().map(|entry| entry.path).collect();
+        let files = &paths[..paths.len() - 2];
+        assert!(files.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // Without a fixed key there is nothing reproducible to sign with
+        assert!(PackageBuilder::new("reproducible".to_string(), temp_dir.path()).deterministic(true).build().await.is_err());
+
+        assert_eq!(source_date_epoch(Some("1700000000")).unwrap().timestamp(), 1_700_000_000);
+        assert!(source_date_epoch(Some("yesterday")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ignore_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let site = temp_dir.path();
+        for name in [
+            "index.html", ".env", ".index.html.swp", ".git/config", ".well-known/security.txt",
+            "node_modules/lib/index.js", "drafts/post.md", "notes.txt", "assets/app.js",
+        ] {
+            let path = site.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, name.as_bytes()).unwrap();
+        }
+        fs::write(site.join(".gitignore"), "node_modules/\n").unwrap();
+        fs::write(site.join(IGNORE_FILENAME), "drafts/\n*.txt\n!.well-known/\n!security.txt\n").unwrap();
+
+        let builder = PackageBuilder::new("ignored".to_string(), site);
+        let paths: Vec<String> = builder.source_files().unwrap().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, vec![".well-known/security.txt", "assets/app.js", "index.html"]);
+
+        // .gitignore can be switched off; .roseliteignore always applies
+        let builder = builder.respect_gitignore(false);
+        let paths: Vec<String> = builder.source_files().unwrap().into_iter().map(|(path, _)| path).collect();
+        assert!(paths.contains(&"node_modules/lib/index.js".to_string()));
+        assert!(!paths.iter().any(|path| path.starts_with("drafts/")));
+
+        // The package holds exactly the listed files
+        let package = builder.build().await.unwrap();
+        let files = package.extract_files().await.unwrap();
+        assert_eq!(files.len(), paths.len());
+        assert!(!files.contains_key(".env"));
+
+        // Excluded files stay out however their path is written; missing ones are no error
+        fs::write(site.join("roselite.toml"), b"[site]").unwrap();
+        fs::write(site.join("ignored.veilidpkg"), b"previous build").unwrap();
+        let builder = PackageBuilder::new("ignored".to_string(), site)
+            .exclude(site.join("roselite.toml"))
+            .exclude(site.join("assets").join("..").join("ignored.veilidpkg"))
+            .exclude(site.join("missing.veilidpkg"));
+        let paths: Vec<String> = builder.source_files().unwrap().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, vec![".well-known/security.txt", "assets/app.js", "index.html"]);
     }
 
     #[test]
@@ -537,11 +1318,18 @@ mod tests {
             permissions: vec![],
             created_at: Utc::now(),
             updated_at: Utc::now(),
-            public_key: "test-key".to_string(),
+            public_key: None,
+            signature_policy: None,
             dht_key: "VLD0:test-key".to_string(),
         };
         
         assert!(Package::validate_manifest(&valid_manifest).is_ok());
+
+        // Unsigned manifests keep writing the key as an empty string
+        let json = serde_json::to_value(&valid_manifest).unwrap();
+        assert_eq!(json["public_key"], "");
+        let parsed: PackageManifest = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.public_key, None);
         
         // Test invalid manifest (empty name)
         let mut invalid_manifest = valid_manifest.clone();
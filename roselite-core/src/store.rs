@@ -1,6 +1,19 @@
 use crate::{Result, RoseliteError};
-use crate::{veilid::VeilidConnection, types::{AppId, VeilUri, AppInfo}, package::Package};
+use crate::error::{CryptoError, VeilidError};
+use crate::crypto::{ChunkSealer, CryptoManager, KeyFormat};
+use crate::identity::{IdentityRecord, KeyStatement};
+use crate::keys::{HashDigest, PublicKey, SecretKey, Signature};
+use crate::trust::TrustPolicy;
+use crate::{dht::DhtBackend, journal::PublishJournal, keystore::KeyStore, veilid::{VeilidConfig, VeilidConnection}, types::{AppId, VeilUri, AppInfo}, package::Package};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
 use serde_json;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
@@ -9,8 +22,18 @@ use serde::{Deserialize, Serialize};
 pub trait AppStore {
     /// Publish a package and return both the URI and the updated package with DHT key set
     async fn publish(&mut self, package: Package) -> Result<(VeilUri, Package)>;
+    /// Replace the content of an already published app, keeping its DHT key
+    async fn update(&mut self, app_id: &AppId, package: Package) -> Result<(VeilUri, Package)>;
     async fn get_app(&self, app_id: &AppId) -> Result<Option<AppInfo>>;
+    /// List every published version of an app, oldest first
+    async fn list_versions(&self, app_id: &AppId) -> Result<Vec<PublishedVersion>>;
+    /// Download the version named in the URI, or the latest version when none is given
     async fn download(&self, uri: &VeilUri) -> Result<Package>;
+    /// Start downloading a version without buffering it, yielding verified chunks as they arrive
+    async fn download_stream(&self, uri: &VeilUri) -> Result<PackageDownload<'_>>;
+    /// Delete every package record of every version and then the lookup record,
+    /// returning how many records were removed
+    async fn unpublish(&mut self, app_id: &AppId) -> Result<usize>;
     async fn shutdown(&mut self) -> Result<()>;
 }
 
@@ -23,113 +46,663 @@ pub struct PackageRecord {
     pub chunk_count: usize,
     /// Size in bytes of this record's content
     pub size_bytes: usize,
+    /// BLAKE3 hash of each chunk, indexed by subkey. Stored in the record's own last subkey
+    /// rather than the lookup record, and filled in when a download is resolved.
+    #[serde(skip)]
+    pub chunk_hashes: Vec<HashDigest>,
 }
 
+/// A single published version of an app and the records holding its package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishedVersion {
+    /// Version string from the package manifest
+    pub version: String,
+    /// Package records containing this version's package data
+    pub package_records: Vec<PackageRecord>,
+    /// Total size across all package records
+    pub total_size_bytes: usize,
+    /// When this version was published
+    pub published_at: DateTime<Utc>,
+    /// Merkle root over every chunk hash of this version, in download order
+    #[serde(default)]
+    pub merkle_root: Option<HashDigest>,
+    /// Key this version's chunks are sealed under, once the site has been re-keyed since
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_key: Option<String>,
+    /// Developer key declared by this version's package manifest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<PublicKey>,
+}
+
+/// Current lookup record schema version
+pub const LOOKUP_SCHEMA_VERSION: &str = "2.0";
+
+/// Most versions a lookup record lists. Older versions are dropped on update, along with
+/// their package records, and fewer are kept when the record would outgrow its subkey.
+pub const MAX_VERSIONS: usize = 16;
+
 /// Lookup record that contains metadata and package record references
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LookupRecord {
-    /// App metadata
+    /// App metadata for the latest version
     pub app_info: AppInfo,
-    /// List of package records containing the actual package data
+    /// Package records of the only version of a schema 1.0 record; later schemas keep
+    /// them in `versions`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub package_records: Vec<PackageRecord>,
-    /// Total size across all package records
+    /// Total size of the only version of a schema 1.0 record
+    #[serde(default, skip_serializing_if = "is_zero")]
     pub total_size_bytes: usize,
     /// Schema version for future compatibility
     pub schema_version: String,
+    /// Every published version, oldest first (schema 2.0 and later)
+    #[serde(default)]
+    pub versions: Vec<PublishedVersion>,
+    /// X25519 public keys the site key is wrapped for, when access is scoped to recipients
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<String>,
+    /// Developer Ed25519 public key the record is signed with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<PublicKey>,
+    /// Ed25519 signature over the record serialized without this field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<Signature>,
+}
+
+impl PublishedVersion {
+    /// Check that a downloaded package declares the key this version was signed with and that
+    /// its own signature verifies. Whoever consumes a `download_stream` must run the same checks.
+    pub fn check_package(&self, crypto: &CryptoManager, package: &Package) -> Result<()> {
+        if package.manifest.public_key.is_none() || package.manifest.public_key != self.public_key {
+            let text = |key: Option<PublicKey>| key.map_or_else(|| "no key".to_string(), |key| key.to_string());
+            return Err(CryptoError::PublisherKeyMismatch {
+                signer: text(self.public_key),
+                manifest: text(package.manifest.public_key),
+            }.into());
+        }
+        if !package.verify_signature(crypto)? {
+            return Err(CryptoError::SignatureVerificationFailed.into());
+        }
+        Ok(())
+    }
+}
+
+impl LookupRecord {
+    /// Published versions, oldest first. Schema 1.0 records only know their single version.
+    pub fn history(&self) -> Vec<PublishedVersion> {
+        if !self.versions.is_empty() {
+            return self.versions.clone();
+        }
+        vec![PublishedVersion {
+            version: self.app_info.version.clone(),
+            package_records: self.package_records.clone(),
+            total_size_bytes: self.total_size_bytes,
+            published_at: self.app_info.updated_at,
+            merkle_root: None,
+            content_key: None,
+            public_key: None,
+        }]
+    }
+
+    /// Sign the record with a developer key, replacing any previous signature
+    pub fn sign(&mut self, crypto: &CryptoManager, public_key: &PublicKey, secret_key: &SecretKey) -> Result<()> {
+        self.public_key = Some(*public_key);
+        self.signature = None;
+        self.signature = Some(crypto.sign(&serde_json::to_vec(self)?, secret_key)?);
+        Ok(())
+    }
+
+    /// Check the signature against the developer key the record names
+    pub fn verify(&self, crypto: &CryptoManager) -> Result<()> {
+        let (Some(public_key), Some(signature)) = (&self.public_key, &self.signature) else {
+            return Err(CryptoError::MissingSignature.into());
+        };
+        let unsigned = LookupRecord { signature: None, ..self.clone() };
+        if !crypto.verify(&serde_json::to_vec(&unsigned)?, signature, public_key)? {
+            return Err(CryptoError::SignatureVerificationFailed.into());
+        }
+        Ok(())
+    }
+
+    /// Find the requested version, or the latest one when `version` is `None`
+    pub fn resolve(&self, version: Option<&str>) -> Option<PublishedVersion> {
+        let history = self.history();
+        match version {
+            Some(version) => history.into_iter().find(|v| v.version == version),
+            None => history.into_iter().last(),
+        }
+    }
 }
 
-/// Concrete implementation that talks directly to a local Veilid node.
-pub struct VeilidStore {
-    conn: VeilidConnection,
+fn is_zero(value: &usize) -> bool {
+    *value == 0
 }
 
-impl VeilidStore {
-    /// Create a new store and connect to the Veilid network.
+/// Cipher used to seal private sites
+pub const SITE_CIPHER: &str = "xchacha20poly1305";
+
+/// Lookup record of a private site. The `LookupRecord` inside and every package chunk
+/// can only be read with the site's content key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedLookupRecord {
+    /// Schema version for future compatibility
+    pub schema_version: String,
+    /// Cipher used for the lookup record and the package chunks
+    pub cipher: String,
+    /// Base64 of the sealed `LookupRecord` JSON
+    pub sealed: String,
+    /// The site key wrapped for each recipient of a recipient-scoped site
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<WrappedSiteKey>,
+}
+
+/// The site key of a recipient-scoped site, wrapped for a single recipient
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrappedSiteKey {
+    /// Recipient X25519 public key (hex)
+    pub recipient: String,
+    /// Ephemeral X25519 public key the key was wrapped with (hex)
+    pub ephemeral_public: String,
+    /// Site key sealed under the shared secret (hex)
+    pub wrapped_key: String,
+}
+
+/// Tuning knobs for moving chunks to and from the DHT
+#[derive(Debug, Clone)]
+pub struct TransferConfig {
+    /// Maximum number of chunks fetched concurrently during a download
+    pub download_concurrency: usize,
+    /// Maximum number of chunks written concurrently during a publish
+    pub upload_concurrency: usize,
+    /// How many times a failed transfer or corrupt chunk is attempted again
+    pub chunk_retries: u32,
+    /// Delay before the first retry; doubled on every further attempt
+    pub retry_backoff: Duration,
+}
+
+impl Default for TransferConfig {
+    fn default() -> Self {
+        Self {
+            download_concurrency: 8,
+            upload_concurrency: 8,
+            chunk_retries: 3,
+            retry_backoff: Duration::from_millis(250),
+        }
+    }
+}
+
+/// Verified package chunks in content order
+pub type ChunkStream<'a> = Pin<Box<dyn Stream<Item = Result<Vec<u8>>> + Send + 'a>>;
+
+/// A package download in progress, yielding the package content chunk by chunk
+pub struct PackageDownload<'a> {
+    /// The version being downloaded
+    pub version: PublishedVersion,
+    /// Publisher identity from the lookup record: an identity record key or a bare public key
+    pub identity: Option<String>,
+    /// Verified chunks in content order; ends with an error if the total size does not match.
+    /// The assembled package is not checked against the publisher key; see `PublishedVersion::check_package`.
+    pub chunks: ChunkStream<'a>,
+}
+
+/// A chunk fetched from the DHT along with how long it took
+struct FetchedChunk {
+    data: Vec<u8>,
+    elapsed: Duration,
+    attempts: u32,
+}
+
+/// Concrete implementation that stores apps in a DHT backend, a local Veilid node by default.
+pub struct VeilidStore<D: DhtBackend = VeilidConnection> {
+    conn: D,
+    keystore: KeyStore,
+    journal: PublishJournal,
+    transfer: TransferConfig,
+    /// Content keys of private sites supplied for this session only
+    site_keys: HashMap<String, String>,
+    /// Developer signing keys supplied for this session only, keyed by public key
+    signing_keys: HashMap<String, SecretKey>,
+}
+
+impl VeilidStore<VeilidConnection> {
+    /// Create a new store and connect to the Veilid network, unlocking the default
+    /// keystore with the password from the environment when one is given.
     pub async fn new() -> Result<Self> {
-        let mut conn = VeilidConnection::new().await?;
+        Self::connect(KeyStore::open_default_unlocked()?, VeilidConfig::default()).await
+    }
+
+    /// Connect to the Veilid network with `config`, keeping keys in `keystore`.
+    /// The protected store is encrypted under the device password of a sealed keystore.
+    /// A keystore without a password has no device password to give, so the protected
+    /// store is left unencrypted like the keystore itself, with a warning.
+    pub async fn connect(mut keystore: KeyStore, mut config: VeilidConfig) -> Result<Self> {
+        let journal = PublishJournal::open_default()?;
+        let device_password = if keystore.is_sealed() {
+            Some(keystore.ensure_device_password()?)
+        } else {
+            if config.storage.enable_encryption {
+                tracing::warn!(
+                    "The keystore is not password protected, so the Veilid protected store is not encrypted either; set {} or {} to encrypt both",
+                    crate::keystore::PASSWORD_ENV, crate::keystore::PASSWORD_FD_ENV
+                );
+                config.storage.enable_encryption = false;
+            }
+            None
+        };
+        let mut conn = VeilidConnection::new_with_config(config).await?;
+        if let Some(password) = device_password {
+            conn.set_device_password(password);
+        }
         conn.connect().await?;
-        Ok(Self { conn })
+        Ok(Self::with_backend(conn).with_keystore(keystore).with_journal(journal))
+    }
+}
+
+impl<D: DhtBackend> VeilidStore<D> {
+    /// Create a store on top of an already connected DHT backend.
+    /// Record owner keys and publish progress are only kept in memory until a
+    /// keystore and journal are attached.
+    pub fn with_backend(backend: D) -> Self {
+        Self {
+            conn: backend,
+            keystore: KeyStore::in_memory(),
+            journal: PublishJournal::in_memory(),
+            transfer: TransferConfig::default(),
+            site_keys: HashMap::new(),
+            signing_keys: HashMap::new(),
+        }
+    }
+
+    /// Use the given concurrency and retry settings for chunk transfers.
+    pub fn with_transfer_config(mut self, transfer: TransferConfig) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
+    /// Use the given keystore for record owner keys.
+    pub fn with_keystore(mut self, keystore: KeyStore) -> Self {
+        self.keystore = keystore;
+        self
+    }
+
+    /// Use the given journal to track publish progress.
+    pub fn with_journal(mut self, journal: PublishJournal) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Supply the content key of a private site for this session, without persisting it.
+    pub fn add_site_key(&mut self, app_id: &AppId, key: String) {
+        self.site_keys.insert(app_id.0.clone(), key);
+    }
+
+    /// Supply a developer signing key for this session, without persisting it, so lookup
+    /// records of packages it signed are signed by the developer rather than the record owner.
+    pub fn add_signing_key(&mut self, secret_key: SecretKey) {
+        self.signing_keys.insert(secret_key.public_key().to_string(), secret_key);
+    }
+
+    /// Access the underlying DHT backend.
+    pub fn backend(&self) -> &D {
+        &self.conn
+    }
+
+    /// Access the keystore holding record owner keys.
+    pub fn keystore(&self) -> &KeyStore {
+        &self.keystore
+    }
+
+    /// Access the journal of the publish in progress.
+    pub fn journal(&self) -> &PublishJournal {
+        &self.journal
     }
 
     /// Maximum size for a single DHT record (leaving room for metadata)
     const MAX_RECORD_SIZE: usize = 950_000; // ~950KB to stay well under 1MiB
     /// Size per chunk within a record
     const CHUNK_SIZE: usize = 8000; // 8KB chunks for good distribution
-}
 
-#[async_trait]
-impl AppStore for VeilidStore {
-    /// Publish a package into the Veilid DHT using multi-record approach.
-    async fn publish(&mut self, package: Package) -> Result<(VeilUri, Package)> {
-        let content = &package.content;
+    /// Split content into package records of at most `MAX_RECORD_SIZE` bytes, each cut into chunks.
+    fn chunk_layout(content: &[u8]) -> Vec<Vec<&[u8]>> {
+        content.chunks(Self::MAX_RECORD_SIZE)
+            .map(|record_content| record_content.chunks(Self::CHUNK_SIZE).collect())
+            .collect()
+    }
+
+    /// Split content across as many package records as needed and upload every chunk.
+    /// Records and subkeys already recorded in the journal are reused rather than written again.
+    async fn write_package_records(&mut self, content: &[u8]) -> Result<Vec<PackageRecord>> {
+        let crypto = CryptoManager::new()?;
+        let concurrency = self.transfer.upload_concurrency.max(1);
+        let sealer = self.journaled_sealer();
         let mut package_records = Vec::new();
-        let mut content_offset = 0;
+        let mut chunk_index = 0u64;
 
-        // Split content across multiple package records if needed
-        while content_offset < content.len() {
-            // Calculate how much content to put in this record
-            let remaining_content = content.len() - content_offset;
-            let record_content_size = std::cmp::min(remaining_content, Self::MAX_RECORD_SIZE);
-            let record_end = content_offset + record_content_size;
-            let record_content = &content[content_offset..record_end];
+        for (index, plain_chunks) in Self::chunk_layout(content).into_iter().enumerate() {
+            // Private sites store every chunk sealed under the site key
+            let chunks: Vec<Cow<[u8]>> = match &sealer {
+                Some(sealer) => plain_chunks.iter().enumerate()
+                    .map(|(i, chunk)| sealer.seal(&crypto, chunk_index + i as u64, chunk).map(Cow::Owned))
+                    .collect::<Result<_>>()?,
+                None => plain_chunks.into_iter().map(Cow::Borrowed).collect(),
+            };
+            chunk_index += chunks.len() as u64;
 
-            // Split this record's content into chunks
-            let mut chunks = Vec::new();
-            let mut chunk_offset = 0;
-            while chunk_offset < record_content.len() {
-                let chunk_end = std::cmp::min(chunk_offset + Self::CHUNK_SIZE, record_content.len());
-                chunks.push(&record_content[chunk_offset..chunk_end]);
-                chunk_offset = chunk_end;
-            }
+            // Reuse the record created by an interrupted attempt, or create a new one
+            let (record_key, owner) = match self.journal.record(index) {
+                Some(record) if record.chunk_count != chunks.len() => {
+                    return Err(RoseliteError::ValidationError(format!(
+                        "Publish journal expects {} chunks in record {}, package has {}",
+                        record.chunk_count, record.record_key, chunks.len()
+                    )));
+                }
+                Some(record) => {
+                    let owner = self.keystore.record_owner(&record.record_key)
+                        .ok_or_else(|| RoseliteError::PermissionDenied(format!(
+                            "No owner key for {} in the local keystore", record.record_key
+                        )))?;
+                    (record.record_key.clone(), owner.to_string())
+                }
+                None => {
+                    // One subkey more than there are chunks, for the chunk hash list
+                    let (record_key, owner) = self.conn.create_owned_dht_record(chunks.len() + 1).await?;
+                    self.keystore.set_record_owner(&record_key, owner.clone())?;
+                    self.journal.add_record(record_key.clone(), chunks.len())?;
+                    (record_key, owner)
+                }
+            };
 
-            // Create DHT record for this chunk group
-            let record_key = self.conn.create_dht_record_with_cols(chunks.len()).await?;
+            let pending: Vec<u32> = (0..chunks.len() as u32)
+                .filter(|subkey| !self.journal.record(index).is_some_and(|r| r.written.contains(subkey)))
+                .collect();
+            let resumed = chunks.len() - pending.len();
 
-            // Store chunks in this record
-            for (idx, chunk) in chunks.iter().enumerate() {
-                self.conn.dht_set_subkey(&record_key, idx as u32, chunk).await?;
+            // Write the remaining chunks concurrently, journaling each one as it lands
+            let (conn, transfer) = (&self.conn, &self.transfer);
+            let uploads: Vec<_> = pending.iter()
+                .map(|&subkey| Self::upload_chunk(conn, transfer, &record_key, &owner, subkey, &chunks[subkey as usize]))
+                .collect();
+            let mut uploads = stream::iter(uploads).buffer_unordered(concurrency);
+            while let Some(subkey) = uploads.next().await.transpose()? {
+                self.journal.mark_written(index, subkey)?;
             }
 
+            let chunk_hashes = chunks.iter()
+                .map(|chunk| crypto.hash(chunk))
+                .collect::<Result<Vec<_>>>()?;
+            let hash_list = serde_json::to_vec(&chunk_hashes)?;
+            Self::upload_chunk(&self.conn, &self.transfer, &record_key, &owner, chunks.len() as u32, &hash_list).await?;
+            let size_bytes = chunks.iter().map(|chunk| chunk.len()).sum();
+
             // Track this package record
             package_records.push(PackageRecord {
                 record_key: record_key.clone(),
                 chunk_count: chunks.len(),
-                size_bytes: record_content.len(),
+                size_bytes,
+                chunk_hashes,
             });
 
-            tracing::info!("Created package record {} with {} chunks ({} bytes)", 
-                record_key, chunks.len(), record_content.len());
+            if resumed > 0 {
+                tracing::info!("Resumed package record {} with {} chunks ({} already written, {} bytes)",
+                    record_key, chunks.len(), resumed, size_bytes);
+            } else {
+                tracing::info!("Created package record {} with {} chunks ({} bytes)",
+                    record_key, chunks.len(), size_bytes);
+            }
+        }
+
+        Ok(package_records)
+    }
+
+    /// Write one chunk as the record owner, retrying failed writes with exponential backoff.
+    async fn upload_chunk(conn: &D, transfer: &TransferConfig, record_key: &str, owner: &str, subkey: u32, chunk: &[u8]) -> Result<u32> {
+        let mut attempt = 0;
+
+        loop {
+            let failure = match conn.dht_set_subkey_as(record_key, subkey, chunk, owner).await {
+                Ok(()) => return Ok(subkey),
+                Err(e) => e,
+            };
+
+            if attempt == transfer.chunk_retries {
+                return Err(failure);
+            }
+
+            let backoff = transfer.retry_backoff * 2u32.saturating_pow(attempt);
+            attempt += 1;
+            tracing::warn!("Writing chunk {} of record {} failed ({}), retrying in {:?} (attempt {}/{})",
+                subkey, record_key, failure, backoff, attempt, transfer.chunk_retries);
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    /// Continue the publish or update recorded in the journal, writing into the same
+    /// records. `package` must be the package the interrupted publish was started with.
+    pub async fn resume(&mut self, package: Package) -> Result<(VeilUri, Package)> {
+        let progress = self.journal.pending().cloned()
+            .ok_or_else(|| RoseliteError::ValidationError("No interrupted publish to resume".to_string()))?;
+
+        if CryptoManager::new()?.hash(&package.content)?.to_string() != progress.package_hash {
+            return Err(RoseliteError::ValidationError(format!(
+                "The interrupted publish was started for {} {}, not this package",
+                progress.app_name, progress.version
+            )));
+        }
+
+        tracing::info!("Resuming publish of {} {} ({} chunks in {} records already written)",
+            progress.app_name, progress.version, progress.written_chunks(), progress.records.len());
+
+        match progress.lookup_key {
+            Some(lookup_key) if progress.is_update => {
+                let app_id = AppId(lookup_key);
+                let (lookup_owner, previous, _) = self.prepare_update(&app_id, &package).await?;
+                self.complete_update(&app_id, package, lookup_owner, previous).await
+            }
+            // A private publish interrupted before its lookup record existed wrote nothing under its site key
+            _ => {
+                let site_key = progress.nonce_prefix
+                    .map(|_| CryptoManager::new()?.generate_content_key())
+                    .transpose()?;
+                self.complete_publish(package, site_key).await
+            }
+        }
+    }
+
+    /// Publish a private site: the package and its metadata are sealed under a random
+    /// content key, returned in the fragment of the URI. Anyone with that URI can read the site.
+    pub async fn publish_private(&mut self, package: Package) -> Result<(VeilUri, Package)> {
+        let crypto = CryptoManager::new()?;
+        let sealer = ChunkSealer::new(crypto.generate_content_key()?);
+        self.check_lookup_fits(&package, &[], Some(&sealer.key))?;
+        let package_hash = crypto.hash(&package.content)?.to_string();
+        self.journal.begin(package_hash, &package.manifest.name, &package.manifest.version, None, Some(sealer.nonce_prefix))?;
+        self.complete_publish(package, Some(sealer.key)).await
+    }
+
+    /// Publish a private site readable only by the given X25519 recipients. The site key is
+    /// wrapped for each recipient in the lookup record and left out of the returned URI.
+    pub async fn publish_to_recipients(&mut self, package: Package, recipients: Vec<String>) -> Result<(VeilUri, Package)> {
+        let crypto = CryptoManager::new()?;
+        let sealer = ChunkSealer::new(crypto.generate_content_key()?);
+        Self::check_recipients(&crypto, &sealer.key, &recipients)?;
+        self.check_lookup_fits(&package, &recipients, Some(&sealer.key))?;
+
+        let package_hash = crypto.hash(&package.content)?.to_string();
+        self.journal.begin(package_hash, &package.manifest.name, &package.manifest.version, None, Some(sealer.nonce_prefix))?;
+        self.journal.set_recipients(recipients)?;
+        self.complete_publish(package, Some(sealer.key)).await
+    }
+
+    /// Replace the site key of a private site we own and grant access to exactly `recipients`,
+    /// or to whoever holds the returned share link when the list is empty. Only the lookup
+    /// record is rewritten: existing versions keep the key their chunks were sealed under,
+    /// inside the newly sealed record, so anyone who loses access cannot follow later updates.
+    pub async fn rekey(&mut self, app_id: &AppId, recipients: Vec<String>) -> Result<VeilUri> {
+        let lookup_owner = self.keystore.record_owner(&app_id.0)
+            .ok_or_else(|| RoseliteError::PermissionDenied(format!(
+                "No owner key for {} in the local keystore", app_id
+            )))?
+            .to_string();
+
+        let (mut lookup_record, old_key) = self.open_lookup_record(app_id, None).await?;
+        let old_key = old_key.ok_or_else(|| RoseliteError::ValidationError(format!(
+            "{} is a public site; only private sites can be re-keyed", app_id
+        )))?;
+
+        let crypto = self.conn.crypto()?;
+        let new_key = crypto.generate_content_key()?;
+        Self::check_recipients(&crypto, &new_key, &recipients)?;
+
+        lookup_record.versions = lookup_record.history();
+        for version in &mut lookup_record.versions {
+            version.content_key.get_or_insert_with(|| old_key.clone());
+        }
+        lookup_record.recipients = recipients;
+        let public_key = lookup_record.public_key
+            .ok_or(CryptoError::MissingSignature)?;
+        let secret_key = self.signing_keys.get(&public_key.to_string())
+            .ok_or_else(|| CryptoError::SigningKeyUnavailable(public_key.to_string()))?;
+        lookup_record.sign(&crypto, &public_key, secret_key)?;
+
+        let lookup_json = Self::encode_lookup_record(&lookup_record, Some(&new_key))?;
+        self.conn.dht_set_subkey_as(&app_id.0, 0, &lookup_json, &lookup_owner).await?;
+        self.keystore.set_site_key(&app_id.0, new_key.clone())?;
+        self.site_keys.remove(&app_id.0);
+
+        tracing::info!("Re-keyed {} for {} recipients", app_id, lookup_record.recipients.len());
+
+        Ok(lookup_record.app_info.uri().with_key(Some(new_key)))
+    }
+
+    /// Append a statement about a publisher's keys to its identity record, creating the record
+    /// on first use, and return the record key.
+    pub async fn publish_key_statement(&mut self, record_key: Option<&str>, statement: KeyStatement) -> Result<String> {
+        let (record_key, mut identity_record) = match record_key {
+            Some(record_key) => (record_key.to_string(), self.fetch_identity_record(record_key).await?),
+            None => {
+                let (record_key, owner) = self.conn.create_owned_dht_record(1).await?;
+                self.keystore.set_record_owner(&record_key, owner)?;
+                (record_key, IdentityRecord::default())
+            }
+        };
+        let owner = self.keystore.record_owner(&record_key)
+            .ok_or_else(|| RoseliteError::PermissionDenied(format!(
+                "No owner key for {} in the local keystore", record_key
+            )))?
+            .to_string();
+
+        identity_record.statements.push(statement);
+        let json = serde_json::to_vec(&identity_record)?;
+        if json.len() > crate::dht::MAX_SUBKEY_SIZE {
+            return Err(RoseliteError::ValidationError(format!(
+                "Identity record exceeds {} byte subkey limit",
+                crate::dht::MAX_SUBKEY_SIZE
+            )));
+        }
+        self.conn.dht_set_subkey_as(&record_key, 0, &json, &owner).await?;
+
+        tracing::info!("Published key statement {} to identity record {}",
+            identity_record.statements.len(), record_key);
+        Ok(record_key)
+    }
+
+    /// Fetch the statements a publisher has made about its keys.
+    pub async fn fetch_identity_record(&self, record_key: &str) -> Result<IdentityRecord> {
+        let bytes = self.conn.dht_get_subkey(record_key, 0).await?
+            .ok_or_else(|| RoseliteError::Veilid(VeilidError::AppNotFound {
+                app_id: record_key.to_string()
+            }))?;
+        serde_json::from_slice(&bytes)
+            .map_err(|_| RoseliteError::ValidationError("Invalid identity record format".to_string()))
+    }
+
+    /// Feed the rotations and revocations a publisher has published into a trust policy.
+    /// Identities given as a hex public key rather than a DHT record have nothing to load.
+    pub async fn load_key_statements(&self, identity: &str, policy: &mut TrustPolicy) -> Result<()> {
+        if !identity.contains(':') {
+            return Ok(());
+        }
+        let record = self.fetch_identity_record(identity).await?;
+        policy.add_identity_record(&record, &self.conn.crypto()?);
+        Ok(())
+    }
 
-            content_offset = record_end;
+    /// Make sure every recipient key is usable before anything is written.
+    fn check_recipients(crypto: &CryptoManager, site_key: &str, recipients: &[String]) -> Result<()> {
+        for recipient in recipients {
+            crypto.wrap_content_key(site_key, recipient)?;
         }
+        Ok(())
+    }
+
+    /// Write the package records and a new lookup record for a journaled publish. A private
+    /// site is sealed under `site_key` unless the journal already holds its lookup record.
+    async fn complete_publish(&mut self, package: Package, site_key: Option<String>) -> Result<(VeilUri, Package)> {
+        let (public_key, signing_key) = self.signing_key(&package)?;
+        let content = &package.content;
+        let recipients = self.journal.pending().map(|p| p.recipients.clone()).unwrap_or_default();
 
-        // Create the lookup record
-        let lookup_key = self.conn.create_dht_record_with_cols(1).await?;
+        // Create the lookup record first, keeping its owner keypair so the site can be updated
+        // later and the site key under it so chunks are sealed with a key the journal never holds
+        let lookup_key = match self.journal.pending().and_then(|p| p.lookup_key.clone()) {
+            Some(lookup_key) => lookup_key,
+            None => {
+                // A Veilid signing keypair owns the lookup record itself, so one key stands for the publisher
+                let (lookup_key, lookup_owner) = if signing_key.format() == KeyFormat::Veilid {
+                    let owner = signing_key.expose();
+                    (self.conn.create_dht_record_owned_by(1, &owner).await?, owner)
+                } else {
+                    self.conn.create_owned_dht_record(1).await?
+                };
+                self.keystore.set_record_owner(&lookup_key, lookup_owner)?;
+                if let Some(key) = site_key {
+                    self.keystore.set_site_key(&lookup_key, key)?;
+                }
+                self.journal.set_lookup_key(&lookup_key)?;
+                lookup_key
+            }
+        };
+        let lookup_owner = self.keystore.record_owner(&lookup_key)
+            .ok_or_else(|| RoseliteError::PermissionDenied(format!(
+                "No owner key for {} in the local keystore", lookup_key
+            )))?
+            .to_string();
+        let package_records = self.write_package_records(content).await?;
+        let site_key = self.journaled_sealer().map(|sealer| sealer.key);
         
         // Build app info with the lookup key as the ID
         let mut app_info = package.to_app_info();
         app_info.id = AppId(lookup_key.clone());
 
         // Create lookup record
-        let lookup_record = LookupRecord {
+        let merkle_root = Self::merkle_root(&package_records)?;
+        let mut lookup_record = LookupRecord {
             app_info: app_info.clone(),
-            package_records,
-            total_size_bytes: content.len(),
-            schema_version: "1.0".to_string(),
+            package_records: Vec::new(),
+            total_size_bytes: 0,
+            schema_version: LOOKUP_SCHEMA_VERSION.to_string(),
+            versions: vec![PublishedVersion {
+                version: app_info.version.clone(),
+                package_records,
+                total_size_bytes: content.len(),
+                published_at: app_info.updated_at,
+                merkle_root: Some(merkle_root),
+                content_key: None,
+                public_key: Some(public_key),
+            }],
+            recipients,
+            public_key: None,
+            signature: None,
         };
+        lookup_record.sign(&self.conn.crypto()?, &public_key, &signing_key)?;
 
         // Store lookup record metadata
-        let lookup_json = serde_json::to_vec(&lookup_record)?;
-        if lookup_json.len() > 1_000_000 { // ~1MB check
-            return Err(RoseliteError::ValidationError(
-                "Lookup record metadata exceeds 1MB limit".to_string()
-            ));
-        }
-
-        self.conn.dht_set_subkey(&lookup_key, 0, &lookup_json).await?;
+        let lookup_json = Self::encode_lookup_record(&lookup_record, site_key.as_deref())?;
+        self.conn.dht_set_subkey_as(&lookup_key, 0, &lookup_json, &lookup_owner).await?;
+        self.finish_journal()?;
 
         tracing::info!("Published package with {} package records, lookup key: {}", 
-            lookup_record.package_records.len(), lookup_key);
+            lookup_record.versions[0].package_records.len(), lookup_key);
 
         // Inspect the lookup record (best-effort)
         let _ = self.conn.inspect_record(&lookup_key).await;
@@ -138,16 +711,516 @@ impl AppStore for VeilidStore {
         let mut updated_package = package;
         updated_package.set_dht_key(lookup_key.clone());
 
-        Ok((app_info.uri(), updated_package))
+        Ok((Self::share_uri(&app_info, &lookup_record, site_key), updated_package))
+    }
+
+    /// The package's developer key, refusing packages whose detached signature does not verify.
+    /// Checked before a publish is journaled so a refused package leaves nothing to resume.
+    fn verified_key(&self, package: &Package) -> Result<PublicKey> {
+        let public_key = package.manifest.public_key.ok_or(CryptoError::MissingSignature)?;
+        if !package.verify_signature(&self.conn.crypto()?)? {
+            return Err(CryptoError::SignatureVerificationFailed.into());
+        }
+        Ok(public_key)
+    }
+
+    /// The package's verified developer key and its secret from the freshly built package or
+    /// the keys supplied for this session. The lookup record is always signed with the key the
+    /// manifest declares, so a package signed offline needs its secret added with `add_signing_key`.
+    fn signing_key(&self, package: &Package) -> Result<(PublicKey, SecretKey)> {
+        let public_key = self.verified_key(package)?;
+        let secret_key = package.signing_key.clone()
+            .filter(|secret_key| secret_key.public_key() == public_key)
+            .or_else(|| self.signing_keys.get(&public_key.to_string()).cloned())
+            .ok_or_else(|| CryptoError::SigningKeyUnavailable(public_key.to_string()))?;
+        Ok((public_key, secret_key))
+    }
+
+    /// Check that a lookup record listing only this package would fit in its subkey, so a
+    /// package too large to describe is refused before any of it is written
+    fn check_lookup_fits(&self, package: &Package, recipients: &[String], site_key: Option<&str>) -> Result<()> {
+        let (public_key, secret_key) = self.signing_key(package)?;
+        let crypto = self.conn.crypto()?;
+
+        // Stand-in keys as long as the real `VLD0:` ones
+        let placeholder = format!("VLD0:{}", "A".repeat(43));
+        let package_records: Vec<PackageRecord> = Self::chunk_layout(&package.content).into_iter()
+            .map(|chunks| PackageRecord {
+                record_key: placeholder.clone(),
+                chunk_count: chunks.len(),
+                size_bytes: chunks.iter().map(|chunk| chunk.len()).sum(),
+                chunk_hashes: Vec::new(),
+            })
+            .collect();
+        let record_count = package_records.len();
+        let mut app_info = package.to_app_info();
+        app_info.id = AppId(placeholder);
+
+        let mut draft = LookupRecord {
+            package_records: Vec::new(),
+            total_size_bytes: 0,
+            schema_version: LOOKUP_SCHEMA_VERSION.to_string(),
+            versions: vec![PublishedVersion {
+                version: app_info.version.clone(),
+                package_records,
+                total_size_bytes: package.content.len(),
+                published_at: app_info.updated_at,
+                merkle_root: Some(crypto.hash(&package.content)?),
+                content_key: None,
+                public_key: Some(public_key),
+            }],
+            app_info,
+            recipients: recipients.to_vec(),
+            public_key: None,
+            signature: None,
+        };
+        draft.sign(&crypto, &public_key, &secret_key)?;
+        if Self::seal_lookup_record(&draft, site_key)?.len() > crate::dht::MAX_SUBKEY_SIZE {
+            return Err(RoseliteError::ValidationError(format!(
+                "{} v{} needs {} package records, too many to list in a {} byte lookup record",
+                package.manifest.name, package.manifest.version, record_count, crate::dht::MAX_SUBKEY_SIZE
+            )));
+        }
+        Ok(())
+    }
+
+    /// Sign the lookup record, first dropping its oldest versions until at most `MAX_VERSIONS`
+    /// are left and it fits in its subkey. Returns the versions that were dropped.
+    fn fit_lookup_record(lookup_record: &mut LookupRecord, public_key: &PublicKey, secret_key: &SecretKey, site_key: Option<&str>) -> Result<Vec<PublishedVersion>> {
+        let crypto = CryptoManager::new()?;
+        let excess = lookup_record.versions.len().saturating_sub(MAX_VERSIONS);
+        let mut pruned: Vec<PublishedVersion> = lookup_record.versions.drain(..excess).collect();
+        loop {
+            lookup_record.sign(&crypto, public_key, secret_key)?;
+            let fits = Self::seal_lookup_record(lookup_record, site_key)?.len() <= crate::dht::MAX_SUBKEY_SIZE;
+            if fits || lookup_record.versions.len() == 1 {
+                return Ok(pruned);
+            }
+            pruned.push(lookup_record.versions.remove(0));
+        }
+    }
+
+    /// Delete the package records of versions dropped from the lookup record. Best effort: the
+    /// update has already landed, so a record that cannot be deleted is only logged.
+    async fn delete_pruned_versions(&self, app_id: &AppId, lookup_record: &LookupRecord, pruned: Vec<PublishedVersion>) {
+        if pruned.is_empty() {
+            return;
+        }
+        let kept: HashSet<&str> = lookup_record.versions.iter()
+            .flat_map(|version| &version.package_records)
+            .map(|record| record.record_key.as_str())
+            .collect();
+        for version in &pruned {
+            for record in version.package_records.iter().filter(|record| !kept.contains(record.record_key.as_str())) {
+                if let Err(e) = self.conn.dht_delete_record(&record.record_key).await {
+                    tracing::warn!("Failed to delete package record {} of dropped version {}: {}",
+                        record.record_key, version.version, e);
+                }
+            }
+        }
+        let versions: Vec<&str> = pruned.iter().map(|version| version.version.as_str()).collect();
+        tracing::warn!("Dropped {} old version(s) of {} to keep its lookup record within limits: {}",
+            pruned.len(), app_id, versions.join(", "));
+    }
+
+    /// URI to hand out for a site. The site key is only included for share-link sites,
+    /// never for sites scoped to recipients.
+    fn share_uri(app_info: &AppInfo, lookup_record: &LookupRecord, site_key: Option<String>) -> VeilUri {
+        let site_key = site_key.filter(|_| lookup_record.recipients.is_empty());
+        app_info.uri().with_key(site_key)
+    }
+
+    /// Check that we own `app_id` and have not published this version yet, returning
+    /// the lookup record owner keypair, the current lookup record and the site key of a private site.
+    async fn prepare_update(&self, app_id: &AppId, package: &Package) -> Result<(String, LookupRecord, Option<String>)> {
+        let lookup_owner = self.keystore.record_owner(&app_id.0)
+            .ok_or_else(|| RoseliteError::PermissionDenied(format!(
+                "No owner key for {} in the local keystore", app_id
+            )))?
+            .to_string();
+
+        let (previous, site_key) = self.open_lookup_record(app_id, None).await?;
+        if previous.history().iter().any(|v| v.version == package.manifest.version) {
+            return Err(RoseliteError::VersionMismatch(format!(
+                "Version {} of {} is already published", package.manifest.version, app_id
+            )));
+        }
+
+        Ok((lookup_owner, previous, site_key))
+    }
+
+    /// Write the package records for a journaled update and point the lookup record at them.
+    async fn complete_update(&mut self, app_id: &AppId, package: Package, lookup_owner: String, previous: LookupRecord) -> Result<(VeilUri, Package)> {
+        let (public_key, signing_key) = self.signing_key(&package)?;
+        let content = &package.content;
+        let package_records = self.write_package_records(content).await?;
+        let site_key = self.journaled_sealer().map(|sealer| sealer.key);
+
+        let mut app_info = package.to_app_info();
+        app_info.id = app_id.clone();
+        app_info.created_at = previous.app_info.created_at;
+
+        let merkle_root = Self::merkle_root(&package_records)?;
+        let mut versions = previous.history();
+        let record_count = package_records.len();
+        versions.push(PublishedVersion {
+            version: app_info.version.clone(),
+            package_records,
+            total_size_bytes: content.len(),
+            published_at: app_info.updated_at,
+            merkle_root: Some(merkle_root),
+            content_key: None,
+            public_key: Some(public_key),
+        });
+
+        let mut lookup_record = LookupRecord {
+            app_info: app_info.clone(),
+            package_records: Vec::new(),
+            total_size_bytes: 0,
+            schema_version: LOOKUP_SCHEMA_VERSION.to_string(),
+            versions,
+            recipients: previous.recipients.clone(),
+            public_key: None,
+            signature: None,
+        };
+        let pruned = Self::fit_lookup_record(&mut lookup_record, &public_key, &signing_key, site_key.as_deref())?;
+
+        let lookup_json = Self::encode_lookup_record(&lookup_record, site_key.as_deref())?;
+        self.conn.dht_set_subkey_as(&app_id.0, 0, &lookup_json, &lookup_owner).await?;
+        self.finish_journal()?;
+        self.delete_pruned_versions(app_id, &lookup_record, pruned).await;
+
+        tracing::info!("Updated {} to version {} with {} package records (replacing {})",
+            app_id, app_info.version, record_count, previous.app_info.version);
+
+        let _ = self.conn.inspect_record(&app_id.0).await;
+
+        let mut updated_package = package;
+        updated_package.set_dht_key(app_id.0.clone());
+
+        Ok((Self::share_uri(&app_info, &lookup_record, site_key), updated_package))
+    }
+
+    /// How chunks of the journaled publish are sealed: the nonce prefix from the journal and
+    /// the site key the keystore holds for its lookup record. `None` for a public site.
+    fn journaled_sealer(&self) -> Option<ChunkSealer> {
+        let progress = self.journal.pending()?;
+        let nonce_prefix = progress.nonce_prefix.clone()?;
+        let key = self.keystore.site_key(progress.lookup_key.as_deref()?)?;
+        Some(ChunkSealer {
+            key: key.to_string(),
+            nonce_prefix,
+        })
+    }
+
+    /// Forget the journaled publish once its lookup record is written, along with the owner
+    /// keypairs of its package records, which are never written again
+    fn finish_journal(&mut self) -> Result<()> {
+        let records: Vec<String> = self.journal.pending()
+            .map(|progress| progress.records.iter().map(|r| r.record_key.clone()).collect())
+            .unwrap_or_default();
+        self.journal.finish()?;
+        self.keystore.remove_record_owners(&records)
+    }
+
+    /// Content key for a private site: from the URI, the keys supplied to this session, the
+    /// keystore, or unwrapped with one of our recipient keys.
+    fn site_key(&self, app_id: &AppId, sealed: &SealedLookupRecord, uri_key: Option<&str>) -> Result<String> {
+        let known = uri_key
+            .or_else(|| self.site_keys.get(&app_id.0).map(|key| key.as_str()))
+            .or_else(|| self.keystore.site_key(&app_id.0));
+        if let Some(key) = known {
+            return Ok(key.to_string());
+        }
+
+        let crypto = CryptoManager::new()?;
+        for wrapped in &sealed.recipients {
+            if let Some(secret) = self.keystore.recipient_secret(&wrapped.recipient) {
+                return crypto.unwrap_content_key(&wrapped.ephemeral_public, &wrapped.wrapped_key, secret);
+            }
+        }
+
+        Err(RoseliteError::PermissionDenied(format!(
+            "{} is a private site; its key is required to read it", app_id
+        )))
+    }
+
+    /// Merkle root over the chunk hashes of all package records, in download order.
+    fn merkle_root(package_records: &[PackageRecord]) -> Result<HashDigest> {
+        let leaves: Vec<HashDigest> = package_records.iter()
+            .flat_map(|record| record.chunk_hashes.iter().copied())
+            .collect();
+        CryptoManager::new()?.merkle_root(&leaves)
+    }
+
+    /// Fetch one chunk and check it against its recorded hash. A chunk that is missing,
+    /// fails to load or fails its hash check is refetched from the network with exponential backoff.
+    async fn fetch_verified_chunk(&self, record: &PackageRecord, subkey: u32) -> Result<FetchedChunk> {
+        let crypto = CryptoManager::new()?;
+        let started = Instant::now();
+        let mut attempt = 0;
+
+        loop {
+            let fetched = if attempt == 0 {
+                self.conn.dht_get_subkey(&record.record_key, subkey).await
+            } else {
+                self.conn.dht_refresh_subkey(&record.record_key, subkey).await
+            };
+
+            let failure = match fetched {
+                Ok(Some(data)) => {
+                    // Records published before chunk hashes existed cannot be verified
+                    match record.chunk_hashes.get(subkey as usize) {
+                        Some(expected) if crypto.hash(&data)? != *expected => {
+                            RoseliteError::Veilid(VeilidError::ChunkHashMismatch {
+                                record_key: record.record_key.clone(),
+                                subkey,
+                            })
+                        }
+                        _ => {
+                            return Ok(FetchedChunk {
+                                data,
+                                elapsed: started.elapsed(),
+                                attempts: attempt + 1,
+                            });
+                        }
+                    }
+                }
+                Ok(None) => RoseliteError::Veilid(VeilidError::AppNotFound {
+                    app_id: record.record_key.clone()
+                }),
+                Err(e) => e,
+            };
+
+            if attempt == self.transfer.chunk_retries {
+                return Err(failure);
+            }
+
+            let backoff = self.transfer.retry_backoff * 2u32.saturating_pow(attempt);
+            attempt += 1;
+            tracing::warn!("Chunk {} of record {} failed ({}), retrying in {:?} (attempt {}/{})",
+                subkey, record.record_key, failure, backoff, attempt, self.transfer.chunk_retries);
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    /// Resolve the version named in the URI and check the record's signature and its chunk
+    /// hash list can be trusted. Returns the site key alongside a private site's version,
+    /// and the publisher identity.
+    async fn resolve_download(&self, uri: &VeilUri) -> Result<(PublishedVersion, Option<String>, Option<String>)> {
+        let (lookup_record, site_key) = self.open_lookup_record(&uri.app_id, uri.key.as_deref()).await?;
+        lookup_record.verify(&self.conn.crypto()?)?;
+        let mut published = lookup_record.resolve(uri.version.as_deref())
+            .ok_or_else(|| RoseliteError::VersionMismatch(format!(
+                "Version {} of {} was never published",
+                uri.version.as_deref().unwrap_or("latest"), uri.app_id
+            )))?;
+        // Versions published before a re-key stay sealed under their original key
+        let site_key = published.content_key.clone().or(site_key);
+        // Versions that predate per-version keys were published by the record's signer
+        if published.public_key.is_none() {
+            published.public_key = lookup_record.public_key;
+        }
+        // The newest version is always published under the key that signs the lookup record
+        let latest = lookup_record.resolve(None).map(|latest| latest.version);
+        if latest.as_deref() == Some(published.version.as_str()) && published.public_key != lookup_record.public_key {
+            let text = |key: Option<PublicKey>| key.map_or_else(|| "no key".to_string(), |key| key.to_string());
+            return Err(CryptoError::PublisherKeyMismatch {
+                signer: text(lookup_record.public_key),
+                manifest: text(published.public_key),
+            }.into());
+        }
+
+        // The chunk hash lists must match the published Merkle root before they can be trusted
+        if let Some(expected_root) = &published.merkle_root {
+            for record in &mut published.package_records {
+                record.chunk_hashes = self.fetch_chunk_hashes(record).await?;
+            }
+            if Self::merkle_root(&published.package_records)? != *expected_root {
+                return Err(RoseliteError::ValidationError(format!(
+                    "Chunk hashes of {} version {} do not match the published Merkle root",
+                    uri.app_id, published.version
+                )));
+            }
+        }
+
+        Ok((published, site_key, lookup_record.app_info.veilid_identity.clone()))
+    }
+
+    /// Fetch the chunk hash list kept in the subkey after a package record's chunks
+    async fn fetch_chunk_hashes(&self, record: &PackageRecord) -> Result<Vec<HashDigest>> {
+        let invalid = || RoseliteError::ValidationError(format!(
+            "Package record {} has no valid chunk hash list", record.record_key
+        ));
+        let fetched = self.fetch_verified_chunk(record, record.chunk_count as u32).await?;
+        let hashes: Vec<HashDigest> = serde_json::from_slice(&fetched.data).map_err(|_| invalid())?;
+        if hashes.len() != record.chunk_count {
+            return Err(invalid());
+        }
+        Ok(hashes)
+    }
+
+    /// Fetch every chunk of a version concurrently, verifying each chunk as it arrives and
+    /// unsealing it when a site key is given. `buffered` yields results in submission order,
+    /// so chunks come out in content order.
+    fn fetch_chunks(&self, published: &PublishedVersion, site_key: Option<String>) -> impl Stream<Item = Result<FetchedChunk>> + Send + '_ {
+        let fetches: Vec<_> = published.package_records.iter()
+            .map(|record| Arc::new(record.clone()))
+            .flat_map(|record| (0..record.chunk_count as u32).map(move |subkey| (record.clone(), subkey)))
+            .map(|(record, subkey)| {
+                let site_key = site_key.clone();
+                async move {
+                    let mut chunk = self.fetch_verified_chunk(&record, subkey).await?;
+                    if let Some(key) = site_key {
+                        chunk.data = CryptoManager::new()?.decrypt(&chunk.data, &key)?;
+                    }
+                    Ok(chunk)
+                }
+            })
+            .collect();
+
+        stream::iter(fetches).buffered(self.transfer.download_concurrency.max(1))
+    }
+
+    /// Serialize a lookup record, sealing it under the site key of a private site and
+    /// wrapping that key for its recipients, and check it still fits in a single subkey.
+    fn encode_lookup_record(lookup_record: &LookupRecord, site_key: Option<&str>) -> Result<Vec<u8>> {
+        let lookup_json = Self::seal_lookup_record(lookup_record, site_key)?;
+        if lookup_json.len() > crate::dht::MAX_SUBKEY_SIZE {
+            return Err(RoseliteError::ValidationError(format!(
+                "Lookup record metadata exceeds {} byte subkey limit",
+                crate::dht::MAX_SUBKEY_SIZE
+            )));
+        }
+        Ok(lookup_json)
+    }
+
+    /// Serialize a lookup record, sealed under the site key for a private site
+    fn seal_lookup_record(lookup_record: &LookupRecord, site_key: Option<&str>) -> Result<Vec<u8>> {
+        let mut lookup_json = serde_json::to_vec(lookup_record)?;
+        if let Some(key) = site_key {
+            let crypto = CryptoManager::new()?;
+            let sealed = crypto.encrypt(&lookup_json, key)?;
+            let recipients = lookup_record.recipients.iter()
+                .map(|recipient| {
+                    let (ephemeral_public, wrapped_key) = crypto.wrap_content_key(key, recipient)?;
+                    Ok(WrappedSiteKey {
+                        recipient: recipient.clone(),
+                        ephemeral_public,
+                        wrapped_key,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            lookup_json = serde_json::to_vec(&SealedLookupRecord {
+                schema_version: LOOKUP_SCHEMA_VERSION.to_string(),
+                cipher: SITE_CIPHER.to_string(),
+                sealed: BASE64.encode(sealed),
+                recipients,
+            })?;
+        }
+        Ok(lookup_json)
+    }
+
+    /// Fetch and parse the lookup record stored at `app_id`.
+    async fn fetch_lookup_record(&self, app_id: &AppId) -> Result<LookupRecord> {
+        Ok(self.open_lookup_record(app_id, None).await?.0)
+    }
+
+    /// Fetch and parse the lookup record stored at `app_id`, unsealing it if the site is
+    /// private. Returns the site key that opened a private site's record.
+    async fn open_lookup_record(&self, app_id: &AppId, uri_key: Option<&str>) -> Result<(LookupRecord, Option<String>)> {
+        let lookup_bytes = self.conn.dht_get_subkey(&app_id.0, 0).await?
+            .ok_or_else(|| RoseliteError::Veilid(VeilidError::AppNotFound { 
+                app_id: app_id.0.clone() 
+            }))?;
+
+        if let Ok(sealed) = serde_json::from_slice::<SealedLookupRecord>(&lookup_bytes) {
+            let site_key = self.site_key(app_id, &sealed, uri_key)?;
+            let lookup_record = Self::unseal_lookup_record(&sealed, &site_key)?;
+            return Ok((lookup_record, Some(site_key)));
+        }
+
+        let lookup_record = serde_json::from_slice(&lookup_bytes)
+            .map_err(|_| RoseliteError::ValidationError(
+                "Invalid lookup record format".to_string()
+            ))?;
+        Ok((lookup_record, None))
+    }
+
+    /// App metadata of a lookup record whose signature checks out, carrying that signature.
+    fn verified_app_info(&self, lookup_record: LookupRecord) -> Result<AppInfo> {
+        lookup_record.verify(&self.conn.crypto()?)?;
+        let mut app_info = lookup_record.app_info;
+        app_info.signature = lookup_record.signature.map(|signature| signature.to_string());
+        Ok(app_info)
+    }
+
+    /// Decrypt the lookup record of a private site.
+    fn unseal_lookup_record(sealed: &SealedLookupRecord, site_key: &str) -> Result<LookupRecord> {
+        if sealed.cipher != SITE_CIPHER {
+            return Err(RoseliteError::ValidationError(format!(
+                "Unsupported private site cipher: {}", sealed.cipher
+            )));
+        }
+
+        let sealed_bytes = BASE64.decode(&sealed.sealed)
+            .map_err(|_| RoseliteError::ValidationError("Invalid sealed lookup record encoding".to_string()))?;
+        let lookup_json = CryptoManager::new()?.decrypt(&sealed_bytes, site_key)?;
+        serde_json::from_slice(&lookup_json)
+            .map_err(|_| RoseliteError::ValidationError(
+                "Invalid lookup record format".to_string()
+            ))
+    }
+}
+
+#[async_trait]
+impl<D: DhtBackend> AppStore for VeilidStore<D> {
+    /// Publish a package into the Veilid DHT using multi-record approach.
+    async fn publish(&mut self, package: Package) -> Result<(VeilUri, Package)> {
+        self.check_lookup_fits(&package, &[], None)?;
+        let package_hash = CryptoManager::new()?.hash(&package.content)?.to_string();
+        self.journal.begin(package_hash, &package.manifest.name, &package.manifest.version, None, None)?;
+        self.complete_publish(package, None).await
+    }
+
+    /// Upload new package records and point the existing lookup record at them.
+    /// A private site stays private under the same site key.
+    async fn update(&mut self, app_id: &AppId, package: Package) -> Result<(VeilUri, Package)> {
+        let (lookup_owner, previous, site_key) = self.prepare_update(app_id, &package).await?;
+        self.check_lookup_fits(&package, &previous.recipients, site_key.as_deref())?;
+        let package_hash = CryptoManager::new()?.hash(&package.content)?.to_string();
+        let sealer = match site_key {
+            Some(key) => {
+                if self.keystore.site_key(&app_id.0) != Some(key.as_str()) {
+                    self.keystore.set_site_key(&app_id.0, key.clone())?;
+                }
+                Some(ChunkSealer::new(key))
+            }
+            None => None,
+        };
+        self.journal.begin(
+            package_hash,
+            &package.manifest.name,
+            &package.manifest.version,
+            Some(&app_id.0),
+            sealer.map(|sealer| sealer.nonce_prefix),
+        )?;
+        self.complete_update(app_id, package, lookup_owner, previous).await
     }
 
     /// Retrieve application metadata from lookup record.
     async fn get_app(&self, app_id: &AppId) -> Result<Option<AppInfo>> {
         match self.conn.dht_get_subkey(&app_id.0, 0).await? {
             Some(bytes) => {
+                // Private sites can only be described with their key
+                if let Ok(sealed) = serde_json::from_slice::<SealedLookupRecord>(&bytes) {
+                    let site_key = self.site_key(app_id, &sealed, None)?;
+                    return self.verified_app_info(Self::unseal_lookup_record(&sealed, &site_key)?).map(Some);
+                }
+
                 // Try to parse as lookup record first
                 if let Ok(lookup_record) = serde_json::from_slice::<LookupRecord>(&bytes) {
-                    Ok(Some(lookup_record.app_info))
+                    self.verified_app_info(lookup_record).map(Some)
                 } else {
                     // Fallback: try to parse as legacy AppInfo for backwards compatibility
                     match serde_json::from_slice::<AppInfo>(&bytes) {
@@ -160,49 +1233,992 @@ impl AppStore for VeilidStore {
         }
     }
 
+    async fn list_versions(&self, app_id: &AppId) -> Result<Vec<PublishedVersion>> {
+        Ok(self.fetch_lookup_record(app_id).await?.history())
+    }
+
     /// Download the raw package bytes using multi-record approach.
     async fn download(&self, uri: &VeilUri) -> Result<Package> {
-        // Get lookup record
-        let lookup_bytes = self.conn.dht_get_subkey(&uri.app_id.0, 0).await?
-            .ok_or_else(|| RoseliteError::Veilid(crate::error::VeilidError::AppNotFound { 
-                app_id: uri.app_id.0.clone() 
-            }))?;
+        let started = Instant::now();
 
-        let lookup_record: LookupRecord = serde_json::from_slice(&lookup_bytes)
-            .map_err(|_| RoseliteError::ValidationError(
-                "Invalid lookup record format".to_string()
-            ))?;
+        // Get lookup record and pick the requested version
+        let (published, site_key, _) = self.resolve_download(uri).await?;
+        let lookup_elapsed = started.elapsed();
 
-        // Download content from all package records
-        let mut full_content = Vec::with_capacity(lookup_record.total_size_bytes);
-        
-        for package_record in &lookup_record.package_records {
-            // Download all chunks from this package record
-            for subkey in 0..package_record.chunk_count {
-                let chunk = self.conn.dht_get_subkey(&package_record.record_key, subkey as u32).await?
-                    .ok_or_else(|| RoseliteError::Veilid(crate::error::VeilidError::AppNotFound { 
-                        app_id: package_record.record_key.clone() 
-                    }))?;
-                full_content.extend_from_slice(&chunk);
-            }
+        // Download chunks from all package records concurrently
+        let concurrency = self.transfer.download_concurrency.max(1);
+        let chunk_total: usize = published.package_records.iter().map(|r| r.chunk_count).sum();
+
+        let fetch_started = Instant::now();
+        let chunks: Vec<FetchedChunk> = self.fetch_chunks(&published, site_key).try_collect().await?;
+        let fetch_elapsed = fetch_started.elapsed();
+
+        let mut full_content = Vec::with_capacity(published.total_size_bytes);
+        for chunk in &chunks {
+            full_content.extend_from_slice(&chunk.data);
         }
 
         // Verify total size matches expectation
-        if full_content.len() != lookup_record.total_size_bytes {
+        if full_content.len() != published.total_size_bytes {
             return Err(RoseliteError::ValidationError(format!(
                 "Downloaded content size ({} bytes) doesn't match expected size ({} bytes)",
-                full_content.len(), lookup_record.total_size_bytes
+                full_content.len(), published.total_size_bytes
             )));
         }
 
-        tracing::info!("Downloaded package from {} package records ({} total bytes)", 
-            lookup_record.package_records.len(), full_content.len());
+        tracing::info!("Downloaded version {} from {} package records ({} total bytes)", 
+            published.version, published.package_records.len(), full_content.len());
 
+        let slowest = chunks.iter().map(|c| c.elapsed).max().unwrap_or_default();
+        let average = chunks.iter().map(|c| c.elapsed).sum::<Duration>()
+            .checked_div(chunk_total as u32)
+            .unwrap_or_default();
+        let retries: u32 = chunks.iter().map(|c| c.attempts - 1).sum();
+
+        let parse_started = Instant::now();
         let package = Package::from_bytes(full_content).await?;
+        published.check_package(&self.conn.crypto()?, &package)?;
+
+        tracing::info!(
+            "Download timing: lookup {:?}, {} chunks in {:?} at concurrency {} (avg {:?}, slowest {:?}, {} retries), parse {:?}, total {:?}",
+            lookup_elapsed, chunk_total, fetch_elapsed, concurrency, average, slowest, retries,
+            parse_started.elapsed(), started.elapsed()
+        );
+
         Ok(package)
     }
 
+    /// Stream the verified chunks of a version without holding the whole package in memory.
+    async fn download_stream(&self, uri: &VeilUri) -> Result<PackageDownload<'_>> {
+        let (published, site_key, identity) = self.resolve_download(uri).await?;
+        let expected = published.total_size_bytes;
+        let app_id = uri.app_id.clone();
+
+        // Track the bytes handed out so a short or oversized download surfaces as a final error
+        let fetched = Box::pin(self.fetch_chunks(&published, site_key));
+        let chunks = stream::unfold((fetched, 0usize, false), move |(mut fetched, received, finished)| {
+            let app_id = app_id.clone();
+            async move {
+                if finished {
+                    return None;
+                }
+                match fetched.next().await {
+                    Some(Ok(chunk)) => {
+                        let received = received + chunk.data.len();
+                        Some((Ok(chunk.data), (fetched, received, false)))
+                    }
+                    Some(Err(e)) => Some((Err(e), (fetched, received, true))),
+                    None if received != expected => Some((
+                        Err(RoseliteError::ValidationError(format!(
+                            "Downloaded content size of {} ({} bytes) doesn't match expected size ({} bytes)",
+                            app_id, received, expected
+                        ))),
+                        (fetched, received, true),
+                    )),
+                    None => None,
+                }
+            }
+        });
+
+        Ok(PackageDownload {
+            version: published,
+            identity,
+            chunks: Box::pin(chunks),
+        })
+    }
+
+    /// Remove an app we own from the DHT, package records first so a failure never
+    /// leaves a lookup record pointing at deleted data.
+    async fn unpublish(&mut self, app_id: &AppId) -> Result<usize> {
+        if self.keystore.record_owner(&app_id.0).is_none() {
+            return Err(RoseliteError::PermissionDenied(format!(
+                "No owner key for {} in the local keystore", app_id
+            )));
+        }
+
+        let lookup_record = self.fetch_lookup_record(app_id).await?;
+        let mut record_keys: Vec<String> = lookup_record.history().into_iter()
+            .flat_map(|version| version.package_records)
+            .map(|record| record.record_key)
+            .collect();
+        record_keys.sort();
+        record_keys.dedup();
+
+        let mut deleted = 0;
+        for record_key in &record_keys {
+            // A package record that is already gone should not keep the app alive
+            match self.conn.dht_delete_record(record_key).await {
+                Ok(()) => deleted += 1,
+                Err(e) => tracing::warn!("Failed to delete package record {}: {}", record_key, e),
+            }
+        }
+
+        self.conn.dht_delete_record(&app_id.0).await?;
+        self.keystore.remove_record_owner(&app_id.0)?;
+        self.keystore.remove_site_key(&app_id.0)?;
+
+        tracing::info!("Unpublished {}: deleted {} of {} package records and the lookup record",
+            app_id, deleted, record_keys.len());
+
+        Ok(deleted + 1)
+    }
+
     async fn shutdown(&mut self) -> Result<()> {
         self.conn.disconnect().await
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dht::MemoryDht;
+    use crate::package::PackageBuilder;
+    use crate::trust::{TrustDecision, TrustPolicy};
+    use tempfile::TempDir;
+    use std::fs;
+
+    async fn build_test_package(dir: &TempDir, payload_size: usize) -> Package {
+        let source_dir = dir.path().join("site");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::write(source_dir.join("index.html"), b"<html><body>Hello DHT</body></html>").unwrap();
+
+        // Incompressible payload so the package spans many chunks
+        let mut state: u32 = 0x1234_5678;
+        let payload: Vec<u8> = (0..payload_size).map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 24) as u8
+        }).collect();
+        fs::write(source_dir.join("blob.bin"), payload).unwrap();
+
+        PackageBuilder::new("store-test".to_string(), &source_dir)
+            .version("1.0.0".to_string())
+            .developer("Test Developer".to_string())
+            .build()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_publish_download_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 64 * 1024).await;
+        let original_content = package.content.clone();
+
+        let mut store = VeilidStore::with_backend(MemoryDht::new());
+        let (uri, published) = store.publish(package).await.unwrap();
+        assert_eq!(published.manifest.dht_key, uri.app_id.0);
+
+        let info = store.get_app(&uri.app_id).await.unwrap().unwrap();
+        assert_eq!(info.name, "store-test");
+        assert_eq!(info.id, uri.app_id);
+
+        let downloaded = store.download(&uri).await.unwrap();
+        assert_eq!(downloaded.content, original_content);
+        assert_eq!(downloaded.manifest.name, "store-test");
+
+        store.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_stream_yields_chunks_in_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 1_200_000).await;
+        let original_content = package.content.clone();
+
+        let mut store = VeilidStore::with_backend(MemoryDht::new()).with_transfer_config(fast_retries(0));
+        let (uri, _) = store.publish(package).await.unwrap();
+
+        let download = store.download_stream(&uri).await.unwrap();
+        assert_eq!(download.version.version, "1.0.0");
+        let expected_chunks: usize = download.version.package_records.iter().map(|r| r.chunk_count).sum();
+
+        let chunks: Vec<Vec<u8>> = download.chunks.try_collect().await.unwrap();
+        assert_eq!(chunks.len(), expected_chunks);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= VeilidStore::<MemoryDht>::CHUNK_SIZE));
+        assert_eq!(chunks.concat(), original_content);
+
+        // A corrupt chunk ends the stream with an error instead of passing bad data through
+        let record_key = store.fetch_lookup_record(&uri.app_id).await.unwrap().versions[0].package_records[1].record_key.clone();
+        let original = store.backend().dht_get_subkey(&record_key, 0).await.unwrap().unwrap();
+        store.backend().dht_set_subkey(&record_key, 0, &vec![0u8; original.len()]).await.unwrap();
+
+        let download = store.download_stream(&uri).await.unwrap();
+        let results: Vec<Result<Vec<u8>>> = download.chunks.collect().await;
+        assert!(matches!(results.last(), Some(Err(RoseliteError::Veilid(VeilidError::ChunkHashMismatch { .. })))));
+    }
+
+    #[tokio::test]
+    async fn test_private_site_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 1_200_000).await;
+        let original_content = package.content.clone();
+
+        let dht = MemoryDht::new();
+        let mut store = VeilidStore::with_backend(dht.clone());
+        let (uri, _) = store.publish_private(package).await.unwrap();
+        let site_key = uri.key.clone().unwrap();
+        assert_eq!(store.keystore().site_key(&uri.app_id.0), Some(site_key.as_str()));
+
+        // Neither the metadata nor the package is readable from the DHT
+        let raw_lookup = dht.dht_get_subkey(&uri.app_id.0, 0).await.unwrap().unwrap();
+        assert!(!String::from_utf8_lossy(&raw_lookup).contains("store-test"));
+        let lookup = store.fetch_lookup_record(&uri.app_id).await.unwrap();
+        let first_chunk = dht.dht_get_subkey(&lookup.versions[0].package_records[0].record_key, 0).await.unwrap().unwrap();
+        assert_ne!(&first_chunk[..], &original_content[..first_chunk.len()]);
+
+        // A reader without the key is refused; the share link opens it
+        let mut reader = VeilidStore::with_backend(dht.clone());
+        assert!(matches!(reader.get_app(&uri.app_id).await, Err(RoseliteError::PermissionDenied(_))));
+        let keyless = VeilUri::new(uri.app_id.clone(), None);
+        assert!(matches!(reader.download(&keyless).await, Err(RoseliteError::PermissionDenied(_))));
+        assert_eq!(reader.download(&uri).await.unwrap().content, original_content);
+
+        let streamed: Vec<Vec<u8>> = reader.download_stream(&uri).await.unwrap().chunks.try_collect().await.unwrap();
+        assert_eq!(streamed.concat(), original_content);
+
+        // A wrong key fails to decrypt
+        let wrong = uri.clone().with_key(Some(CryptoManager::new().unwrap().generate_content_key().unwrap()));
+        assert!(matches!(reader.download(&wrong).await, Err(RoseliteError::Crypto(CryptoError::DecryptionFailed))));
+
+        reader.add_site_key(&uri.app_id, site_key.clone());
+        assert_eq!(reader.get_app(&uri.app_id).await.unwrap().unwrap().name, "store-test");
+
+        // Updates stay private under the same key
+        let mut second = build_test_package(&TempDir::new().unwrap(), 4 * 1024).await;
+        second.manifest.version = "2.0.0".to_string();
+        let second_content = second.content.clone();
+        let (updated_uri, _) = store.update(&uri.app_id, second).await.unwrap();
+        assert_eq!(updated_uri.key.as_deref(), Some(site_key.as_str()));
+        assert_eq!(reader.download(&keyless).await.unwrap().content, second_content);
+        assert!(matches!(
+            VeilidStore::with_backend(dht).get_app(&uri.app_id).await,
+            Err(RoseliteError::PermissionDenied(_))
+        ));
+    }
+
+    /// A store on `dht` whose keystore holds a fresh recipient key, and that key
+    fn recipient_store(dht: &MemoryDht) -> (VeilidStore<MemoryDht>, String) {
+        let mut keystore = KeyStore::in_memory();
+        let recipient = keystore.create_recipient_key().unwrap();
+        (VeilidStore::with_backend(dht.clone()).with_keystore(keystore), recipient)
+    }
+
+    #[tokio::test]
+    async fn test_recipient_scoped_site_and_rekey() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 20 * 1024).await;
+        let original_content = package.content.clone();
+        let developer_key = package.signing_key.clone().unwrap();
+
+        let dht = MemoryDht::new();
+        let (alice, alice_key) = recipient_store(&dht);
+        let (bob, bob_key) = recipient_store(&dht);
+        let mut store = VeilidStore::with_backend(dht.clone());
+
+        assert!(store.publish_to_recipients(package.clone(), vec!["not-a-key".to_string()]).await.is_err());
+        let (uri, _) = store.publish_to_recipients(package, vec![alice_key.clone()]).await.unwrap();
+        assert!(uri.key.is_none());
+
+        // The lookup record names its recipients but only they can open it
+        let raw_lookup = dht.dht_get_subkey(&uri.app_id.0, 0).await.unwrap().unwrap();
+        let sealed: SealedLookupRecord = serde_json::from_slice(&raw_lookup).unwrap();
+        assert_eq!(sealed.recipients.len(), 1);
+        assert_eq!(sealed.recipients[0].recipient, alice_key);
+
+        assert_eq!(alice.get_app(&uri.app_id).await.unwrap().unwrap().name, "store-test");
+        assert_eq!(alice.download(&uri).await.unwrap().content, original_content);
+        assert!(matches!(bob.get_app(&uri.app_id).await, Err(RoseliteError::PermissionDenied(_))));
+
+        // Re-keying for bob alone locks alice out, and bob can read the existing version
+        let old_key = store.keystore().site_key(&uri.app_id.0).unwrap().to_string();
+        store.add_signing_key(developer_key.clone());
+        let rekeyed = store.rekey(&uri.app_id, vec![bob_key.clone()]).await.unwrap();
+        let record = store.fetch_lookup_record(&uri.app_id).await.unwrap();
+        assert_eq!(record.public_key, Some(developer_key.public_key()));
+        assert_ne!(rekeyed.key.as_deref(), Some(old_key.as_str()));
+        assert_eq!(rekeyed.key.as_deref(), store.keystore().site_key(&uri.app_id.0));
+        assert!(matches!(alice.download(&uri).await, Err(RoseliteError::PermissionDenied(_))));
+        assert_eq!(bob.download(&uri).await.unwrap().content, original_content);
+
+        // Later versions follow the new key and recipients
+        let mut second = build_test_package(&TempDir::new().unwrap(), 4 * 1024).await;
+        second.manifest.version = "2.0.0".to_string();
+        let second_content = second.content.clone();
+        let (updated_uri, _) = store.update(&uri.app_id, second).await.unwrap();
+        assert!(updated_uri.key.is_none());
+        let latest = VeilUri::new(uri.app_id.clone(), None);
+        assert_eq!(bob.download(&latest).await.unwrap().content, second_content);
+        assert_eq!(bob.download(&uri).await.unwrap().content, original_content);
+        assert!(matches!(alice.get_app(&uri.app_id).await, Err(RoseliteError::PermissionDenied(_))));
+
+        // Public sites cannot be re-keyed
+        let (public_uri, _) = store.publish(build_test_package(&TempDir::new().unwrap(), 1024).await).await.unwrap();
+        assert!(matches!(store.rekey(&public_uri.app_id, Vec::new()).await, Err(RoseliteError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_key_statements_append_to_identity_record() {
+        let crypto = CryptoManager::new().unwrap();
+        let identity = crate::identity::Identity::generate("work").unwrap();
+        let (rotated, first) = identity.rotated().unwrap();
+        let (_, second) = rotated.rotated().unwrap();
+
+        let mut store = VeilidStore::with_backend(MemoryDht::new());
+        let record_key = store.publish_key_statement(None, KeyStatement::Rotation(first)).await.unwrap();
+        assert!(store.keystore().record_owner(&record_key).is_some());
+        assert_eq!(store.publish_key_statement(Some(&record_key), KeyStatement::Rotation(second)).await.unwrap(), record_key);
+
+        let record = store.fetch_identity_record(&record_key).await.unwrap();
+        assert_eq!(record.statements.len(), 2);
+        let KeyStatement::Rotation(endorsement) = &record.statements[1] else {
+            panic!("expected a rotation statement");
+        };
+        endorsement.verify(&crypto).unwrap();
+        assert_eq!(endorsement.old_key, rotated.public_key);
+
+        // A published revocation reaches the trust policy of anyone checking the publisher
+        let revocation = rotated.revocation(Some(&identity.public_key), None).unwrap();
+        store.publish_key_statement(Some(&record_key), KeyStatement::Revocation(revocation)).await.unwrap();
+        let mut policy = TrustPolicy::in_memory();
+        store.load_key_statements(&record_key, &mut policy).await.unwrap();
+        assert_eq!(policy.check("app", &identity.public_key).unwrap(), TrustDecision::Revoked(None));
+    }
+
+    #[tokio::test]
+    async fn test_publish_spans_multiple_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 1_200_000).await;
+        let original_content = package.content.clone();
+
+        let mut store = VeilidStore::with_backend(MemoryDht::new());
+        let (uri, _) = store.publish(package).await.unwrap();
+
+        // Two package records plus the lookup record
+        assert_eq!(store.backend().record_count().await, 3);
+
+        let downloaded = store.download(&uri).await.unwrap();
+        assert_eq!(downloaded.content, original_content);
+    }
+
+    #[tokio::test]
+    async fn test_update_keeps_site_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = build_test_package(&temp_dir, 16 * 1024).await;
+
+        let mut store = VeilidStore::with_backend(MemoryDht::new());
+        let (uri, _) = store.publish(first).await.unwrap();
+        assert!(store.keystore().record_owner(&uri.app_id.0).is_some());
+
+        let second_dir = TempDir::new().unwrap();
+        let mut second = build_test_package(&second_dir, 24 * 1024).await;
+        second.manifest.version = "2.0.0".to_string();
+        let second_content = second.content.clone();
+
+        let (updated_uri, updated) = store.update(&uri.app_id, second).await.unwrap();
+        assert_eq!(updated_uri.app_id, uri.app_id);
+        assert_eq!(updated.manifest.dht_key, uri.app_id.0);
+
+        let info = store.get_app(&uri.app_id).await.unwrap().unwrap();
+        assert_eq!(info.version, "2.0.0");
+
+        let downloaded = store.download(&VeilUri::new(uri.app_id.clone(), None)).await.unwrap();
+        assert_eq!(downloaded.content, second_content);
+    }
+
+    #[tokio::test]
+    async fn test_many_updates_keep_lookup_record_bounded() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 4 * 1024).await;
+        let dht = MemoryDht::new();
+        let mut store = VeilidStore::with_backend(dht.clone());
+        let (uri, _) = store.publish(package.clone()).await.unwrap();
+
+        let updates = MAX_VERSIONS + 4;
+        for i in 1..=updates {
+            let mut next = package.clone();
+            next.manifest.version = format!("1.0.{}", i);
+            store.update(&uri.app_id, next).await.unwrap();
+        }
+
+        // Only the newest versions are listed, and the package records of the rest are gone
+        let history = store.list_versions(&uri.app_id).await.unwrap();
+        assert_eq!(history.len(), MAX_VERSIONS);
+        assert_eq!(history.last().unwrap().version, format!("1.0.{}", updates));
+        assert_eq!(dht.record_count().await, 1 + MAX_VERSIONS);
+        let oldest = VeilUri::new(uri.app_id.clone(), Some(history[0].version.clone()));
+        assert!(store.download(&oldest).await.is_ok());
+        let dropped = VeilUri::new(uri.app_id.clone(), Some("1.0.0".to_string()));
+        assert!(matches!(store.download(&dropped).await, Err(RoseliteError::VersionMismatch(_))));
+
+        // Recipients take up most of a private site's lookup record, so fewer versions fit
+        let recipients: Vec<String> = (0..60).map(|_| recipient_store(&dht).1).collect();
+        let (private_uri, _) = store.publish_to_recipients(package.clone(), recipients.clone()).await.unwrap();
+        for i in 1..=updates {
+            let mut next = package.clone();
+            next.manifest.version = format!("1.0.{}", i);
+            store.update(&private_uri.app_id, next).await.unwrap();
+            let raw_lookup = dht.dht_get_subkey(&private_uri.app_id.0, 0).await.unwrap().unwrap();
+            assert!(raw_lookup.len() <= crate::dht::MAX_SUBKEY_SIZE);
+        }
+        let history = store.list_versions(&private_uri.app_id).await.unwrap();
+        assert!(history.len() > 1 && history.len() < MAX_VERSIONS);
+        assert_eq!(history.last().unwrap().version, format!("1.0.{}", updates));
+
+        // A site whose lookup record cannot list even one version is refused before any upload
+        let records = dht.record_count().await;
+        let crowd: Vec<String> = (0..100).map(|_| recipient_store(&dht).1).collect();
+        assert!(matches!(
+            store.publish_to_recipients(package, crowd).await,
+            Err(RoseliteError::ValidationError(_))
+        ));
+        assert_eq!(dht.record_count().await, records);
+        assert!(store.journal().pending().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_download_resolves_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = build_test_package(&temp_dir, 4 * 1024).await;
+        let first_content = first.content.clone();
+
+        let mut store = VeilidStore::with_backend(MemoryDht::new());
+        let (uri, _) = store.publish(first).await.unwrap();
+        assert_eq!(uri.version.as_deref(), Some("1.0.0"));
+
+        let mut second = build_test_package(&TempDir::new().unwrap(), 6 * 1024).await;
+        second.manifest.version = "1.1.0".to_string();
+        let second_content = second.content.clone();
+        store.update(&uri.app_id, second).await.unwrap();
+
+        let versions = store.list_versions(&uri.app_id).await.unwrap();
+        let names: Vec<&str> = versions.iter().map(|v| v.version.as_str()).collect();
+        assert_eq!(names, vec!["1.0.0", "1.1.0"]);
+
+        let pinned = VeilUri::new(uri.app_id.clone(), Some("1.0.0".to_string()));
+        assert_eq!(store.download(&pinned).await.unwrap().content, first_content);
+
+        let latest = VeilUri::new(uri.app_id.clone(), None);
+        assert_eq!(store.download(&latest).await.unwrap().content, second_content);
+
+        let missing = VeilUri::new(uri.app_id.clone(), Some("9.9.9".to_string()));
+        assert!(matches!(store.download(&missing).await, Err(RoseliteError::VersionMismatch(_))));
+
+        // Republishing an existing version is refused
+        let duplicate = build_test_package(&TempDir::new().unwrap(), 1024).await;
+        assert!(matches!(
+            store.update(&uri.app_id, duplicate).await,
+            Err(RoseliteError::VersionMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_legacy_lookup_record_resolves() {
+        let json = serde_json::json!({
+            "app_info": {
+                "id": "VLD0:legacy",
+                "name": "legacy",
+                "version": "0.9.0",
+                "description": "",
+                "developer": "",
+                "category": "general",
+                "size_bytes": 10,
+                "created_at": "2024-01-01T00:00:00Z",
+                "updated_at": "2024-01-01T00:00:00Z",
+                "tags": [],
+                "entry_point": "index.html",
+                "veilid_identity": null,
+                "signature": null
+            },
+            "package_records": [{ "record_key": "VLD0:pkg", "chunk_count": 1, "size_bytes": 10 }],
+            "total_size_bytes": 10,
+            "schema_version": "1.0"
+        });
+        let record: LookupRecord = serde_json::from_value(json).unwrap();
+
+        let latest = record.resolve(None).unwrap();
+        assert_eq!(latest.version, "0.9.0");
+        assert_eq!(latest.package_records[0].record_key, "VLD0:pkg");
+        assert!(record.resolve(Some("0.9.0")).is_some());
+        assert!(record.resolve(Some("1.0.0")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_requires_owner_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let dht = MemoryDht::new();
+
+        let mut publisher = VeilidStore::with_backend(dht.clone());
+        let (uri, _) = publisher.publish(build_test_package(&temp_dir, 1024).await).await.unwrap();
+
+        // A second store sharing the DHT but not the keystore cannot rewrite the site
+        let mut other = VeilidStore::with_backend(dht);
+        let package = build_test_package(&TempDir::new().unwrap(), 1024).await;
+        assert!(matches!(
+            other.update(&uri.app_id, package).await,
+            Err(RoseliteError::PermissionDenied(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unpublish_removes_all_records() {
+        let dht = MemoryDht::new();
+        let mut store = VeilidStore::with_backend(dht.clone());
+        let (uri, _) = store.publish(build_test_package(&TempDir::new().unwrap(), 1_200_000).await).await.unwrap();
+
+        let mut second = build_test_package(&TempDir::new().unwrap(), 4 * 1024).await;
+        second.manifest.version = "2.0.0".to_string();
+        store.update(&uri.app_id, second).await.unwrap();
+        assert_eq!(dht.record_count().await, 4);
+
+        // Only the owner can unpublish
+        let mut other = VeilidStore::with_backend(dht.clone());
+        assert!(matches!(other.unpublish(&uri.app_id).await, Err(RoseliteError::PermissionDenied(_))));
+
+        assert_eq!(store.unpublish(&uri.app_id).await.unwrap(), 4);
+        assert_eq!(dht.record_count().await, 0);
+        assert!(store.keystore().record_owner(&uri.app_id.0).is_none());
+        assert!(store.get_app(&uri.app_id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_corrupt_chunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 32 * 1024).await;
+
+        let dht = MemoryDht::new();
+        let mut store = VeilidStore::with_backend(dht.clone()).with_transfer_config(fast_retries(3));
+        let (uri, _) = store.publish(package).await.unwrap();
+
+        // Overwrite one chunk with same-length garbage
+        let lookup = store.fetch_lookup_record(&uri.app_id).await.unwrap();
+        let record_key = lookup.versions[0].package_records[0].record_key.clone();
+        let original = dht.dht_get_subkey(&record_key, 2).await.unwrap().unwrap();
+        let corrupt: Vec<u8> = original.iter().map(|b| b ^ 0xff).collect();
+        dht.dht_set_subkey(&record_key, 2, &corrupt).await.unwrap();
+
+        match store.download(&uri).await {
+            Err(RoseliteError::Veilid(VeilidError::ChunkHashMismatch { record_key: key, subkey })) => {
+                assert_eq!(key, record_key);
+                assert_eq!(subkey, 2);
+            }
+            other => panic!("expected chunk hash mismatch, got {:?}", other.map(|p| p.size_bytes)),
+        }
+
+        // Restoring the chunk makes the download succeed again
+        dht.dht_set_subkey(&record_key, 2, &original).await.unwrap();
+        assert!(store.download(&uri).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_tampered_hash_list() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 16 * 1024).await;
+
+        let dht = MemoryDht::new();
+        let mut store = VeilidStore::with_backend(dht.clone());
+        let (uri, _) = store.publish(package).await.unwrap();
+
+        // The lookup record carries only the Merkle root, not the per-chunk hashes
+        let raw_lookup = dht.dht_get_subkey(&uri.app_id.0, 0).await.unwrap().unwrap();
+        assert!(!String::from_utf8_lossy(&raw_lookup).contains("chunk_hashes"));
+
+        // Replace the hash list after the chunks with one that no longer matches the root
+        let lookup = store.fetch_lookup_record(&uri.app_id).await.unwrap();
+        let record = &lookup.versions[0].package_records[0];
+        let subkey = record.chunk_count as u32;
+        let raw_hashes = dht.dht_get_subkey(&record.record_key, subkey).await.unwrap().unwrap();
+        let mut hashes: Vec<HashDigest> = serde_json::from_slice(&raw_hashes).unwrap();
+        assert_eq!(hashes.len(), record.chunk_count);
+        hashes[0] = CryptoManager::new().unwrap().hash(b"forged").unwrap();
+        dht.dht_set_subkey(&record.record_key, subkey, &serde_json::to_vec(&hashes).unwrap()).await.unwrap();
+
+        let pinned = VeilUri::new(uri.app_id.clone(), Some("1.0.0".to_string()));
+        assert!(matches!(store.download(&pinned).await, Err(RoseliteError::ValidationError(_))));
+
+        // A hash list that is missing entirely is refused the same way
+        dht.dht_set_subkey(&record.record_key, subkey, b"[]").await.unwrap();
+        assert!(matches!(store.download(&pinned).await, Err(RoseliteError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_record_signature_is_verified() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 4 * 1024).await;
+        let developer_key = package.manifest.public_key;
+
+        let mut store = VeilidStore::with_backend(MemoryDht::new());
+        let (uri, _) = store.publish(package).await.unwrap();
+        let app_info = store.get_app(&uri.app_id).await.unwrap().unwrap();
+        assert!(app_info.signature.is_some());
+
+        let original = store.fetch_lookup_record(&uri.app_id).await.unwrap();
+        assert_eq!(original.public_key, developer_key);
+        let owner = store.keystore().record_owner(&uri.app_id.0).unwrap().to_string();
+        let crypto = CryptoManager::new().unwrap();
+
+        // Swapping the package records without the developer key breaks the signature
+        let mut swapped = original.clone();
+        swapped.versions[0].total_size_bytes += 1;
+        let json = serde_json::to_vec(&swapped).unwrap();
+        store.backend().dht_set_subkey_as(&uri.app_id.0, 0, &json, &owner).await.unwrap();
+        assert!(matches!(
+            store.get_app(&uri.app_id).await,
+            Err(RoseliteError::Crypto(CryptoError::SignatureVerificationFailed))
+        ));
+        assert!(matches!(
+            store.download(&uri).await,
+            Err(RoseliteError::Crypto(CryptoError::SignatureVerificationFailed))
+        ));
+
+        // Unsigned records are refused
+        let mut unsigned = original.clone();
+        unsigned.signature = None;
+        let json = serde_json::to_vec(&unsigned).unwrap();
+        store.backend().dht_set_subkey_as(&uri.app_id.0, 0, &json, &owner).await.unwrap();
+        assert!(matches!(store.get_app(&uri.app_id).await, Err(RoseliteError::Crypto(CryptoError::MissingSignature))));
+
+        // Re-signing with another key does not match the developer key in the package manifest
+        let (other_public, other_secret) = crypto.generate_keypair().unwrap();
+        let mut resigned = original.clone();
+        resigned.versions[0].public_key = Some(other_public);
+        resigned.sign(&crypto, &other_public, &other_secret).unwrap();
+        let json = serde_json::to_vec(&resigned).unwrap();
+        store.backend().dht_set_subkey_as(&uri.app_id.0, 0, &json, &owner).await.unwrap();
+        store.get_app(&uri.app_id).await.unwrap();
+        assert!(matches!(
+            store.download(&uri).await,
+            Err(RoseliteError::Crypto(CryptoError::PublisherKeyMismatch { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_veilid_key_owns_and_signs_site() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("index.html"), b"<html></html>").unwrap();
+        let (public_key, keypair) = CryptoManager::new().unwrap().generate_keypair_in(KeyFormat::Veilid).unwrap();
+        let package = PackageBuilder::new("veilid-signed".to_string(), temp_dir.path())
+            .keypair(public_key, keypair.clone())
+            .build()
+            .await
+            .unwrap();
+        assert!(package.verify_signature(&CryptoManager::new().unwrap()).unwrap());
+
+        let mut store = VeilidStore::with_backend(MemoryDht::new());
+        let (uri, _) = store.publish(package).await.unwrap();
+
+        // The signing keypair is the lookup record's owner, and the record verifies with its typed public key
+        assert_eq!(store.keystore().record_owner(&uri.app_id.0), Some(keypair.expose().as_str()));
+        let record = store.fetch_lookup_record(&uri.app_id).await.unwrap();
+        assert_eq!(record.signature.unwrap().format(), KeyFormat::Veilid);
+        assert_eq!(store.download(&uri).await.unwrap().manifest.public_key, Some(public_key));
+    }
+
+    #[tokio::test]
+    async fn test_publish_offline_signed_package() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("index.html"), b"<html></html>").unwrap();
+        let crypto = CryptoManager::new().unwrap();
+        let (public_key, secret_key) = crypto.generate_keypair().unwrap();
+        let unsigned = PackageBuilder::new("offline".to_string(), temp_dir.path())
+            .public_key(public_key)
+            .build()
+            .await
+            .unwrap();
+
+        // Packages whose detached signature does not verify are refused before any upload
+        let mut store = VeilidStore::with_backend(MemoryDht::new());
+        assert!(matches!(
+            store.publish(unsigned.clone()).await,
+            Err(RoseliteError::Crypto(CryptoError::SignatureVerificationFailed))
+        ));
+        assert!(store.keystore().owned_records().is_empty());
+        assert!(store.journal().pending().is_none());
+        assert!(store.publish_private(unsigned.clone()).await.is_err());
+        assert!(store.journal().pending().is_none());
+
+        // Signed offline, it still needs its secret to sign the lookup record
+        let mut signed = Package::from_bytes(unsigned.content.clone()).await.unwrap();
+        signed.sign(&crypto, &secret_key).unwrap();
+        assert!(matches!(
+            store.publish(signed.clone()).await,
+            Err(RoseliteError::Crypto(CryptoError::SigningKeyUnavailable(_)))
+        ));
+        assert!(store.keystore().owned_records().is_empty());
+        assert!(store.journal().pending().is_none());
+
+        store.add_signing_key(secret_key);
+        let (uri, _) = store.publish(signed).await.unwrap();
+        let record = store.fetch_lookup_record(&uri.app_id).await.unwrap();
+        assert_eq!(record.public_key, Some(public_key));
+        let downloaded = store.download_stream(&uri).await.unwrap();
+        assert_eq!(downloaded.version.public_key, Some(public_key));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_record_cannot_vouch_for_another_publisher() {
+        let temp_dir = TempDir::new().unwrap();
+        let victim_package = build_test_package(&temp_dir, 4 * 1024).await;
+        let victim_key = victim_package.manifest.public_key.unwrap();
+        let dht = MemoryDht::new();
+        let mut store = VeilidStore::with_backend(dht.clone());
+        let (uri, _) = store.publish(victim_package).await.unwrap();
+        let owner = store.keystore().record_owner(&uri.app_id.0).unwrap().to_string();
+        let victim_version = store.fetch_lookup_record(&uri.app_id).await.unwrap().versions[0].clone();
+
+        // The attacker publishes a package of their own under their own key
+        let attacker_package = build_test_package(&TempDir::new().unwrap(), 4 * 1024).await;
+        let attacker_secret = attacker_package.signing_key.clone().unwrap();
+        let attacker_key = attacker_secret.public_key();
+        let mut attacker = VeilidStore::with_backend(dht.clone());
+        let (attacker_uri, _) = attacker.publish(attacker_package).await.unwrap();
+        let attacker_version = attacker.fetch_lookup_record(&attacker_uri.app_id).await.unwrap().versions[0].clone();
+
+        // With write access to the victim's record, they point it at a package and re-sign it
+        let crypto = CryptoManager::new().unwrap();
+        let original = store.fetch_lookup_record(&uri.app_id).await.unwrap();
+        let forge = |version: PublishedVersion| {
+            let mut forged = LookupRecord { versions: vec![version], ..original.clone() };
+            forged.sign(&crypto, &attacker_key, &attacker_secret).unwrap();
+            serde_json::to_vec(&forged).unwrap()
+        };
+
+        // Claiming the victim's key for the attacker's package does not match the record's signer
+        let claimed = PublishedVersion { public_key: Some(victim_key), ..attacker_version.clone() };
+        dht.dht_set_subkey_as(&uri.app_id.0, 0, &forge(claimed), &owner).await.unwrap();
+        assert!(matches!(
+            store.download(&uri).await,
+            Err(RoseliteError::Crypto(CryptoError::PublisherKeyMismatch { .. }))
+        ));
+
+        // The victim's own package does not declare the attacker's key
+        let borrowed = PublishedVersion { public_key: Some(attacker_key), ..victim_version };
+        dht.dht_set_subkey_as(&uri.app_id.0, 0, &forge(borrowed), &owner).await.unwrap();
+        assert!(matches!(
+            store.download(&uri).await,
+            Err(RoseliteError::Crypto(CryptoError::PublisherKeyMismatch { .. }))
+        ));
+
+        // A consistently signed attacker package downloads, but the pinned publisher refuses it
+        dht.dht_set_subkey_as(&uri.app_id.0, 0, &forge(attacker_version), &owner).await.unwrap();
+        let package = store.download(&uri).await.unwrap();
+        assert_eq!(package.manifest.public_key, Some(attacker_key));
+        let mut policy = TrustPolicy::in_memory();
+        policy.pin(&uri.app_id.0, &victim_key, None).unwrap();
+        assert!(matches!(
+            package.verify_trusted(&crypto, &mut policy, &uri.app_id.0),
+            Err(RoseliteError::Crypto(CryptoError::UntrustedKey(_)))
+        ));
+    }
+
+    type SubkeyCounts = std::collections::HashMap<(String, u32), u32>;
+
+    /// Backend that fails the first reads and writes of every chunk to exercise retries,
+    /// and can refuse all chunk writes once a fixed number have succeeded
+    struct FlakyDht {
+        inner: MemoryDht,
+        failures_per_subkey: u32,
+        write_failures_per_subkey: u32,
+        write_budget: Option<usize>,
+        reads: tokio::sync::Mutex<SubkeyCounts>,
+        writes: tokio::sync::Mutex<(SubkeyCounts, usize)>,
+    }
+
+    impl FlakyDht {
+        fn new(inner: MemoryDht) -> Self {
+            Self {
+                inner,
+                failures_per_subkey: 0,
+                write_failures_per_subkey: 0,
+                write_budget: None,
+                reads: Default::default(),
+                writes: Default::default(),
+            }
+        }
+
+        async fn check_write(&self, key: &str, subkey: u32, value: &[u8]) -> Result<()> {
+            // Leave the single-subkey lookup record alone
+            if value.starts_with(b"{") {
+                return Ok(());
+            }
+
+            let mut writes = self.writes.lock().await;
+            let (attempts, succeeded) = &mut *writes;
+            let count = attempts.entry((key.to_string(), subkey)).or_insert(0);
+            *count += 1;
+            if *count <= self.write_failures_per_subkey
+                || self.write_budget.is_some_and(|budget| *succeeded >= budget) {
+                return Err(RoseliteError::NetworkError("simulated write timeout".to_string()));
+            }
+            *succeeded += 1;
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl DhtBackend for FlakyDht {
+        async fn create_owned_dht_record(&self, cols: usize) -> Result<(String, String)> {
+            self.inner.create_owned_dht_record(cols).await
+        }
+
+        async fn create_dht_record_owned_by(&self, cols: usize, owner: &str) -> Result<String> {
+            self.inner.create_dht_record_owned_by(cols, owner).await
+        }
+
+        async fn dht_set_subkey(&self, key: &str, subkey: u32, value: &[u8]) -> Result<()> {
+            self.check_write(key, subkey, value).await?;
+            self.inner.dht_set_subkey(key, subkey, value).await
+        }
+
+        async fn dht_set_subkey_as(&self, key: &str, subkey: u32, value: &[u8], owner: &str) -> Result<()> {
+            self.check_write(key, subkey, value).await?;
+            self.inner.dht_set_subkey_as(key, subkey, value, owner).await
+        }
+
+        async fn dht_get_subkey(&self, key: &str, subkey: u32) -> Result<Option<Vec<u8>>> {
+            let value = self.inner.dht_get_subkey(key, subkey).await?;
+            // Leave the single-subkey lookup record alone
+            if value.as_ref().is_some_and(|v| v.starts_with(b"{")) {
+                return Ok(value);
+            }
+
+            let mut reads = self.reads.lock().await;
+            let count = reads.entry((key.to_string(), subkey)).or_insert(0);
+            *count += 1;
+            if *count <= self.failures_per_subkey {
+                return Err(RoseliteError::NetworkError("simulated timeout".to_string()));
+            }
+            Ok(value)
+        }
+
+        async fn dht_delete_record(&self, key: &str) -> Result<()> {
+            self.inner.dht_delete_record(key).await
+        }
+
+        async fn inspect_record(&self, key: &str) -> Result<()> {
+            self.inner.inspect_record(key).await
+        }
+
+        async fn disconnect(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn fast_retries(retries: u32) -> TransferConfig {
+        TransferConfig {
+            download_concurrency: 4,
+            upload_concurrency: 4,
+            chunk_retries: retries,
+            retry_backoff: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_download_retries_failed_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 80 * 1024).await;
+        let original_content = package.content.clone();
+
+        let flaky = FlakyDht {
+            failures_per_subkey: 2,
+            ..FlakyDht::new(MemoryDht::new())
+        };
+        let mut store = VeilidStore::with_backend(flaky).with_transfer_config(fast_retries(2));
+        let (uri, _) = store.publish(package).await.unwrap();
+
+        // Every chunk fails twice and then arrives; content is reassembled in order
+        let downloaded = store.download(&uri).await.unwrap();
+        assert_eq!(downloaded.content, original_content);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_download_gives_up_after_retries() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 16 * 1024).await;
+
+        let flaky = FlakyDht {
+            failures_per_subkey: 3,
+            ..FlakyDht::new(MemoryDht::new())
+        };
+        let mut store = VeilidStore::with_backend(flaky).with_transfer_config(fast_retries(2));
+        let (uri, _) = store.publish(package).await.unwrap();
+
+        assert!(matches!(store.download(&uri).await, Err(RoseliteError::NetworkError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_parallel_upload_retries_failed_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let package = build_test_package(&temp_dir, 80 * 1024).await;
+        let original_content = package.content.clone();
+
+        let flaky = FlakyDht {
+            write_failures_per_subkey: 2,
+            ..FlakyDht::new(MemoryDht::new())
+        };
+        let mut store = VeilidStore::with_backend(flaky).with_transfer_config(fast_retries(2));
+        let (uri, _) = store.publish(package).await.unwrap();
+        assert!(store.journal().pending().is_none());
+
+        let downloaded = store.download(&uri).await.unwrap();
+        assert_eq!(downloaded.content, original_content);
+    }
+
+    #[tokio::test]
+    async fn test_resume_interrupted_publish() {
+        let temp_dir = TempDir::new().unwrap();
+        let journal_path = temp_dir.path().join("publish-journal.json");
+        let keystore_path = temp_dir.path().join("keystore.json");
+        let package = build_test_package(&temp_dir, 1_200_000).await;
+        let original_content = package.content.clone();
+
+        // The network stops accepting writes partway through the first record
+        let dht = MemoryDht::new();
+        let flaky = FlakyDht {
+            write_budget: Some(40),
+            ..FlakyDht::new(dht.clone())
+        };
+        let mut interrupted = VeilidStore::with_backend(flaky)
+            .with_keystore(KeyStore::open(&keystore_path).unwrap())
+            .with_journal(PublishJournal::open(&journal_path).unwrap())
+            .with_transfer_config(fast_retries(1));
+        assert!(interrupted.publish(package.clone()).await.is_err());
+
+        let journal = PublishJournal::open(&journal_path).unwrap();
+        let progress = journal.pending().unwrap().clone();
+        assert_eq!(progress.records.len(), 1);
+        assert_eq!(progress.written_chunks(), 40);
+
+        // The journal only names records; their owner keys are in the keystore
+        let journal_text = std::fs::read_to_string(&journal_path).unwrap();
+        let keystore = KeyStore::open(&keystore_path).unwrap();
+        let owner = keystore.record_owner(&progress.records[0].record_key).unwrap();
+        assert!(!journal_text.contains(owner));
+
+        // A different package cannot pick up the journal
+        let mut store = VeilidStore::with_backend(dht.clone()).with_keystore(keystore).with_journal(journal);
+        let other = build_test_package(&TempDir::new().unwrap(), 1024).await;
+        assert!(matches!(store.resume(other).await, Err(RoseliteError::ValidationError(_))));
+
+        let (uri, _) = store.resume(package).await.unwrap();
+        let lookup = store.fetch_lookup_record(&uri.app_id).await.unwrap();
+        assert_eq!(lookup.versions[0].package_records[0].record_key, progress.records[0].record_key);
+
+        // No orphaned records: two package records plus the lookup record
+        assert_eq!(dht.record_count().await, 3);
+        assert!(!journal_path.exists());
+        assert!(store.keystore().record_owner(&progress.records[0].record_key).is_none());
+        assert!(matches!(store.resume(build_test_package(&temp_dir, 1024).await).await,
+            Err(RoseliteError::ValidationError(_))));
+
+        let downloaded = store.download(&uri).await.unwrap();
+        assert_eq!(downloaded.content, original_content);
+    }
+
+    #[tokio::test]
+    async fn test_get_missing_app() {
+        let store = VeilidStore::with_backend(MemoryDht::new());
+        let missing = AppId("VLD0:missing".to_string());
+        assert!(store.get_app(&missing).await.unwrap().is_none());
+        assert!(store.download(&VeilUri::new(missing, None)).await.is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use crate::crypto::CryptoManager;
+use crate::error::*;
+use crate::identity::{rotated_into, IdentityRecord};
+use crate::keys::PublicKey;
+use crate::keystore::write_private_file;
+use crate::package::SignaturePolicy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// File name of the pinned publisher keys inside the Roselite config directory, next to the keystore
+pub const TRUST_STORE_FILENAME: &str = "trust.json";
+
+/// Which publisher keys are trusted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustMode {
+    /// Trust the first key seen for an app and pin it
+    #[default]
+    Tofu,
+    /// Only trust keys pinned ahead of time
+    Pinned,
+    /// Only trust keys listed in an allowlist file
+    Allowlist,
+}
+
+impl FromStr for TrustMode {
+    type Err = RoseliteError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "tofu" => Ok(Self::Tofu),
+            "pinned" => Ok(Self::Pinned),
+            "allowlist" => Ok(Self::Allowlist),
+            other => Err(RoseliteError::ValidationError(format!(
+                "Unknown trust mode '{}', expected tofu, pinned or allowlist", other
+            ))),
+        }
+    }
+}
+
+/// What happens to a package signed by a revoked or untrusted key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UntrustedAction {
+    /// Fail verification
+    #[default]
+    Refuse,
+    /// Let the package through; the caller flags it
+    Warn,
+}
+
+/// Outcome of checking a publisher key against the trust policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrustDecision {
+    /// The key is pinned or allowlisted, or was rotated into from such a key
+    Trusted,
+    /// No key was known for the app yet; this one is now pinned
+    FirstUse,
+    /// The key is not trusted for the app, and why
+    Untrusted(String),
+    /// The publisher revoked the key, with the reason they gave
+    Revoked(Option<String>),
+}
+
+impl TrustDecision {
+    /// Whether the package can be used without flagging it
+    pub fn is_trusted(&self) -> bool {
+        matches!(self, Self::Trusted | Self::FirstUse)
+    }
+}
+
+/// Publisher pinned for an app
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pin {
+    /// Publisher key
+    pub key: PublicKey,
+    /// DHT record of the publisher's identity, the only place rotations and revocations
+    /// for the app are read from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+}
+
+/// On-disk layout of the trust store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TrustData {
+    /// Publisher pinned for each app, keyed by app key
+    #[serde(default)]
+    pins: BTreeMap<String, Pin>,
+    /// Signature policy every release of an app must meet, keyed by app key
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    policies: BTreeMap<String, SignaturePolicy>,
+}
+
+/// Decides whether a package's signing key may be trusted for the app it was published as.
+///
+/// Keys are matched against pins or an allowlist depending on the mode. Rotations and
+/// revocations learned from publisher identity records are applied on top: a key rotated
+/// into from a trusted key is trusted too, and a revoked key never is.
+#[derive(Debug, Clone, Default)]
+pub struct TrustPolicy {
+    path: Option<PathBuf>,
+    data: TrustData,
+    mode: TrustMode,
+    action: UntrustedAction,
+    allowlist: HashSet<PublicKey>,
+    /// Verified `(old, new)` key rotations
+    rotations: Vec<(PublicKey, PublicKey)>,
+    /// Revoked keys with the reason given
+    revoked: HashMap<PublicKey, Option<String>>,
+}
+
+impl TrustPolicy {
+    /// Create a policy whose pins only live in memory
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Open the trust store at `path`, starting without pins if the file does not exist yet
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let data = if path.exists() {
+            let bytes = std::fs::read(&path)?;
+            serde_json::from_slice(&bytes)?
+        } else {
+            TrustData::default()
+        };
+
+        Ok(Self {
+            path: Some(path),
+            data,
+            ..Self::default()
+        })
+    }
+
+    /// Default location of the trust store, in the Roselite config directory
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(crate::config_dir()?.join(TRUST_STORE_FILENAME))
+    }
+
+    /// Open the trust store at the default location
+    pub fn open_default() -> Result<Self> {
+        Self::open(Self::default_path()?)
+    }
+
+    /// Use the given trust mode
+    pub fn with_mode(mut self, mode: TrustMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Use the given action for revoked and untrusted keys
+    pub fn with_action(mut self, action: UntrustedAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Add the keys listed in an allowlist file: one public key per line, `#` starts a comment
+    pub fn load_allowlist<P: AsRef<Path>>(&mut self, path: P) -> Result<usize> {
+        let contents = std::fs::read_to_string(path)?;
+        let before = self.allowlist.len();
+        for line in contents.lines() {
+            let key = line.split('#').next().unwrap_or_default().trim();
+            if !key.is_empty() {
+                self.allowlist.insert(key.parse()?);
+            }
+        }
+        Ok(self.allowlist.len() - before)
+    }
+
+    /// The trust mode in use
+    pub fn mode(&self) -> TrustMode {
+        self.mode
+    }
+
+    /// Key pinned for an app, if any
+    pub fn pinned(&self, subject: &str) -> Option<&PublicKey> {
+        self.data.pins.get(subject).map(|pin| &pin.key)
+    }
+
+    /// Identity record pinned with the publisher of an app, if any
+    pub fn pinned_identity(&self, subject: &str) -> Option<&str> {
+        self.data.pins.get(subject)?.identity.as_deref()
+    }
+
+    /// Pin the publisher key of an app, and the identity record its rotations and
+    /// revocations are to be read from
+    pub fn pin(&mut self, subject: &str, key: &PublicKey, identity: Option<&str>) -> Result<()> {
+        let pin = Pin {
+            key: *key,
+            identity: identity.map(str::to_string),
+        };
+        if self.data.pins.get(subject) == Some(&pin) {
+            return Ok(());
+        }
+        self.data.pins.insert(subject.to_string(), pin);
+        self.save()
+    }
+
+    /// Signature policy pinned for an app, if any
+    pub fn pinned_policy(&self, subject: &str) -> Option<&SignaturePolicy> {
+        self.data.policies.get(subject)
+    }
+
+    /// Pin the signature policy every later release of an app must meet
+    pub fn pin_policy(&mut self, subject: &str, policy: &SignaturePolicy) -> Result<()> {
+        if self.data.policies.get(subject) == Some(policy) {
+            return Ok(());
+        }
+        self.data.policies.insert(subject.to_string(), policy.clone());
+        self.save()
+    }
+
+    /// Check the signature policy a release of `subject` declares against the one pinned for
+    /// it, so a single maintainer cannot drop or weaken the policy in a release they sign
+    /// alone. In TOFU mode the first policy seen is pinned.
+    pub fn check_policy(&mut self, subject: &str, policy: Option<&SignaturePolicy>) -> Result<TrustDecision> {
+        let Some(pinned) = self.pinned_policy(subject) else {
+            if let (Some(policy), TrustMode::Tofu) = (policy, self.mode) {
+                self.pin_policy(subject, policy)?;
+                return Ok(TrustDecision::FirstUse);
+            }
+            return Ok(TrustDecision::Trusted);
+        };
+
+        Ok(match policy {
+            Some(policy) if policy.is_as_strict_as(pinned) => TrustDecision::Trusted,
+            Some(policy) => TrustDecision::Untrusted(format!(
+                "release needs {} of {} keys, but {} of {} is pinned for {}",
+                policy.threshold, policy.keys.len(), pinned.threshold, pinned.keys.len(), subject
+            )),
+            None => TrustDecision::Untrusted(format!(
+                "release has no signature policy, but {} of {} keys is pinned for {}",
+                pinned.threshold, pinned.keys.len(), subject
+            )),
+        })
+    }
+
+    /// Learn the verified rotations and revocations a publisher has published
+    pub fn add_identity_record(&mut self, record: &IdentityRecord, crypto: &CryptoManager) {
+        for endorsement in record.rotations(crypto) {
+            let rotation = (endorsement.old_key, endorsement.new_key);
+            if !self.rotations.contains(&rotation) {
+                self.rotations.push(rotation);
+            }
+        }
+        for revocation in record.revocations(crypto) {
+            self.revoked.insert(revocation.revoked_key, revocation.reason.clone());
+        }
+    }
+
+    /// Check `key` as the publisher key of `subject`, pinning it on first use in TOFU mode
+    pub fn check(&mut self, subject: &str, key: &PublicKey) -> Result<TrustDecision> {
+        if let Some(reason) = self.revoked.get(key) {
+            return Ok(TrustDecision::Revoked(reason.clone()));
+        }
+
+        if self.mode == TrustMode::Allowlist {
+            let allowed = self.allowlist.iter().any(|allowed| rotated_into(&self.rotations, allowed, key));
+            return Ok(if allowed {
+                TrustDecision::Trusted
+            } else {
+                TrustDecision::Untrusted(format!("key {} is not on the allowlist", key))
+            });
+        }
+
+        let Some(pinned) = self.pinned(subject).copied() else {
+            if self.mode == TrustMode::Pinned {
+                return Ok(TrustDecision::Untrusted(format!("no key is pinned for {}", subject)));
+            }
+            self.pin(subject, key, None)?;
+            return Ok(TrustDecision::FirstUse);
+        };
+
+        if !rotated_into(&self.rotations, &pinned, key) {
+            return Ok(TrustDecision::Untrusted(format!(
+                "signed by {}, but {} is pinned for {}", key, pinned, subject
+            )));
+        }
+        // Follow the publisher's rotation so the old key is no longer needed for the chain
+        if self.mode == TrustMode::Tofu {
+            let identity = self.pinned_identity(subject).map(str::to_string);
+            self.pin(subject, key, identity.as_deref())?;
+        }
+        Ok(TrustDecision::Trusted)
+    }
+
+    /// Turn a revoked or untrusted decision into an error when the policy refuses such keys
+    pub fn enforce(&self, decision: TrustDecision) -> Result<TrustDecision> {
+        if self.action == UntrustedAction::Warn {
+            return Ok(decision);
+        }
+        match decision {
+            TrustDecision::Revoked(reason) => Err(CryptoError::KeyRevoked(
+                reason.unwrap_or_else(|| "no reason given".to_string())
+            ).into()),
+            TrustDecision::Untrusted(reason) => Err(CryptoError::UntrustedKey(reason).into()),
+            decision => Ok(decision),
+        }
+    }
+
+    /// Persist the pins if the policy is backed by a file
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        write_private_file(path, &serde_json::to_vec_pretty(&self.data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::{Identity, KeyStatement};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_tofu_pins_and_follows_rotation() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("trust.json");
+        let crypto = CryptoManager::new().unwrap();
+        let identity = Identity::generate("work").unwrap();
+        let (rotated, endorsement) = identity.rotated().unwrap();
+
+        let mut policy = TrustPolicy::open(&path).unwrap();
+        assert_eq!(policy.check("app", &identity.public_key).unwrap(), TrustDecision::FirstUse);
+        assert_eq!(policy.check("app", &identity.public_key).unwrap(), TrustDecision::Trusted);
+        policy.pin("app", &identity.public_key, Some("VLD0:identity")).unwrap();
+
+        // A different key is refused until the publisher endorses it
+        let mut reopened = TrustPolicy::open(&path).unwrap();
+        assert_eq!(reopened.pinned("app"), Some(&identity.public_key));
+        let decision = reopened.check("app", &rotated.public_key).unwrap();
+        assert!(matches!(decision, TrustDecision::Untrusted(_)));
+        assert!(matches!(
+            reopened.enforce(decision),
+            Err(RoseliteError::Crypto(CryptoError::UntrustedKey(_)))
+        ));
+
+        let record = IdentityRecord {
+            statements: vec![KeyStatement::Rotation(endorsement)],
+            ..IdentityRecord::default()
+        };
+        reopened.add_identity_record(&record, &crypto);
+        assert_eq!(reopened.check("app", &rotated.public_key).unwrap(), TrustDecision::Trusted);
+        assert_eq!(TrustPolicy::open(&path).unwrap().pinned("app"), Some(&rotated.public_key));
+        assert_eq!(TrustPolicy::open(&path).unwrap().pinned_identity("app"), Some("VLD0:identity"));
+
+        // Pins match a key however it is written
+        assert_eq!(reopened.check("app", &rotated.public_key.to_typed()).unwrap(), TrustDecision::Trusted);
+    }
+
+    #[test]
+    fn test_revoked_keys_are_refused_or_flagged() {
+        let crypto = CryptoManager::new().unwrap();
+        let identity = Identity::generate("work").unwrap();
+        let revocation = identity.revocation(None, Some("compromised".to_string())).unwrap();
+        let record = IdentityRecord {
+            statements: vec![KeyStatement::Revocation(revocation)],
+            ..IdentityRecord::default()
+        };
+
+        let mut policy = TrustPolicy::in_memory();
+        policy.pin("app", &identity.public_key, None).unwrap();
+        policy.add_identity_record(&record, &crypto);
+        let decision = policy.check("app", &identity.public_key).unwrap();
+        assert_eq!(decision, TrustDecision::Revoked(Some("compromised".to_string())));
+        assert!(matches!(
+            policy.enforce(decision.clone()),
+            Err(RoseliteError::Crypto(CryptoError::KeyRevoked(_)))
+        ));
+
+        let policy = policy.with_action(UntrustedAction::Warn);
+        assert!(!policy.enforce(decision).unwrap().is_trusted());
+    }
+
+    #[test]
+    fn test_signature_policy_cannot_be_weakened() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("trust.json");
+        let keys: Vec<_> = (0..3).map(|i| Identity::generate(&format!("maintainer-{}", i)).unwrap().public_key).collect();
+        let two_of_three = SignaturePolicy { threshold: 2, keys: keys.clone() };
+
+        let mut policy = TrustPolicy::open(&path).unwrap();
+        assert_eq!(policy.check_policy("app", None).unwrap(), TrustDecision::Trusted);
+        assert_eq!(policy.check_policy("app", Some(&two_of_three)).unwrap(), TrustDecision::FirstUse);
+
+        // Later releases may tighten the pinned policy, never drop or loosen it
+        let mut reopened = TrustPolicy::open(&path).unwrap();
+        assert_eq!(reopened.pinned_policy("app"), Some(&two_of_three));
+        assert_eq!(reopened.check_policy("app", Some(&two_of_three)).unwrap(), TrustDecision::Trusted);
+        let stricter = SignaturePolicy { threshold: 2, keys: keys[..2].to_vec() };
+        assert_eq!(reopened.check_policy("app", Some(&stricter)).unwrap(), TrustDecision::Trusted);
+
+        let lower = SignaturePolicy { threshold: 1, keys: keys.clone() };
+        let outsider = Identity::generate("outsider").unwrap().public_key;
+        let other_keys = SignaturePolicy { threshold: 2, keys: vec![keys[0], outsider] };
+        for weaker in [None, Some(&lower), Some(&other_keys)] {
+            assert!(matches!(reopened.check_policy("app", weaker).unwrap(), TrustDecision::Untrusted(_)));
+        }
+        assert_eq!(reopened.pinned_policy("app"), Some(&two_of_three));
+
+        // Outside TOFU only an explicitly pinned policy is enforced
+        let mut pinned = TrustPolicy::in_memory().with_mode(TrustMode::Pinned);
+        assert_eq!(pinned.check_policy("app", Some(&lower)).unwrap(), TrustDecision::Trusted);
+        assert_eq!(pinned.pinned_policy("app"), None);
+        pinned.pin_policy("app", &two_of_three).unwrap();
+        assert!(matches!(pinned.check_policy("app", Some(&lower)).unwrap(), TrustDecision::Untrusted(_)));
+    }
+
+    #[test]
+    fn test_pinned_and_allowlist_modes() {
+        let temp_dir = TempDir::new().unwrap();
+        let trusted = Identity::generate("trusted").unwrap();
+        let other = Identity::generate("other").unwrap();
+
+        let mut pinned = TrustPolicy::in_memory().with_mode(TrustMode::Pinned);
+        assert!(matches!(pinned.check("app", &trusted.public_key).unwrap(), TrustDecision::Untrusted(_)));
+        assert_eq!(pinned.pinned("app"), None);
+        pinned.pin("app", &trusted.public_key, Some("VLD0:identity")).unwrap();
+        assert_eq!(pinned.pinned_identity("app"), Some("VLD0:identity"));
+        assert_eq!(pinned.check("app", &trusted.public_key).unwrap(), TrustDecision::Trusted);
+
+        let allowlist_path = temp_dir.path().join("allowlist");
+        std::fs::write(&allowlist_path, format!("# release keys\n{}  # ci\n\n", trusted.public_key)).unwrap();
+        let mut allowlist = TrustPolicy::in_memory().with_mode("allowlist".parse().unwrap());
+        assert_eq!(allowlist.load_allowlist(&allowlist_path).unwrap(), 1);
+        assert_eq!(allowlist.check("any-app", &trusted.public_key).unwrap(), TrustDecision::Trusted);
+        assert!(matches!(allowlist.check("any-app", &other.public_key).unwrap(), TrustDecision::Untrusted(_)));
+        assert!("sometimes".parse::<TrustMode>().is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use std::fmt;
+use std::str::FromStr;
+use crate::error::RoseliteError;
 
 /// Unique identifier for a Veilid app package
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -24,6 +26,9 @@ pub struct VeilUri {
     pub scheme: String,
     pub app_id: AppId,
     pub version: Option<String>,
+    /// Content key of a private site, carried in the URI fragment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
 }
 
 impl VeilUri {
@@ -32,15 +37,64 @@ impl VeilUri {
             scheme: "veil".to_string(),
             app_id,
             version,
+            key: None,
         }
     }
+
+    /// Attach the content key of a private site
+    pub fn with_key(mut self, key: Option<String>) -> Self {
+        self.key = key;
+        self
+    }
+}
+
+impl FromStr for VeilUri {
+    type Err = RoseliteError;
+
+    /// Parse `veil:///app/<key>` or `veil:///app/<key>/<version>`, optionally followed
+    /// by a `#key=<content key>` fragment for private sites
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (s, fragment) = match s.split_once('#') {
+            Some((uri, fragment)) => (uri, Some(fragment)),
+            None => (s, None),
+        };
+        let key = match fragment {
+            Some(fragment) => match fragment.strip_prefix("key=") {
+                Some(key) if !key.is_empty() => Some(key.to_string()),
+                _ => return Err(RoseliteError::InvalidUri(format!("{}#{}", s, fragment))),
+            },
+            None => None,
+        };
+
+        let rest = s.strip_prefix("veil:///app/")
+            .ok_or_else(|| RoseliteError::InvalidUri(s.to_string()))?;
+
+        let mut parts = rest.trim_end_matches('/').splitn(2, '/');
+        let app_id = match parts.next() {
+            Some(key) if !key.is_empty() => AppId(key.to_string()),
+            _ => return Err(RoseliteError::InvalidUri(s.to_string())),
+        };
+        let version = match parts.next() {
+            Some(version) if version.is_empty() || version.contains('/') => {
+                return Err(RoseliteError::InvalidUri(s.to_string()));
+            }
+            Some(version) => Some(version.to_string()),
+            None => None,
+        };
+
+        Ok(Self::new(app_id, version).with_key(key))
+    }
 }
 
 impl fmt::Display for VeilUri {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.version {
-            Some(version) => write!(f, "{}:///app/{}/{}", self.scheme, self.app_id, version),
-            None => write!(f, "{}:///app/{}", self.scheme, self.app_id),
+            Some(version) => write!(f, "{}:///app/{}/{}", self.scheme, self.app_id, version)?,
+            None => write!(f, "{}:///app/{}", self.scheme, self.app_id)?,
+        }
+        match &self.key {
+            Some(key) => write!(f, "#key={}", key),
+            None => Ok(()),
         }
     }
 }
@@ -83,4 +137,39 @@ impl AppInfo {
     pub fn access_url_latest(&self) -> String {
         format!("https://www.roselite.app/access/{}", self.id.0)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_veil_uri_round_trip() {
+        let latest: VeilUri = "veil:///app/VLD0:abc123".parse().unwrap();
+        assert_eq!(latest.app_id, AppId("VLD0:abc123".to_string()));
+        assert_eq!(latest.version, None);
+        assert_eq!(latest.to_string(), "veil:///app/VLD0:abc123");
+
+        let pinned: VeilUri = "veil:///app/VLD0:abc123/1.2.0".parse().unwrap();
+        assert_eq!(pinned.version.as_deref(), Some("1.2.0"));
+        assert_eq!(pinned.to_string(), "veil:///app/VLD0:abc123/1.2.0");
+
+        assert!("https://example.com".parse::<VeilUri>().is_err());
+        assert!("veil:///app/".parse::<VeilUri>().is_err());
+        assert!("veil:///app/VLD0:abc123/1.0/extra".parse::<VeilUri>().is_err());
+    }
+
+    #[test]
+    fn test_veil_uri_fragment_key() {
+        let private: VeilUri = "veil:///app/VLD0:abc123/1.2.0#key=00ff".parse().unwrap();
+        assert_eq!(private.version.as_deref(), Some("1.2.0"));
+        assert_eq!(private.key.as_deref(), Some("00ff"));
+        assert_eq!(private.to_string(), "veil:///app/VLD0:abc123/1.2.0#key=00ff");
+
+        let latest: VeilUri = "veil:///app/VLD0:abc123#key=00ff".parse().unwrap();
+        assert_eq!(latest.version, None);
+        assert_eq!(latest.key.as_deref(), Some("00ff"));
+
+        assert!("veil:///app/VLD0:abc123#other=1".parse::<VeilUri>().is_err());
+        assert!("veil:///app/VLD0:abc123#key=".parse::<VeilUri>().is_err());
+    }
+}
@@ -1,12 +1,15 @@
 use crate::error::*;
+use crate::dht::DhtBackend;
+use std::collections::HashSet;
 use std::sync::Arc;
-use std::collections::HashMap;
 use tracing;
+use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
 use tokio::sync::RwLock;
 // Base64 may be used elsewhere; import if necessary (currently unused)
 use crate::crypto::CryptoManager;
-use veilid_core::{TypedKey};
+use crate::keys::{PublicKey, SecretKey};
+use veilid_core::{KeyPair, TypedKey};
 use std::str::FromStr;
 // DHT types
 use veilid_core::{DHTSchema, DHTReportScope, ValueSubkey};
@@ -21,12 +24,14 @@ pub struct VeilidConnection {
     api: Option<Arc<veilid_core::VeilidAPI>>,
     /// Connection state
     state: Arc<RwLock<ConnectionState>>,
-    /// Fallback in-memory storage for development/testing
-    storage: Arc<RwLock<HashMap<String, Vec<u8>>>>,
     /// Configuration for network behavior
     config: VeilidConfig,
     /// Routing context for peer-to-peer operations
     routing_context: Option<veilid_core::RoutingContext>,
+    /// Password the protected store encrypts the device key under
+    device_password: Option<String>,
+    /// Records opened with an owner keypair as writer, closed on disconnect
+    writable_records: Arc<RwLock<HashSet<String>>>,
 }
 
 /// Connection state information
@@ -150,12 +155,18 @@ impl VeilidConnection {
         Ok(Self {
             api: None,
             state: Arc::new(RwLock::new(ConnectionState::default())),
-            storage: Arc::new(RwLock::new(HashMap::new())),
             config,
             routing_context: None,
+            device_password: None,
+            writable_records: Arc::new(RwLock::new(HashSet::new())),
         })
     }
 
+    /// Encrypt the protected store's device key under `password`
+    pub fn set_device_password(&mut self, password: String) {
+        self.device_password = Some(password);
+    }
+
     /// Connect to the Veilid network with full initialization
     pub async fn connect(&mut self) -> Result<()> {
         tracing::info!("Initializing Veilid connection...");
@@ -207,6 +218,18 @@ impl VeilidConnection {
     /// Disconnect from the Veilid network
     pub async fn disconnect(&mut self) -> Result<()> {
         tracing::info!("Disconnecting from Veilid network...");
+
+        // Close records held open for writing before the routing context goes away
+        let writable: Vec<String> = self.writable_records.write().await.drain().collect();
+        if let Some(routing_ctx) = &self.routing_context {
+            for key_str in writable {
+                if let Ok(typed_key) = TypedKey::from_str(&key_str) {
+                    if let Err(e) = routing_ctx.close_dht_record(typed_key).await {
+                        tracing::warn!("Failed to close DHT record {}: {:?}", key_str, e);
+                    }
+                }
+            }
+        }
         
         if let Some(api) = self.api.take() {
             // Detach from network gracefully
@@ -230,9 +253,6 @@ impl VeilidConnection {
         // Clean up routing context
         self.routing_context = None;
         
-        // Clear storage
-        self.storage.write().await.clear();
-        
         // Reset state
         {
             let mut state = self.state.write().await;
@@ -258,6 +278,15 @@ impl VeilidConnection {
 
     /// Retrieve bytes from a DHT record subkey
     pub async fn dht_get_subkey(&self, key_str: &str, subkey: ValueSubkey) -> Result<Option<Vec<u8>>> {
+        self.get_subkey(key_str, subkey, false).await
+    }
+
+    /// Retrieve bytes from a DHT record subkey, forcing a refresh from the network
+    pub async fn dht_refresh_subkey(&self, key_str: &str, subkey: ValueSubkey) -> Result<Option<Vec<u8>>> {
+        self.get_subkey(key_str, subkey, true).await
+    }
+
+    async fn get_subkey(&self, key_str: &str, subkey: ValueSubkey, force_refresh: bool) -> Result<Option<Vec<u8>>> {
         self.wait_until_attached().await?;
         let routing_ctx = self.routing_context.as_ref()
             .ok_or_else(|| RoseliteError::Veilid(VeilidError::ConnectionFailed))?;
@@ -265,7 +294,7 @@ impl VeilidConnection {
             .map_err(|_| RoseliteError::InvalidUri(format!("Invalid DHT key: {}", key_str)))?;
         
         // Try to get the value first, if it fails with "record not open", try opening it
-        match routing_ctx.get_dht_value(typed_key, subkey, false).await {
+        match routing_ctx.get_dht_value(typed_key, subkey, force_refresh).await {
             Ok(resp) => Ok(resp.map(|v| v.data().to_vec())),
             Err(e) => {
                 // Check if the error is due to record not being open
@@ -273,7 +302,7 @@ impl VeilidConnection {
                     tracing::debug!("Record not open, attempting to open: {}", key_str);
                     // Try to open the record and then get the value
                     self.open_dht_record(key_str).await?;
-                    let resp = routing_ctx.get_dht_value(typed_key, subkey, false)
+                    let resp = routing_ctx.get_dht_value(typed_key, subkey, force_refresh)
                         .await
                         .map_err(|e| RoseliteError::Veilid(VeilidError::DhtOperationFailed { 
                             operation: format!("get_dht_value failed after opening: {:?}", e) 
@@ -313,6 +342,7 @@ impl VeilidConnection {
             .ok_or_else(|| RoseliteError::Veilid(VeilidError::ConnectionFailed))?;
         let typed_key = TypedKey::from_str(key_str)
             .map_err(|_| RoseliteError::InvalidUri(format!("Invalid DHT key: {}", key_str)))?;
+        self.writable_records.write().await.remove(key_str);
         routing_ctx.delete_dht_record(typed_key)
             .await
             .map_err(|e| RoseliteError::Veilid(VeilidError::DhtOperationFailed { operation: format!("delete_dht_record failed: {:?}", e) }))?;
@@ -339,18 +369,67 @@ impl VeilidConnection {
 
     /// Create DHT record with custom column count.
     pub async fn create_dht_record_with_cols(&self, cols: usize) -> Result<String> {
+        Ok(self.create_owned_dht_record(cols).await?.0)
+    }
+
+    /// Create DHT record with custom column count, returning the key and the owner keypair.
+    pub async fn create_owned_dht_record(&self, cols: usize) -> Result<(String, String)> {
         self.wait_until_attached().await?;
         let routing_ctx = self.routing_context.as_ref()
             .ok_or_else(|| RoseliteError::Veilid(VeilidError::ConnectionFailed))?;
-        let cols_u16: u16 = cols.try_into().map_err(|_| RoseliteError::InvalidUri(format!("Too many columns: {}", cols)))?;
-        let schema = DHTSchema::dflt(cols_u16)
-            .map_err(|e| RoseliteError::Veilid(VeilidError::DhtOperationFailed { operation: format!("schema build failed: {:?}", e) }))?;
-        let desc = routing_ctx.create_dht_record(schema, None, None)
+        let desc = routing_ctx.create_dht_record(Self::dflt_schema(cols)?, None, None)
+            .await
+            .map_err(|e| RoseliteError::Veilid(VeilidError::DhtOperationFailed { operation: format!("create_dht_record failed: {:?}", e) }))?;
+        let owner_secret = desc.owner_secret()
+            .ok_or_else(|| RoseliteError::Veilid(VeilidError::DhtOperationFailed { operation: "create_dht_record returned no owner secret".to_string() }))?;
+        let owner = KeyPair::new(*desc.owner(), *owner_secret);
+        Ok((desc.key().to_string(), owner.to_string()))
+    }
+
+    /// Create DHT record with custom column count owned by the given `public:secret` keypair.
+    pub async fn create_dht_record_owned_by(&self, cols: usize, owner: &str) -> Result<String> {
+        self.wait_until_attached().await?;
+        let routing_ctx = self.routing_context.as_ref()
+            .ok_or(RoseliteError::Veilid(VeilidError::ConnectionFailed))?;
+        let owner = KeyPair::from_str(owner.strip_prefix(crate::keys::TYPED_PREFIX).unwrap_or(owner))
+            .map_err(|_| RoseliteError::Crypto(CryptoError::InvalidKeyFormat))?;
+        let desc = routing_ctx.create_dht_record(Self::dflt_schema(cols)?, Some(owner), None)
             .await
             .map_err(|e| RoseliteError::Veilid(VeilidError::DhtOperationFailed { operation: format!("create_dht_record failed: {:?}", e) }))?;
         Ok(desc.key().to_string())
     }
 
+    /// Default schema with `cols` subkeys for a single owner
+    fn dflt_schema(cols: usize) -> Result<DHTSchema> {
+        let cols_u16: u16 = cols.try_into().map_err(|_| RoseliteError::InvalidUri(format!("Too many columns: {}", cols)))?;
+        DHTSchema::dflt(cols_u16)
+            .map_err(|e| RoseliteError::Veilid(VeilidError::DhtOperationFailed { operation: format!("schema build failed: {:?}", e) }))
+    }
+
+    /// Store raw bytes in a DHT record subkey, opening the record with the owner keypair as writer.
+    pub async fn dht_set_subkey_as(&self, key_str: &str, subkey: ValueSubkey, value: &[u8], owner: &str) -> Result<()> {
+        self.wait_until_attached().await?;
+        let routing_ctx = self.routing_context.as_ref()
+            .ok_or(RoseliteError::Veilid(VeilidError::ConnectionFailed))?;
+        let typed_key = TypedKey::from_str(key_str)
+            .map_err(|_| RoseliteError::InvalidUri(format!("Invalid DHT key: {}", key_str)))?;
+        let writer = KeyPair::from_str(owner)
+            .map_err(|_| RoseliteError::Crypto(CryptoError::InvalidKeyFormat))?;
+
+        // Open each record for writing once; it stays open until disconnect or delete
+        if !self.writable_records.read().await.contains(key_str) {
+            let descriptor = routing_ctx.open_dht_record(typed_key, Some(writer))
+                .await
+                .map_err(|e| RoseliteError::Veilid(VeilidError::DhtOperationFailed { operation: format!("open_dht_record failed: {:?}", e) }))?;
+            tracing::debug!("Opened DHT record {} for writing (owner {})", descriptor.key(), descriptor.owner());
+            self.writable_records.write().await.insert(key_str.to_string());
+        }
+        routing_ctx.set_dht_value(typed_key, subkey, value.to_vec(), Some(writer))
+            .await
+            .map_err(|e| RoseliteError::Veilid(VeilidError::DhtOperationFailed { operation: format!("set_dht_value failed: {:?}", e) }))?;
+        Ok(())
+    }
+
     /// Inspect a record to gauge replication consensus.
     pub async fn inspect_record(&self, key_str: &str) -> Result<()> {
         self.wait_until_attached().await?;
@@ -377,20 +456,23 @@ impl VeilidConnection {
         cfg["program_name"] = JsonValue::String("roselite".to_string());
         cfg["namespace"] = JsonValue::String("dev".to_string());
 
-        // protected store tweaks
-        let password = std::env::var("ROSELITE_PASSWORD").unwrap_or_default();
-        let insecure = password.is_empty();
+        // protected store: encrypted under the device password, or in the clear only when
+        // storage encryption has been turned off explicitly
+        let password = match (&self.device_password, self.config.storage.enable_encryption) {
+            (Some(password), _) => Some(password.clone()),
+            (None, false) => None,
+            (None, true) => return Err(RoseliteError::ValidationError(
+                "No device key password: unlock the keystore or turn storage encryption off".to_string(),
+            )),
+        };
+        let insecure = password.is_none();
         if let Some(ps) = cfg.get_mut("protected_store") {
             ps["allow_insecure_fallback"] = JsonValue::Bool(insecure);
             ps["always_use_insecure_storage"] = JsonValue::Bool(insecure);
             ps["directory"] = JsonValue::String(data_dir.into());
 
-            if insecure {
-                // Use a dummy password to avoid OS keyring errors while still storing unencrypted.
-                ps["device_encryption_key_password"] = JsonValue::String("roselite-dev".into());
-            } else {
-                ps["device_encryption_key_password"] = JsonValue::String(password.clone());
-            }
+            // Insecure storage still wants a password to avoid OS keyring errors
+            ps["device_encryption_key_password"] = JsonValue::String(password.unwrap_or_else(|| "roselite-dev".into()));
 
             // Only include new password field when changing passwords in secure mode
             ps["new_device_encryption_key_password"] = JsonValue::Null;
@@ -406,11 +488,19 @@ impl VeilidConnection {
     }
 
     /// Generate a new cryptographic key pair using our crypto manager
-    pub async fn generate_keypair(&self) -> Result<(String, String)> {
+    pub async fn generate_keypair(&self) -> Result<(PublicKey, SecretKey)> {
         let crypto = CryptoManager::new()?;
         crypto.generate_keypair()
     }
 
+    /// Crypto manager backed by this node's crypto system once connected
+    pub fn crypto(&self) -> Result<CryptoManager> {
+        match &self.api {
+            Some(api) => CryptoManager::with_veilid(api.clone()),
+            None => CryptoManager::new(),
+        }
+    }
+
     /// Check if connected to Veilid network
     pub async fn is_connected(&self) -> bool {
         self.state.read().await.is_connected
@@ -649,6 +739,49 @@ impl VeilidConnection {
     }
 }
 
+#[async_trait]
+impl DhtBackend for VeilidConnection {
+    async fn create_owned_dht_record(&self, cols: usize) -> Result<(String, String)> {
+        VeilidConnection::create_owned_dht_record(self, cols).await
+    }
+
+    async fn create_dht_record_owned_by(&self, cols: usize, owner: &str) -> Result<String> {
+        VeilidConnection::create_dht_record_owned_by(self, cols, owner).await
+    }
+
+    async fn dht_set_subkey(&self, key: &str, subkey: ValueSubkey, value: &[u8]) -> Result<()> {
+        VeilidConnection::dht_set_subkey(self, key, subkey, value).await
+    }
+
+    async fn dht_set_subkey_as(&self, key: &str, subkey: ValueSubkey, value: &[u8], owner: &str) -> Result<()> {
+        VeilidConnection::dht_set_subkey_as(self, key, subkey, value, owner).await
+    }
+
+    async fn dht_get_subkey(&self, key: &str, subkey: ValueSubkey) -> Result<Option<Vec<u8>>> {
+        VeilidConnection::dht_get_subkey(self, key, subkey).await
+    }
+
+    async fn dht_refresh_subkey(&self, key: &str, subkey: ValueSubkey) -> Result<Option<Vec<u8>>> {
+        VeilidConnection::dht_refresh_subkey(self, key, subkey).await
+    }
+
+    async fn dht_delete_record(&self, key: &str) -> Result<()> {
+        VeilidConnection::dht_delete_record(self, key).await
+    }
+
+    async fn inspect_record(&self, key: &str) -> Result<()> {
+        VeilidConnection::inspect_record(self, key).await
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        VeilidConnection::disconnect(self).await
+    }
+
+    fn crypto(&self) -> Result<CryptoManager> {
+        VeilidConnection::crypto(self)
+    }
+}
+
 /// Detailed network state information
 #[derive(Debug, Clone)]
 pub struct NetworkStateInfo {
@@ -693,8 +826,12 @@ mod tests {
     use super::*;
 
     #[tokio::test]
+    #[ignore = "needs a live Veilid network; run with --ignored"]
     async fn test_veilid_connection_basic_ops() {
-        let mut conn = VeilidConnection::new().await.unwrap();
+        // No keystore here, so the protected store has to be unencrypted
+        let mut config = VeilidConfig::default();
+        config.storage.enable_encryption = false;
+        let mut conn = VeilidConnection::new_with_config(config).await.unwrap();
         
         // Connect should succeed (or return an explicit error)
         conn.connect().await.unwrap();
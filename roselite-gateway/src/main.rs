@@ -1,7 +1,14 @@
 use roselite_core::{
-    store::{AppStore, VeilidStore},
+    dht::DhtBackend,
+    crypto::CryptoManager,
+    error::CryptoError,
+    keys::PublicKey,
+    package::{Package, PackageManifest, SignaturePolicy},
+    store::{AppStore, PackageDownload, TransferConfig, VeilidStore},
+    trust::{TrustDecision, TrustMode, TrustPolicy, UntrustedAction},
     types::{AppId, VeilUri}, 
-    package::Package,
+    veilid::{VeilidConfig, VeilidConnection},
+    KeyStore,
 };
 use axum::{
     extract::{Host, Path, State},
@@ -25,6 +32,7 @@ use std::{
 };
 use clap::Parser;
 use anyhow::Result;
+use futures::StreamExt;
 // Add DNS resolver
 use hickory_resolver::{TokioAsyncResolver, config::{ResolverConfig, ResolverOpts}};
 
@@ -60,6 +68,45 @@ struct Args {
     #[arg(long, default_value = ".cache")]
     cache_dir: String,
 
+    /// Maximum number of DHT chunks fetched concurrently when downloading an app
+    #[arg(long, default_value = "8")]
+    download_concurrency: usize,
+
+    /// Key of a private site this gateway may decrypt and serve, as `<APP_KEY>=<SITE_KEY>` (repeatable).
+    /// Browsers never send the `#key=` part of a share link, so the key has to be configured here.
+    #[arg(long = "site-key", value_name = "APP_KEY=SITE_KEY")]
+    site_keys: Vec<String>,
+
+    /// Which publisher keys to trust: `tofu` pins the first key seen for each app,
+    /// `pinned` only accepts keys given with --pin, `allowlist` only keys in --allowlist
+    #[arg(long, default_value = "tofu")]
+    trust_mode: TrustMode,
+
+    /// File the publisher keys pinned for each app are kept in (defaults to trust.json in
+    /// the Roselite config directory)
+    #[arg(long)]
+    trust_store: Option<PathBuf>,
+
+    /// Pin the publisher key of an app, as `<APP_KEY>=<PUBLIC_KEY>` or
+    /// `<APP_KEY>=<PUBLIC_KEY>@<IDENTITY_KEY>` to also pin the identity record its key
+    /// rotations and revocations are read from (repeatable)
+    #[arg(long = "pin", value_name = "APP_KEY=PUBLIC_KEY[@IDENTITY_KEY]")]
+    pins: Vec<String>,
+
+    /// Pin the signature policy every release of an app must meet, as
+    /// `<APP_KEY>=<THRESHOLD>:<PUBLIC_KEY>,<PUBLIC_KEY>,...` (repeatable). In TOFU mode the
+    /// first policy seen for an app is pinned
+    #[arg(long = "pin-policy", value_name = "APP_KEY=THRESHOLD:KEYS")]
+    policy_pins: Vec<String>,
+
+    /// File of trusted publisher keys, one per line, for --trust-mode allowlist
+    #[arg(long, value_name = "FILE")]
+    allowlist: Option<PathBuf>,
+
+    /// Serve sites signed by revoked or untrusted keys with a warning header instead of refusing them
+    #[arg(long)]
+    warn_untrusted: bool,
+
     /// Automatically start rust-rpxy in front of the HTTP service (provides automatic HTTPS)
     #[arg(long)]
     proxy: bool,
@@ -74,38 +121,57 @@ struct Args {
 }
 
 /// Shared application state
-#[derive(Clone)]
-struct AppState {
-    store: Arc<tokio::sync::Mutex<VeilidStore>>,
+struct AppState<D: DhtBackend = VeilidConnection> {
+    store: Arc<tokio::sync::Mutex<VeilidStore<D>>>,
+    trust: Arc<tokio::sync::Mutex<TrustPolicy>>,
     cache_dir: PathBuf,
     domain: String,
 }
 
+// Manual impl so the backend itself does not need to be `Clone`
+impl<D: DhtBackend> Clone for AppState<D> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            trust: self.trust.clone(),
+            cache_dir: self.cache_dir.clone(),
+            domain: self.domain.clone(),
+        }
+    }
+}
+
 /// Cached app information
 #[derive(Clone, Debug)]
 struct CachedApp {
-    package: Package,
+    version: String,
     extract_path: PathBuf,
     last_accessed: std::time::Instant,
+    /// Why the publisher key was flagged, when untrusted sites are served with a warning
+    trust_flag: Option<&'static str>,
 }
 
 type AppCache = Arc<tokio::sync::RwLock<HashMap<String, CachedApp>>>;
 
-/// Resolve Veilid DHT key for a domain via DNS TXT record `veilid-app=<KEY>`
-async fn lookup_dht_key(domain: &str) -> Option<String> {
+/// Resolve Veilid DHT key for a domain via DNS TXT record `veilid-app=<KEY>`.
+/// An optional `veilid-version=<VERSION>` record pins the site to a published version.
+async fn lookup_dht_key(domain: &str) -> Option<(String, Option<String>)> {
     let resolver = TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default());
-    if let Ok(response) = resolver.txt_lookup(domain).await {
-        for txt in response.iter() {
-            for data in txt.txt_data() {
-                if let Ok(text) = std::str::from_utf8(data) {
-                    if let Some(rest) = text.strip_prefix("veilid-app=") {
-                        return Some(rest.to_string());
-                    }
+    let response = resolver.txt_lookup(domain).await.ok()?;
+
+    let mut key = None;
+    let mut version = None;
+    for txt in response.iter() {
+        for data in txt.txt_data() {
+            if let Ok(text) = std::str::from_utf8(data) {
+                if let Some(rest) = text.strip_prefix("veilid-app=") {
+                    key.get_or_insert_with(|| rest.to_string());
+                } else if let Some(rest) = text.strip_prefix("veilid-version=") {
+                    version.get_or_insert_with(|| rest.to_string());
                 }
             }
         }
     }
-    None
+    key.map(|key| (key, version))
 }
 
 #[tokio::main]
@@ -124,7 +190,54 @@ async fn main() -> Result<()> {
     info!("📡 Connecting to Veilid DHT...");
     
     // Initialize Veilid store
-    let store = Arc::new(tokio::sync::Mutex::new(VeilidStore::new().await?));
+    let transfer = TransferConfig {
+        download_concurrency: args.download_concurrency,
+        ..TransferConfig::default()
+    };
+    let mut store = VeilidStore::connect(KeyStore::open_default_unlocked()?, VeilidConfig::default()).await?
+        .with_transfer_config(transfer);
+    for site_key in &args.site_keys {
+        let (app_key, key) = site_key.rsplit_once('=')
+            .ok_or_else(|| anyhow::anyhow!("--site-key must look like <APP_KEY>=<SITE_KEY>, got {}", site_key))?;
+        store.add_site_key(&AppId(app_key.to_string()), key.to_string());
+        info!("🔑 Serving private site {}", app_key);
+    }
+    let store = Arc::new(tokio::sync::Mutex::new(store));
+
+    // Load the trust policy publisher keys are checked against
+    let action = if args.warn_untrusted { UntrustedAction::Warn } else { UntrustedAction::Refuse };
+    let trust_store = match &args.trust_store {
+        Some(path) => path.clone(),
+        None => TrustPolicy::default_path()?,
+    };
+    let mut trust = TrustPolicy::open(trust_store)?
+        .with_mode(args.trust_mode)
+        .with_action(action);
+    for pin in &args.pins {
+        let (app_key, key) = pin.rsplit_once('=')
+            .ok_or_else(|| anyhow::anyhow!("--pin must look like <APP_KEY>=<PUBLIC_KEY>[@<IDENTITY_KEY>], got {}", pin))?;
+        let (key, identity) = match key.split_once('@') {
+            Some((key, identity)) => (key, Some(identity)),
+            None => (key, None),
+        };
+        trust.pin(app_key, &key.parse()?, identity)?;
+    }
+    for pin in &args.policy_pins {
+        let usage = || anyhow::anyhow!("--pin-policy must look like <APP_KEY>=<THRESHOLD>:<PUBLIC_KEY>,..., got {}", pin);
+        let (app_key, policy) = pin.rsplit_once('=').ok_or_else(usage)?;
+        let (threshold, keys) = policy.split_once(':').ok_or_else(usage)?;
+        let policy = SignaturePolicy {
+            threshold: threshold.parse().map_err(|_| usage())?,
+            keys: keys.split(',').map(str::parse).collect::<roselite_core::Result<_>>()?,
+        };
+        trust.pin_policy(app_key, &policy)?;
+    }
+    if let Some(allowlist) = &args.allowlist {
+        let count = trust.load_allowlist(allowlist)?;
+        info!("📜 Loaded {} allowlisted publisher keys from {}", count, allowlist.display());
+    }
+    info!("🔏 Trust mode: {:?}", args.trust_mode);
+    let trust = Arc::new(tokio::sync::Mutex::new(trust));
     
     // Create cache directory
     let cache_dir = PathBuf::from(&args.cache_dir);
@@ -135,6 +248,7 @@ async fn main() -> Result<()> {
     // Create shared state
     let state = AppState {
         store,
+        trust,
         cache_dir,
         domain: args.domain.clone(),
     };
@@ -153,15 +267,7 @@ async fn main() -> Result<()> {
     }
     
     // Build our application with routes
-    let app = Router::new()
-        .route("/", get(handle_root_request))
-        .route("/*path", get(handle_path_request))
-        .layer(
-            ServiceBuilder::new()
-                .layer(CorsLayer::permissive())
-                .layer(CompressionLayer::new())
-        )
-        .with_state((state, cache));
+    let app = build_router(state, cache);
 
     // Start HTTP server
     let http_addr = format!("0.0.0.0:{}", args.port);
@@ -200,28 +306,41 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Build the gateway router on top of any DHT backend
+fn build_router<D: DhtBackend + 'static>(state: AppState<D>, cache: AppCache) -> Router {
+    Router::new()
+        .route("/", get(handle_root_request::<D>))
+        .route("/*path", get(handle_path_request::<D>))
+        .layer(
+            ServiceBuilder::new()
+                .layer(CorsLayer::permissive())
+                .layer(CompressionLayer::new())
+        )
+        .with_state((state, cache))
+}
+
 /// Handle root domain requests (show welcome page)
-async fn handle_root_request(
+async fn handle_root_request<D: DhtBackend + 'static>(
     Host(hostname): Host,
-    State((state, cache)): State<(AppState, AppCache)>,
+    State((state, cache)): State<(AppState<D>, AppCache)>,
 ) -> impl IntoResponse {
     handle_request_internal(hostname, String::new(), state, cache).await
 }
 
 /// Handle requests with paths
-async fn handle_path_request(
+async fn handle_path_request<D: DhtBackend + 'static>(
     Host(hostname): Host,
     Path(path): Path<String>,
-    State((state, cache)): State<(AppState, AppCache)>,
+    State((state, cache)): State<(AppState<D>, AppCache)>,
 ) -> impl IntoResponse {
     handle_request_internal(hostname, path, state, cache).await
 }
 
 /// Internal request handler
-async fn handle_request_internal(
+async fn handle_request_internal<D: DhtBackend>(
     hostname: String,
     path: String,
-    state: AppState,
+    state: AppState<D>,
     cache: AppCache,
 ) -> impl IntoResponse {
     debug!("📡 Request: {} -> /{}", hostname, path);
@@ -239,10 +358,13 @@ async fn handle_request_internal(
     info!("🎯 Serving domain: {} (path: /{})", domain, path);
     
     // Resolve domain to DHT key via DNS TXT
-    let dht_key = match lookup_dht_key(&domain).await {
-        Some(key) => {
+    let (dht_key, pinned_version) = match lookup_dht_key(&domain).await {
+        Some((key, version)) => {
             info!("✅ Resolved domain '{}' to DHT key '{}' via DNS TXT", domain, key);
-            key
+            if let Some(version) = &version {
+                info!("📌 Domain '{}' is pinned to version {}", domain, version);
+            }
+            (key, version)
         },
         None => {
             warn!("❌ No veilid-app TXT record found for domain: {}", domain);
@@ -255,7 +377,8 @@ async fn handle_request_internal(
         let cache_read = cache.read().await;
         if let Some(cached_app) = cache_read.get(&domain) {
             debug!("💾 Found app in cache: {}", domain);
-            return serve_static_file(&cached_app.extract_path, &path).await;
+            let response = serve_static_file(&cached_app.extract_path, &path).await;
+            return flag_response(response, cached_app.trust_flag);
         }
     }
     
@@ -271,26 +394,64 @@ async fn handle_request_internal(
     
     let store = state.store.lock().await;
     
-    // Create VeilUri from the DHT key
+    // Create VeilUri from the DHT key, falling back to the latest version when not pinned
     let app_id = AppId(dht_key.clone());
-    let uri = VeilUri::new(app_id, None);
+    let uri = VeilUri::new(app_id.clone(), pinned_version);
     
-    match store.download(&uri).await {
-        Ok(package) => {
-            info!("✅ Successfully downloaded package for domain: {}", domain);
+    let download = store.download_stream(&uri).await;
+    match download {
+        Ok(download) => {
+            let version = download.version.version.clone();
+            let signer = download.version.public_key;
             
-            // Extract package to cache directory
+            // Check the publisher key before any of the site reaches the cache
+            let flag = match check_publisher(&store, &state.trust, &app_id, signer.as_ref(), download.identity.as_deref()).await {
+                Ok(decision) => trust_flag(&decision),
+                Err(e) => {
+                    warn!("🚫 Refusing {}: {}", domain, e);
+                    return handle_untrusted_publisher(&domain, &e.to_string()).await;
+                }
+            };
+            if let Some(flag) = flag {
+                warn!("⚠️  Serving {} although its publisher key is {}", domain, flag);
+            }
+            
+            // Stream the package into a private directory as it downloads; the site only moves
+            // into the cache once every check has passed, so no request sees it half-verified
             let extract_path = state.cache_dir.join(&domain);
-            if let Err(e) = extract_package_to_cache(&package, &extract_path).await {
-                error!("❌ Failed to extract package {}: {}", domain, e);
+            let partial_path = partial_extract_path(&state.cache_dir, &domain);
+            if let Err(e) = extract_download_to_cache(download, &partial_path).await {
+                error!("❌ Failed to download and extract package {}: {}", domain, e);
                 return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to extract app").into_response();
             }
+            let manifest = match check_extracted_manifest(&partial_path, signer.as_ref()) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    error!("❌ Package for {} does not match its signed lookup record: {}", domain, e);
+                    let _ = std::fs::remove_dir_all(&partial_path);
+                    return handle_untrusted_publisher(&domain, &e.to_string()).await;
+                }
+            };
+            let flag = match check_signature_policy(&state.trust, &app_id, &manifest).await {
+                Ok(decision) => flag.or(trust_flag(&decision)),
+                Err(e) => {
+                    warn!("🚫 Refusing {}: {}", domain, e);
+                    let _ = std::fs::remove_dir_all(&partial_path);
+                    return handle_untrusted_publisher(&domain, &e.to_string()).await;
+                }
+            };
+            if let Err(e) = install_extracted(&partial_path, &extract_path) {
+                error!("❌ Failed to move package for {} into the cache: {}", domain, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to extract app").into_response();
+            }
+            info!("✅ Successfully downloaded package for domain: {} (version {})", domain, version);
             
             // Cache the app
             let cached_app = CachedApp {
-                package: package.clone(),
+                version,
                 extract_path: extract_path.clone(),
                 last_accessed: std::time::Instant::now(),
+                trust_flag: flag,
             };
             
             {
@@ -301,7 +462,7 @@ async fn handle_request_internal(
             info!("💾 Cached app: {}", domain);
             
             // Serve the requested file
-            serve_static_file(&extract_path, &path).await
+            flag_response(serve_static_file(&extract_path, &path).await, flag)
         }
         Err(e) => {
             error!("❌ Failed to fetch app {}: {}", domain, e);
@@ -364,6 +525,57 @@ async fn handle_domain_not_found(domain: &str) -> Response {
     (StatusCode::NOT_FOUND, Html(html)).into_response()
 }
 
+/// Handle a site whose publisher key is revoked or not trusted
+async fn handle_untrusted_publisher(domain: &str, reason: &str) -> Response {
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <title>Untrusted Publisher</title>
+    <style>
+        body {{
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', 'Roboto', sans-serif;
+            margin: 0;
+            padding: 20px;
+            background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+            color: #333;
+            min-height: 100vh;
+            display: flex;
+            align-items: center;
+            justify-content: center;
+        }}
+        .container {{
+            background: white;
+            padding: 40px;
+            border-radius: 10px;
+            box-shadow: 0 10px 30px rgba(0,0,0,0.2);
+            text-align: center;
+            max-width: 500px;
+        }}
+        h1 {{ color: #e74c3c; margin-bottom: 20px; }}
+        p {{ margin: 10px 0; line-height: 1.6; }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>🚫 Untrusted Publisher</h1>
+        <p>The site <strong>{}</strong> was not served because it is signed by a key this gateway does not trust.</p>
+        <p>{}</p>
+    </div>
+</body>
+</html>"#,
+        domain,
+        html_escape(reason)
+    );
+    
+    (StatusCode::FORBIDDEN, Html(html)).into_response()
+}
+
+/// Escape text for inclusion in an HTML page
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
 /// Handle app download failure
 async fn handle_app_not_found(domain: &str) -> Response {
     let html = format!(
@@ -411,7 +623,7 @@ async fn handle_app_not_found(domain: &str) -> Response {
 }
 
 /// Generate root response HTML
-async fn handle_root_response(state: &AppState) -> Response {
+async fn handle_root_response<D: DhtBackend>(state: &AppState<D>) -> Response {
     let html = format!(r#"
 <!DOCTYPE html>
 <html>
@@ -550,8 +762,117 @@ fn extract_domain_from_hostname(hostname: &str, domain: &str) -> Option<String>
     }
 }
 
-/// Extract package contents to cache directory
-async fn extract_package_to_cache(package: &Package, extract_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+/// Check the key a download was published with against the trust policy, after applying
+/// any rotations and revocations from the publisher's pinned identity record. The identity
+/// the download names is only pinned on first use; after that it is never read, so a site
+/// cannot hide a revocation by pointing somewhere else. Revoked and untrusted keys, and an
+/// identity record that cannot be read, are an error unless the gateway only warns about them.
+async fn check_publisher<D: DhtBackend>(
+    store: &VeilidStore<D>,
+    trust: &tokio::sync::Mutex<TrustPolicy>,
+    app_id: &AppId,
+    signer: Option<&PublicKey>,
+    identity: Option<&str>,
+) -> roselite_core::Result<TrustDecision> {
+    let mut policy = trust.lock().await;
+    let Some(signer) = signer else {
+        return policy.enforce(TrustDecision::Untrusted("the version is not signed".to_string()));
+    };
+    let identity = match policy.pinned(&app_id.0) {
+        Some(_) => policy.pinned_identity(&app_id.0).map(str::to_string),
+        // Publishers without an identity record name their public key instead
+        None if policy.mode() == TrustMode::Tofu => identity
+            .filter(|identity| identity.parse().ok().as_ref() != Some(signer))
+            .map(str::to_string),
+        None => None,
+    };
+    if let Some(identity) = &identity {
+        if let Err(e) = store.load_key_statements(identity, &mut policy).await {
+            return policy.enforce(TrustDecision::Untrusted(format!(
+                "the publisher's identity record {} could not be read: {}", identity, e
+            )));
+        }
+    }
+    let decision = policy.check(&app_id.0, signer)?;
+    if decision == TrustDecision::FirstUse && identity.is_some() {
+        policy.pin(&app_id.0, signer, identity.as_deref())?;
+    }
+    policy.enforce(decision)
+}
+
+/// Check the signature policy of a release against the one pinned for the app, so a single
+/// maintainer cannot publish a release that drops or weakens it. A weaker policy is an error
+/// unless the gateway only warns about untrusted sites.
+async fn check_signature_policy(
+    trust: &tokio::sync::Mutex<TrustPolicy>,
+    app_id: &AppId,
+    manifest: &PackageManifest,
+) -> roselite_core::Result<TrustDecision> {
+    let mut policy = trust.lock().await;
+    let decision = policy.check_policy(&app_id.0, manifest.signature_policy.as_ref())?;
+    policy.enforce(decision)
+}
+
+/// Short reason a flagged site is served with, for the `x-roselite-trust` header
+fn trust_flag(decision: &TrustDecision) -> Option<&'static str> {
+    match decision {
+        TrustDecision::Revoked(_) => Some("revoked"),
+        TrustDecision::Untrusted(_) => Some("untrusted"),
+        TrustDecision::Trusted | TrustDecision::FirstUse => None,
+    }
+}
+
+/// Mark a response from a flagged publisher so clients can warn about it
+fn flag_response(mut response: Response, flag: Option<&'static str>) -> Response {
+    if let Some(flag) = flag {
+        response.headers_mut().insert("x-roselite-trust", axum::http::HeaderValue::from_static(flag));
+    }
+    response
+}
+
+/// Make sure the extracted manifest declares the key the lookup record was signed with, and
+/// that the extracted files carry the signatures the manifest asks for. This is the streamed
+/// counterpart of `PublishedVersion::check_package`.
+fn check_extracted_manifest(extract_path: &std::path::Path, signer: Option<&PublicKey>) -> roselite_core::Result<PackageManifest> {
+    let manifest: PackageManifest =
+        serde_json::from_slice(&std::fs::read(extract_path.join(roselite_core::MANIFEST_FILENAME))?)?;
+    if manifest.public_key.is_none() || manifest.public_key.as_ref() != signer {
+        let text = |key: Option<&PublicKey>| key.map_or_else(|| "no key".to_string(), |key| key.to_string());
+        return Err(CryptoError::PublisherKeyMismatch {
+            signer: text(signer),
+            manifest: text(manifest.public_key.as_ref()),
+        }.into());
+    }
+    if !Package::verify_extracted(&CryptoManager::new()?, extract_path)? {
+        return Err(CryptoError::SignatureVerificationFailed.into());
+    }
+    Ok(manifest)
+}
+
+/// Blocking reader over package chunks arriving from the async download
+struct ChunkReader {
+    chunks: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    current: std::io::Cursor<Vec<u8>>,
+}
+
+impl std::io::Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.current.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            match self.chunks.blocking_recv() {
+                Some(chunk) => self.current = std::io::Cursor::new(chunk),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+/// Stream a package download through extraction into the cache directory.
+/// Only a few chunks are held in memory at a time, however large the site is.
+async fn extract_download_to_cache(download: PackageDownload<'_>, extract_path: &PathBuf) -> Result<()> {
     // Remove existing directory if it exists
     if extract_path.exists() {
         std::fs::remove_dir_all(extract_path)?;
@@ -560,15 +881,66 @@ async fn extract_package_to_cache(package: &Package, extract_path: &PathBuf) ->
     // Create the directory
     std::fs::create_dir_all(extract_path)?;
     
-    // Extract the archive contents - use package.content which contains the gzipped tar
-    use flate2::read::GzDecoder;
-    use std::io::Cursor;
+    // Decompress and unpack on a blocking thread while chunks are fed to it over a bounded channel
+    let (sender, receiver) = tokio::sync::mpsc::channel(4);
+    let unpack_path = extract_path.clone();
+    let unpacker = tokio::task::spawn_blocking(move || {
+        let reader = ChunkReader { chunks: receiver, current: std::io::Cursor::new(Vec::new()) };
+        roselite_core::archive::unpack(reader, &unpack_path)
+    });
     
-    let decoder = GzDecoder::new(Cursor::new(&package.content));
-    let mut archive = tar::Archive::new(decoder);
-    archive.unpack(extract_path)?;
+    let mut chunks = download.chunks;
+    let mut streamed = Ok(());
+    while let Some(chunk) = chunks.next().await {
+        match chunk {
+            Ok(chunk) => {
+                // The unpacker stopped early; its own error is reported below
+                if sender.send(chunk).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                streamed = Err(e);
+                break;
+            }
+        }
+    }
+    drop(sender);
     
-    Ok(())
+    let unpacked = unpacker.await?;
+    let result = streamed.map_err(anyhow::Error::from)
+        .and_then(|_| unpacked.map_err(anyhow::Error::from));
+    
+    // Never leave a partially extracted app behind to be served from the cache
+    if result.is_err() {
+        let _ = std::fs::remove_dir_all(extract_path);
+    }
+    
+    result
+}
+
+/// Unique directory next to the cache entry for `domain` to extract a download into. The
+/// leading dot keeps it from ever matching a domain.
+fn partial_extract_path(cache_dir: &std::path::Path, domain: &str) -> PathBuf {
+    static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let n = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    cache_dir.join(format!(".{}.partial-{}-{}", domain, std::process::id(), n))
+}
+
+/// Move a verified extraction into its cache entry. If a concurrent request installed the
+/// same site first, its copy is kept and ours is discarded.
+fn install_extracted(partial_path: &std::path::Path, extract_path: &std::path::Path) -> Result<()> {
+    match std::fs::rename(partial_path, extract_path) {
+        Ok(()) => Ok(()),
+        Err(_) if extract_path.is_dir() => {
+            let _ = std::fs::remove_dir_all(partial_path);
+            Ok(())
+        }
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(partial_path);
+            Err(e.into())
+        }
+    }
 }
 
 /// Serve static file from extracted app
@@ -669,6 +1041,13 @@ email = "{email}"
 #[cfg(test)]
 mod tests {
     use super::*;
+    use roselite_core::{
+        crypto::CryptoManager,
+        dht::MemoryDht,
+        identity::{Identity, KeyStatement},
+        package::PackageBuilder,
+        RoseliteError,
+    };
 
     #[test]
     fn test_extract_domain_from_hostname() {
@@ -690,4 +1069,147 @@ mod tests {
         assert_eq!(extract_domain_from_hostname("my-app.roselite.app", prod_domain), Some("my-app".to_string()));
         assert_eq!(extract_domain_from_hostname("roselite.app", prod_domain), None);
     }
+
+    #[tokio::test]
+    async fn test_extract_download_to_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("site");
+        std::fs::create_dir_all(source_dir.join("assets")).unwrap();
+        std::fs::write(source_dir.join("index.html"), b"<html><body>Streamed</body></html>").unwrap();
+        std::fs::write(source_dir.join("assets").join("app.js"), b"console.log('hi');").unwrap();
+
+        let package = PackageBuilder::new("stream-test".to_string(), &source_dir)
+            .version("1.0.0".to_string())
+            .build()
+            .await
+            .unwrap();
+
+        let mut store = VeilidStore::with_backend(MemoryDht::new());
+        let (uri, _) = store.publish(package).await.unwrap();
+
+        let extract_path = temp_dir.path().join("cache").join("stream-test");
+        let download = store.download_stream(&uri).await.unwrap();
+        extract_download_to_cache(download, &extract_path).await.unwrap();
+
+        assert_eq!(std::fs::read(extract_path.join("index.html")).unwrap(), b"<html><body>Streamed</body></html>");
+        assert_eq!(std::fs::read(extract_path.join("assets").join("app.js")).unwrap(), b"console.log('hi');");
+        let manifest = check_extracted_manifest(&extract_path, Some(&download_signer(&store, &uri).await)).unwrap();
+        let someone_else = CryptoManager::new().unwrap().generate_keypair().unwrap().0;
+        assert!(matches!(
+            check_extracted_manifest(&extract_path, Some(&someone_else)),
+            Err(RoseliteError::Crypto(CryptoError::PublisherKeyMismatch { .. }))
+        ));
+        assert!(check_extracted_manifest(&extract_path, None).is_err());
+
+        // A release without the signature policy pinned for the app is refused, or flagged when warning
+        let policy = SignaturePolicy { threshold: 2, keys: vec![download_signer(&store, &uri).await, someone_else] };
+        let pinned = |action| {
+            let mut trust = TrustPolicy::in_memory().with_action(action);
+            trust.pin_policy(&uri.app_id.0, &policy).unwrap();
+            tokio::sync::Mutex::new(trust)
+        };
+        assert!(check_signature_policy(&pinned(UntrustedAction::Refuse), &uri.app_id, &manifest).await.is_err());
+        let decision = check_signature_policy(&pinned(UntrustedAction::Warn), &uri.app_id, &manifest).await.unwrap();
+        assert_eq!(trust_flag(&decision), Some("untrusted"));
+        let tofu = tokio::sync::Mutex::new(TrustPolicy::in_memory());
+        assert_eq!(check_signature_policy(&tofu, &uri.app_id, &manifest).await.unwrap(), TrustDecision::Trusted);
+
+        // Files that no longer match the package signature are refused
+        std::fs::write(extract_path.join("index.html"), b"<html>tampered</html>").unwrap();
+        assert!(matches!(
+            check_extracted_manifest(&extract_path, Some(&download_signer(&store, &uri).await)),
+            Err(RoseliteError::Crypto(CryptoError::SignatureVerificationFailed))
+        ));
+    }
+
+    #[test]
+    fn test_install_extracted_keeps_first_copy() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let cache_dir = temp_dir.path().join("cache");
+        let extract_path = cache_dir.join("example");
+
+        // Partial extractions never collide and stay out of the entry until installed
+        let first = partial_extract_path(&cache_dir, "example");
+        let second = partial_extract_path(&cache_dir, "example");
+        assert_ne!(first, second);
+        for (path, body) in [(&first, "first"), (&second, "second")] {
+            std::fs::create_dir_all(path).unwrap();
+            std::fs::write(path.join("index.html"), body).unwrap();
+        }
+        assert!(!extract_path.exists());
+
+        install_extracted(&first, &extract_path).unwrap();
+        assert!(!first.exists());
+        install_extracted(&second, &extract_path).unwrap();
+        assert!(!second.exists());
+        assert_eq!(std::fs::read_to_string(extract_path.join("index.html")).unwrap(), "first");
+    }
+
+    async fn download_signer(store: &VeilidStore<MemoryDht>, uri: &VeilUri) -> PublicKey {
+        store.download_stream(uri).await.unwrap().version.public_key.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_check_publisher_against_trust_policy() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("index.html"), b"<html></html>").unwrap();
+        let package = PackageBuilder::new("trusted".to_string(), temp_dir.path())
+            .build()
+            .await
+            .unwrap();
+
+        let mut store = VeilidStore::with_backend(MemoryDht::new());
+        let (uri, _) = store.publish(package).await.unwrap();
+        let download = store.download_stream(&uri).await.unwrap();
+        let (signer, identity) = (download.version.public_key.as_ref(), download.identity.as_deref());
+
+        // Trust on first use pins the publisher
+        let tofu = tokio::sync::Mutex::new(TrustPolicy::in_memory());
+        assert_eq!(check_publisher(&store, &tofu, &uri.app_id, signer, identity).await.unwrap(), TrustDecision::FirstUse);
+        assert_eq!(check_publisher(&store, &tofu, &uri.app_id, signer, identity).await.unwrap(), TrustDecision::Trusted);
+
+        // Nothing is pinned, so a pinned-only gateway refuses the site, or flags it when warning
+        let pinned = tokio::sync::Mutex::new(TrustPolicy::in_memory().with_mode(TrustMode::Pinned));
+        assert!(check_publisher(&store, &pinned, &uri.app_id, signer, identity).await.is_err());
+        let warning = tokio::sync::Mutex::new(
+            TrustPolicy::in_memory().with_mode(TrustMode::Pinned).with_action(UntrustedAction::Warn)
+        );
+        let decision = check_publisher(&store, &warning, &uri.app_id, signer, identity).await.unwrap();
+        assert_eq!(trust_flag(&decision), Some("untrusted"));
+    }
+
+    #[tokio::test]
+    async fn test_check_publisher_reads_only_the_pinned_identity() {
+        let mut store = VeilidStore::with_backend(MemoryDht::new());
+        let identity = Identity::generate("work").unwrap();
+        let (rotated, endorsement) = identity.rotated().unwrap();
+        let record_key = store.publish_key_statement(None, KeyStatement::Rotation(endorsement)).await.unwrap();
+        let app_id = AppId("VLD0:app".to_string());
+        let signer = Some(&rotated.public_key);
+
+        // First use pins the identity record the site names along with its key
+        let trust = tokio::sync::Mutex::new(TrustPolicy::in_memory());
+        assert_eq!(check_publisher(&store, &trust, &app_id, signer, Some(&record_key)).await.unwrap(), TrustDecision::FirstUse);
+        assert_eq!(trust.lock().await.pinned_identity(&app_id.0), Some(record_key.as_str()));
+
+        // A revocation in the pinned record applies whatever identity the site names now
+        let revocation = rotated.revocation(None, Some("compromised".to_string())).unwrap();
+        store.publish_key_statement(Some(&record_key), KeyStatement::Revocation(revocation)).await.unwrap();
+        for named in [Some("VLD0:elsewhere"), None] {
+            assert!(matches!(
+                check_publisher(&store, &trust, &app_id, signer, named).await,
+                Err(RoseliteError::Crypto(CryptoError::KeyRevoked(_)))
+            ));
+        }
+
+        // A pinned identity record that cannot be read refuses the site, or flags it when warning
+        let unreadable = |action| {
+            let mut policy = TrustPolicy::in_memory().with_action(action);
+            policy.pin(&app_id.0, &rotated.public_key, Some("VLD0:missing")).unwrap();
+            tokio::sync::Mutex::new(policy)
+        };
+        assert!(check_publisher(&store, &unreadable(UntrustedAction::Refuse), &app_id, signer, Some(&record_key)).await.is_err());
+        let decision = check_publisher(&store, &unreadable(UntrustedAction::Warn), &app_id, signer, Some(&record_key)).await.unwrap();
+        assert_eq!(trust_flag(&decision), Some("untrusted"));
+    }
 } 
\ No newline at end of file